@@ -0,0 +1,43 @@
+// Evaluates the same expressions over and over, with and without a cache of results
+// Run with `cargo bench --bench cache`
+
+use std::time::{Duration, Instant};
+
+use basic_arithmetic_calculator::ExpressionEvaluator;
+
+const EXPRESSIONS: &[&str] = &[
+    "2+3*4",
+    "(12+3)*|4-10|/2^2",
+    "if(3>2,powmod(7,128,13),0)",
+    "abs(-5)*(1+2)*(3+4)-100/7",
+];
+const REPEATS: usize = 2_000;
+
+fn measure(evaluator: &ExpressionEvaluator) -> Duration {
+    let start = Instant::now();
+
+    for _ in 0..REPEATS {
+        for expr in EXPRESSIONS {
+            evaluator.eval(expr.to_string()).unwrap();
+        }
+    }
+
+    start.elapsed()
+}
+
+fn main() {
+    let uncached = ExpressionEvaluator::new().unwrap();
+    let cached = ExpressionEvaluator::builder()
+        .cache_size(EXPRESSIONS.len())
+        .build()
+        .unwrap();
+
+    let without = measure(&uncached);
+    let with = measure(&cached);
+
+    println!(
+        "{} evaluations: {without:?} without a cache, {with:?} with one, {:.1}x faster",
+        REPEATS * EXPRESSIONS.len(),
+        without.as_secs_f64() / with.as_secs_f64()
+    );
+}
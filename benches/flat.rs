@@ -0,0 +1,35 @@
+// Evaluates expressions without parentheses, and the same ones nested in parentheses, which have to go through every pass
+// Run with `cargo bench --bench flat`
+
+use std::time::{Duration, Instant};
+
+use basic_arithmetic_calculator::ExpressionEvaluator;
+
+const EXPRESSIONS: &[&str] = &["2+3*4-10/5+6*7", "1-2-3", "-2*-3+8/2/2", "2^3^2-1"];
+const REPEATS: usize = 2_000;
+
+fn measure(evaluator: &ExpressionEvaluator, wrap: impl Fn(&str) -> String) -> Duration {
+    let expressions: Vec<String> = EXPRESSIONS.iter().map(|expr| wrap(expr)).collect();
+    let start = Instant::now();
+
+    for _ in 0..REPEATS {
+        for expr in &expressions {
+            evaluator.eval(expr.clone()).unwrap();
+        }
+    }
+
+    start.elapsed()
+}
+
+fn main() {
+    let evaluator = ExpressionEvaluator::new().unwrap();
+
+    let flat = measure(&evaluator, str::to_string);
+    let nested = measure(&evaluator, |expr| format!("0+({expr})"));
+
+    println!(
+        "{} evaluations: {flat:?} flat, {nested:?} nested, {:.1}x faster without parentheses",
+        REPEATS * EXPRESSIONS.len(),
+        nested.as_secs_f64() / flat.as_secs_f64()
+    );
+}
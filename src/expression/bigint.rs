@@ -0,0 +1,353 @@
+use std::{cmp::Ordering, fmt};
+
+use anyhow::anyhow;
+
+use super::CalcNumber;
+
+// Largest power of ten fitting a limb, numbers are written out and read in chunks of this many digits
+const DECIMAL_CHUNK: u64 = 1_000_000_000;
+const DECIMAL_CHUNK_DIGITS: usize = 9;
+// Results of more limbs than this are treated as an overflow, so a huge power can't exhaust the memory
+const MAX_LIMBS: usize = 1 << 12;
+
+/// Arbitrary precision integer, see `ExpressionEvaluator::eval_bigint`
+///
+/// Division truncates toward zero, like for `i64`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    // Base 2^32 digits, least significant first, without leading zeros, so zero has none
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+
+        BigInt {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+
+    fn checked(self) -> Option<BigInt> {
+        (self.magnitude.len() <= MAX_LIMBS).then_some(self)
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        let magnitude = value.unsigned_abs();
+
+        BigInt::new(value < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut magnitude = self.magnitude.clone();
+        let mut chunks = Vec::new();
+
+        while !magnitude.is_empty() {
+            chunks.push(divide_small(&mut magnitude, DECIMAL_CHUNK as u32));
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+
+        match chunks.split_last() {
+            Some((first, rest)) => {
+                write!(f, "{first}")?;
+
+                for chunk in rest.iter().rev() {
+                    write!(f, "{chunk:0width$}", width = DECIMAL_CHUNK_DIGITS)?;
+                }
+
+                Ok(())
+            }
+            None => write!(f, "0"),
+        }
+    }
+}
+
+impl CalcNumber for BigInt {
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+
+        if digits.is_empty() || !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+            return Err(anyhow!("invalid integer '{text}'"));
+        }
+
+        let mut magnitude = Vec::new();
+        let first_chunk = match digits.len() % DECIMAL_CHUNK_DIGITS {
+            0 => DECIMAL_CHUNK_DIGITS,
+            length => length,
+        };
+        let mut start = 0;
+        let mut end = first_chunk;
+
+        while start < digits.len() {
+            let chunk = &digits[start..end];
+
+            multiply_add_small(
+                &mut magnitude,
+                10u32.pow(chunk.len() as u32),
+                chunk.parse()?,
+            );
+
+            start = end;
+            end += DECIMAL_CHUNK_DIGITS;
+        }
+
+        Ok(BigInt::new(negative, magnitude))
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add(&self.magnitude, &other.magnitude)).checked();
+        }
+
+        // Opposite signs, so the smaller magnitude is taken from the larger one, which decides the sign
+        match compare(&self.magnitude, &other.magnitude) {
+            Ordering::Less => Some(BigInt::new(
+                other.negative,
+                subtract(&other.magnitude, &self.magnitude),
+            )),
+            _ => Some(BigInt::new(
+                self.negative,
+                subtract(&self.magnitude, &other.magnitude),
+            )),
+        }
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        let negated = BigInt::new(!other.negative, other.magnitude);
+
+        self.checked_add(negated)
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        if self.magnitude.len() + other.magnitude.len() > MAX_LIMBS + 1 {
+            return None;
+        }
+
+        BigInt::new(
+            self.negative != other.negative,
+            multiply(&self.magnitude, &other.magnitude),
+        )
+        .checked()
+    }
+
+    fn checked_div(self, other: Self) -> Option<Self> {
+        if other.magnitude.is_empty() {
+            return None;
+        }
+
+        Some(BigInt::new(
+            self.negative != other.negative,
+            divide(&self.magnitude, &other.magnitude),
+        ))
+    }
+
+    fn checked_pow(self, exponent: u32) -> Option<Self> {
+        let mut result = BigInt::from(1);
+        let mut base = self;
+        let mut exponent = exponent;
+
+        while exponent > 0 {
+            if exponent % 2 == 1 {
+                result = result.checked_mul(base.clone())?;
+            }
+
+            exponent /= 2;
+
+            if exponent > 0 {
+                base = base.clone().checked_mul(base)?;
+            }
+        }
+
+        Some(result)
+    }
+}
+
+fn compare(first: &[u32], second: &[u32]) -> Ordering {
+    first
+        .len()
+        .cmp(&second.len())
+        .then_with(|| first.iter().rev().cmp(second.iter().rev()))
+}
+
+fn add(first: &[u32], second: &[u32]) -> Vec<u32> {
+    let mut sum = Vec::with_capacity(first.len().max(second.len()) + 1);
+    let mut carry = 0;
+
+    for index in 0..first.len().max(second.len()) {
+        let digit = u64::from(first.get(index).copied().unwrap_or(0))
+            + u64::from(second.get(index).copied().unwrap_or(0))
+            + carry;
+
+        sum.push(digit as u32);
+        carry = digit >> 32;
+    }
+
+    sum.push(carry as u32);
+    sum
+}
+
+// The first magnitude can't be smaller than the second one
+fn subtract(first: &[u32], second: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(first.len());
+    let mut borrow = false;
+
+    for (index, &digit) in first.iter().enumerate() {
+        let (digit, borrowed) = digit.overflowing_sub(second.get(index).copied().unwrap_or(0));
+        let (digit, borrowed_again) = digit.overflowing_sub(u32::from(borrow));
+
+        difference.push(digit);
+        borrow = borrowed || borrowed_again;
+    }
+
+    difference
+}
+
+fn multiply(first: &[u32], second: &[u32]) -> Vec<u32> {
+    let mut product = vec![0; first.len() + second.len()];
+
+    for (i, &a) in first.iter().enumerate() {
+        let mut carry = 0;
+
+        for (j, &b) in second.iter().enumerate() {
+            let digit = u64::from(a) * u64::from(b) + u64::from(product[i + j]) + carry;
+
+            product[i + j] = digit as u32;
+            carry = digit >> 32;
+        }
+
+        product[i + second.len()] = carry as u32;
+    }
+
+    product
+}
+
+// Binary long division, only the quotient is kept
+fn divide(dividend: &[u32], divisor: &[u32]) -> Vec<u32> {
+    let mut quotient = vec![0; dividend.len()];
+    let mut remainder = Vec::new();
+
+    for bit in (0..dividend.len() * 32).rev() {
+        remainder = add(&remainder, &remainder);
+        remainder[0] |= (dividend[bit / 32] >> (bit % 32)) & 1;
+
+        while remainder.last() == Some(&0) {
+            remainder.pop();
+        }
+
+        if compare(&remainder, divisor) != Ordering::Less {
+            remainder = subtract(&remainder, divisor);
+
+            while remainder.last() == Some(&0) {
+                remainder.pop();
+            }
+
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+
+    quotient
+}
+
+// Divides the magnitude in place and returns the remainder, leading zeros are removed
+fn divide_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+
+    for digit in magnitude.iter_mut().rev() {
+        let current = (remainder << 32) | u64::from(*digit);
+
+        *digit = (current / u64::from(divisor)) as u32;
+        remainder = current % u64::from(divisor);
+    }
+
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+
+    remainder as u32
+}
+
+fn multiply_add_small(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = u64::from(addend);
+
+    for digit in magnitude.iter_mut() {
+        let current = u64::from(*digit) * u64::from(factor) + carry;
+
+        *digit = current as u32;
+        carry = current >> 32;
+    }
+
+    if carry > 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(text: &str) -> BigInt {
+        BigInt::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parses_and_prints() {
+        for text in [
+            "0",
+            "7",
+            "-7",
+            "4294967296",
+            "1000000000",
+            "-123456789012345678901234567890",
+        ] {
+            assert_eq!(big(text).to_string(), text);
+        }
+
+        assert_eq!(big("-0").to_string(), "0");
+        assert_eq!(big("000042").to_string(), "42");
+        assert_eq!(BigInt::from(i64::MIN).to_string(), i64::MIN.to_string());
+        assert!(BigInt::parse("").is_err());
+        assert!(BigInt::parse("-").is_err());
+        assert!(BigInt::parse("1.5").is_err());
+    }
+
+    #[test]
+    fn calculates() {
+        let max = BigInt::from(i64::MAX);
+
+        assert_eq!(
+            max.clone().checked_add(big("1")).unwrap(),
+            big("9223372036854775808")
+        );
+        assert_eq!(
+            max.clone().checked_mul(max.clone()).unwrap(),
+            big("85070591730234615847396907784232501249")
+        );
+        assert_eq!(big("5").checked_sub(big("8")).unwrap(), big("-3"));
+        assert_eq!(big("-5").checked_add(big("5")).unwrap(), big("0"));
+        assert_eq!(big("-7").checked_div(big("2")).unwrap(), big("-3"));
+        assert_eq!(
+            big("85070591730234615847396907784232501249")
+                .checked_div(max)
+                .unwrap(),
+            BigInt::from(i64::MAX)
+        );
+        assert_eq!(big("1").checked_div(big("0")), None);
+        assert_eq!(big("-2").checked_pow(3).unwrap(), big("-8"));
+        assert_eq!(big("3").checked_pow(0).unwrap(), big("1"));
+        assert_eq!(big("2").checked_pow(u32::MAX), None);
+    }
+}
@@ -0,0 +1,120 @@
+use super::ExpressionEvaluator;
+use crate::Config;
+
+#[derive(Default)]
+pub struct ExpressionEvaluatorBuilder {
+    config: Config,
+}
+
+// Starts from an existing configuration, so only the differences have to be set
+impl From<Config> for ExpressionEvaluatorBuilder {
+    fn from(config: Config) -> Self {
+        ExpressionEvaluatorBuilder { config }
+    }
+}
+
+impl ExpressionEvaluatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn wrap_bits(mut self, bits: u32) -> Self {
+        self.config.wrap_bits = Some(bits);
+        self
+    }
+
+    pub fn max_terms(mut self, max_terms: usize) -> Self {
+        self.config.max_terms = Some(max_terms);
+        self
+    }
+
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.config.max_length = Some(max_length);
+        self
+    }
+
+    pub fn cache_size(mut self, capacity: usize) -> Self {
+        self.config.cache_size = Some(capacity);
+        self
+    }
+
+    pub fn strict_division(mut self, enabled: bool) -> Self {
+        self.config.strict_division = enabled;
+        self
+    }
+
+    pub fn divide_promotes_to_float(mut self, enabled: bool) -> Self {
+        self.config.divide_promotes_to_float = enabled;
+        self
+    }
+
+    pub fn float_mode(mut self, enabled: bool) -> Self {
+        self.config.float_mode = enabled;
+        self
+    }
+
+    pub fn decimal_separator(mut self, separator: char) -> Self {
+        self.config.decimal_separator = Some(separator);
+        self
+    }
+
+    pub fn radix_suffixes(mut self, enabled: bool) -> Self {
+        self.config.radix_suffixes = enabled;
+        self
+    }
+
+    pub fn magnitude_suffixes(mut self, enabled: bool) -> Self {
+        self.config.magnitude_suffixes = enabled;
+        self
+    }
+
+    pub fn time_units(mut self, enabled: bool) -> Self {
+        self.config.time_units = enabled;
+        self
+    }
+
+    pub fn require_operator_spaces(mut self, enabled: bool) -> Self {
+        self.config.require_operator_spaces = enabled;
+        self
+    }
+
+    pub fn require_full_parens(mut self, enabled: bool) -> Self {
+        self.config.require_full_parens = enabled;
+        self
+    }
+
+    pub fn allow_double_negation(mut self, enabled: bool) -> Self {
+        self.config.allow_double_negation = enabled;
+        self
+    }
+
+    pub fn group_output_digits(mut self, enabled: bool) -> Self {
+        self.config.group_output_digits = enabled;
+        self
+    }
+
+    pub fn digit_separator(mut self, separator: char) -> Self {
+        self.config.digit_separator = Some(separator);
+        self
+    }
+
+    pub fn decimal_output(mut self, enabled: bool) -> Self {
+        self.config.decimal_output = enabled;
+        self
+    }
+
+    pub fn roman_output(mut self, enabled: bool) -> Self {
+        self.config.roman_output = enabled;
+        self
+    }
+
+    pub fn scientific_digits(mut self, digits: u32) -> Self {
+        self.config.scientific_digits = Some(digits);
+        self
+    }
+
+    // Validates the options, and compiles the patterns they need
+    pub fn build(self) -> anyhow::Result<ExpressionEvaluator> {
+        ExpressionEvaluator::with_config(self.config)
+    }
+}
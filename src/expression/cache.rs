@@ -0,0 +1,99 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::Evaluation;
+
+// Evaluations of the most recently evaluated expressions, the one used the longest time ago is forgotten first
+pub(super) struct ResultCache {
+    capacity: usize,
+    // With the time of the last use, which is also the key of the expression in the order of use
+    entries: HashMap<String, (Evaluation, u64)>,
+    order: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+impl ResultCache {
+    pub(super) fn new(capacity: usize) -> ResultCache {
+        ResultCache {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub(super) fn get(&mut self, key: &str) -> Option<Evaluation> {
+        let (evaluation, last_used) = self.entries.get_mut(key)?;
+        let key = self.order.remove(last_used)?;
+
+        self.clock += 1;
+        *last_used = self.clock;
+        self.order.insert(self.clock, key);
+
+        Some(evaluation.clone())
+    }
+
+    pub(super) fn insert(&mut self, key: String, evaluation: Evaluation) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let Some((_, last_used)) = self.entries.remove(&key) {
+            self.order.remove(&last_used);
+        } else if self.entries.len() == self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.clock += 1;
+        self.order.insert(self.clock, key.clone());
+        self.entries.insert(key, (evaluation, self.clock));
+    }
+
+    #[cfg(test)]
+    pub(super) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OpStats, Value};
+
+    fn evaluation(value: i64) -> Evaluation {
+        Evaluation {
+            value: Value::Int(value),
+            truncated: false,
+            operations: 0,
+            max_magnitude: 0,
+            op_stats: OpStats::default(),
+            literal_base: None,
+        }
+    }
+
+    #[test]
+    fn forgets_least_recently_used() {
+        let mut cache = ResultCache::new(2);
+        let value =
+            |cache: &mut ResultCache, key| cache.get(key).map(|evaluation| evaluation.value);
+
+        cache.insert("1+1".to_string(), evaluation(2));
+        cache.insert("2+2".to_string(), evaluation(4));
+
+        assert_eq!(value(&mut cache, "1+1"), Some(Value::Int(2)));
+
+        cache.insert("3+3".to_string(), evaluation(6));
+
+        assert_eq!(value(&mut cache, "2+2"), None);
+        assert_eq!(value(&mut cache, "1+1"), Some(Value::Int(2)));
+        assert_eq!(value(&mut cache, "3+3"), Some(Value::Int(6)));
+        assert_eq!(cache.len(), 2);
+
+        let mut disabled = ResultCache::new(0);
+
+        disabled.insert("1+1".to_string(), evaluation(2));
+
+        assert_eq!(disabled.len(), 0);
+    }
+}
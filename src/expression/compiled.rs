@@ -0,0 +1,245 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+#[cfg(feature = "serialize")]
+use anyhow::anyhow;
+
+use super::{solver, unknown_name, ExpressionEvaluator, Value, Variables};
+
+// Evaluator of the expressions compiled without one, created on the first use
+static SHARED_EVALUATOR: OnceLock<ExpressionEvaluator> = OnceLock::new();
+
+// Where the value of a variable goes in the template, the validated form can't have it
+const VALUE_SLOT: char = '\0';
+
+// First byte of the serialized form, so a change of the format can be told apart from corrupted bytes
+#[cfg(feature = "serialize")]
+const FORMAT_VERSION: u8 = 2;
+
+/// Expression validated once, so it can be evaluated with many values of its variables cheaply
+///
+/// Created by `ExpressionEvaluator::compile`, or with the default configuration by `TryFrom<&str>`.
+pub struct CompiledExpression<'a> {
+    evaluator: &'a ExpressionEvaluator,
+    source: String,
+    template: Template,
+}
+
+// The form the solver works on, apart from the values of the variables
+// Constants are already substituted and exponentiations rewritten, so only the values are left to fill in for an evaluation
+struct Template {
+    // Text around the values, there is one more part than variables
+    parts: Vec<String>,
+    variables: Vec<String>,
+}
+
+impl Template {
+    fn new(evaluator: &ExpressionEvaluator, validated: &str) -> anyhow::Result<Template> {
+        let mut variables = Vec::new();
+        let mut substituted = String::new();
+        let mut expr = String::new();
+
+        evaluator.substitute_names(
+            validated,
+            |name| match evaluator.constant(name) {
+                Some(value) => Ok(value.to_expression_text()),
+                None => {
+                    variables.push(name.to_string());
+                    Ok(VALUE_SLOT.to_string())
+                }
+            },
+            &mut substituted,
+        )?;
+        // A variable is parenthesized like its value would be, so it is a base or exponent the same way
+        solver::replace_exponentiations(
+            solver::strip_enclosing_parentheses(&substituted),
+            &mut expr,
+        )?;
+
+        Ok(Template {
+            parts: expr.split(VALUE_SLOT).map(str::to_string).collect(),
+            variables,
+        })
+    }
+
+    fn fill(&self, variables: &Variables) -> anyhow::Result<String> {
+        let mut expr = self.parts[0].clone();
+
+        for (name, part) in self.variables.iter().zip(&self.parts[1..]) {
+            let value = variables.get(name).ok_or_else(|| unknown_name(name))?;
+
+            expr.push_str(&value.to_expression_text());
+            expr.push_str(part);
+        }
+
+        Ok(expr)
+    }
+}
+
+impl<'a> CompiledExpression<'a> {
+    pub(super) fn new(
+        evaluator: &'a ExpressionEvaluator,
+        source: &str,
+        validated: String,
+    ) -> anyhow::Result<CompiledExpression<'a>> {
+        Ok(CompiledExpression {
+            evaluator,
+            source: source.to_string(),
+            template: Template::new(evaluator, &validated)?,
+        })
+    }
+
+    pub fn eval(&self, vars: &HashMap<String, i64>) -> anyhow::Result<i64> {
+        let variables: Variables = vars
+            .iter()
+            .map(|(name, value)| (name.clone(), Value::Int(*value)))
+            .collect();
+
+        self.eval_with_variables(&variables)
+    }
+
+    pub fn eval_with_variables(&self, variables: &Variables) -> anyhow::Result<i64> {
+        self.evaluator
+            .solve_rewritten(
+                &mut self.template.fill(variables)?,
+                &self.source,
+                false,
+                variables,
+                Default::default(),
+                None,
+            )?
+            .value
+            .to_i64()
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<'a> CompiledExpression<'a> {
+    /// Compact form to be stored, for example on disk, and loaded with `from_bytes`
+    ///
+    /// It is the format version, then the source of the expression as a little endian `u32` length followed by that many bytes of UTF-8.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![FORMAT_VERSION];
+
+        bytes.extend_from_slice(&(self.source.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.source.as_bytes());
+
+        bytes
+    }
+
+    /// Loads an expression stored with `to_bytes`, by compiling its source with the evaluator
+    ///
+    /// Only the source is stored, as it is validated again on purpose: the evaluator can have another configuration than the one it was compiled with, and stored bytes can't be trusted.
+    /// So an evaluator without `radix_suffixes` fails for the bytes of `0FFh`, like it would fail to compile it.
+    pub fn from_bytes(
+        evaluator: &'a ExpressionEvaluator,
+        bytes: &[u8],
+    ) -> anyhow::Result<CompiledExpression<'a>> {
+        let invalid = || anyhow!("invalid compiled expression");
+
+        let (&version, rest) = bytes.split_first().ok_or_else(invalid)?;
+
+        if version != FORMAT_VERSION {
+            return Err(anyhow!("unsupported compiled expression format {version}"));
+        }
+
+        let (length, source) = rest.split_first_chunk::<4>().ok_or_else(invalid)?;
+
+        if source.len() != u32::from_le_bytes(*length) as usize {
+            return Err(invalid());
+        }
+
+        evaluator.compile(std::str::from_utf8(source).map_err(|_| invalid())?)
+    }
+}
+
+impl TryFrom<&str> for CompiledExpression<'static> {
+    type Error = anyhow::Error;
+
+    fn try_from(expr: &str) -> anyhow::Result<Self> {
+        let evaluator = match SHARED_EVALUATOR.get() {
+            Some(evaluator) => evaluator,
+            None => {
+                let evaluator = ExpressionEvaluator::new()?;
+
+                SHARED_EVALUATOR.get_or_init(|| evaluator)
+            }
+        };
+
+        evaluator.compile(expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_repeatedly() {
+        let compiled = CompiledExpression::try_from("x*2+1").unwrap();
+        let eval = |x| compiled.eval(&HashMap::from([("x".to_string(), x)]));
+
+        assert_eq!(eval(0).unwrap(), 1);
+        assert_eq!(eval(5).unwrap(), 11);
+        assert_eq!(eval(-3).unwrap(), -5);
+        assert_eq!(
+            compiled.eval(&HashMap::new()).unwrap_err().to_string(),
+            "unknown variable 'x'"
+        );
+
+        let compiled = CompiledExpression::try_from("-x^2+MAX/x-y").unwrap();
+        let vars = HashMap::from([("x".to_string(), -3), ("y".to_string(), 1)]);
+
+        assert_eq!(
+            compiled.eval(&vars).unwrap(),
+            ExpressionEvaluator::new()
+                .unwrap()
+                .eval("-(-3)^2+MAX/(-3)-1".to_string())
+                .unwrap()
+        );
+
+        assert!(CompiledExpression::try_from("x*").is_err());
+        assert!(CompiledExpression::try_from("2(3)").is_err());
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn round_trips_through_bytes() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let compiled = evaluator.compile("|x-10|*2^3+abs(-x)").unwrap();
+        let bytes = compiled.to_bytes();
+        let loaded = CompiledExpression::from_bytes(&evaluator, &bytes).unwrap();
+
+        for x in [-5, 0, 3, 12] {
+            let vars = HashMap::from([("x".to_string(), x)]);
+
+            assert_eq!(loaded.eval(&vars).unwrap(), compiled.eval(&vars).unwrap());
+        }
+
+        assert_eq!(loaded.to_bytes(), bytes);
+
+        for corrupted in [
+            &[][..],
+            &bytes[..bytes.len() - 1],
+            &[2, 0, 0, 0, 0, 0],
+            &[1, 0, 0, 0, 0],
+        ] {
+            assert!(CompiledExpression::from_bytes(&evaluator, corrupted).is_err());
+        }
+
+        let suffixed = ExpressionEvaluator::with_config(crate::Config {
+            radix_suffixes: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let bytes = suffixed.compile("0FFh+x").unwrap().to_bytes();
+
+        assert!(CompiledExpression::from_bytes(&evaluator, &bytes).is_err());
+        assert_eq!(
+            CompiledExpression::from_bytes(&suffixed, &bytes)
+                .unwrap()
+                .eval(&HashMap::from([("x".to_string(), 1)]))
+                .unwrap(),
+            256
+        );
+    }
+}
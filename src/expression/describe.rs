@@ -0,0 +1,66 @@
+use anyhow::anyhow;
+
+use super::{
+    replacement,
+    tokenizer::{tokenize, TokenKind},
+};
+
+// Reads every token as words, in the order they appear in the input
+pub fn describe(expr: &str) -> anyhow::Result<String> {
+    let mut words = Vec::new();
+    let mut after_operand = false;
+    let mut open_bars = 0;
+
+    let tokens = tokenize(expr);
+
+    for token in &tokens {
+        // Alternative operator characters read the same as the ones they stand for
+        let text = match token.kind {
+            TokenKind::Operator => token.text.chars().next().and_then(replacement),
+            _ => None,
+        }
+        .unwrap_or(&token.text);
+
+        let word = match (token.kind, text) {
+            (TokenKind::Number | TokenKind::Identifier, text) => text,
+            (TokenKind::Operator, "-") if !after_operand => "negative",
+            (TokenKind::Operator, "+") => "plus",
+            (TokenKind::Operator, "-") => "minus",
+            (TokenKind::Operator, "*") => "times",
+            (TokenKind::Operator, "/") => "divided by",
+            (TokenKind::Operator, "^") => "to the power of",
+            (TokenKind::Superscript, "²") => "squared",
+            (TokenKind::Superscript, "³") => "cubed",
+            (TokenKind::OpenParenthesis, _) => "open paren",
+            (TokenKind::CloseParenthesis, _) => "close paren",
+            (TokenKind::Comma, _) => "comma",
+            // Same rule as in the evaluator: a bar right after an operand closes the innermost absolute value
+            (TokenKind::Bar, _) if after_operand && open_bars > 0 => {
+                open_bars -= 1;
+                "end absolute value"
+            }
+            (TokenKind::Bar, _) => {
+                open_bars += 1;
+                "absolute value of"
+            }
+            _ => return Err(anyhow!("invalid character '{}'", token.text)),
+        };
+
+        after_operand = match token.kind {
+            TokenKind::Number
+            | TokenKind::Identifier
+            | TokenKind::CloseParenthesis
+            | TokenKind::Superscript => true,
+            TokenKind::Bar => word == "end absolute value",
+            _ => false,
+        };
+
+        words.push(word);
+    }
+
+    if words.is_empty() {
+        return Err(anyhow!("empty expression"));
+    }
+
+    Ok(words.join(" "))
+}
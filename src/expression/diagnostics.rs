@@ -0,0 +1,240 @@
+use std::ops::Range;
+
+use super::{
+    operators::{self, Arity},
+    tokenizer::{tokenize, Token, TokenKind},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(span: Range<usize>, message: impl Into<String>) -> Self {
+        Diagnostic {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+enum Group {
+    Parenthesis(Range<usize>),
+    FunctionCall(Range<usize>),
+    Bar(Range<usize>),
+}
+
+// Walks the tokens alternating between expecting an operand and expecting an operator
+// On an unexpected token the problem is recorded and the walk continues as if the input was correct, so that later problems are reported too
+pub fn check(expr: &str) -> Vec<Diagnostic> {
+    let tokens = tokenize(expr);
+
+    let mut diagnostics = Vec::new();
+    let mut groups: Vec<Group> = Vec::new();
+    let mut expect_operand = true;
+    let mut unary_minus = false;
+    let mut previous: Option<&Token> = None;
+
+    for (index, token) in tokens.iter().enumerate() {
+        let span = token.span.clone();
+        let after_unary_minus = std::mem::take(&mut unary_minus);
+
+        match (token.kind, expect_operand) {
+            (TokenKind::Invalid, _) => {
+                diagnostics.push(Diagnostic::new(
+                    span,
+                    format!("invalid character '{}'", token.text),
+                ));
+                unary_minus = after_unary_minus;
+                continue;
+            }
+            (TokenKind::Number, true) => expect_operand = false,
+            (TokenKind::Identifier, true) => {
+                let is_call = tokens
+                    .get(index + 1)
+                    .is_some_and(|next| next.kind == TokenKind::OpenParenthesis);
+
+                if !is_call {
+                    if !super::CONSTANTS.contains(&token.text.as_ref()) {
+                        diagnostics.push(Diagnostic::new(
+                            span,
+                            format!("unknown identifier '{}'", token.text),
+                        ));
+                    }
+
+                    expect_operand = false;
+                }
+            }
+            (TokenKind::OpenParenthesis, true) => {
+                let is_call = previous.is_some_and(|previous| {
+                    previous.kind == TokenKind::Identifier && previous.span.end == span.start
+                });
+
+                groups.push(if is_call {
+                    Group::FunctionCall(span)
+                } else {
+                    Group::Parenthesis(span)
+                });
+            }
+            (TokenKind::Bar, true) => groups.push(Group::Bar(span)),
+            // A single unary minus is allowed at the start of every operand
+            (TokenKind::Operator, true)
+                if operators::arity(&token.text) == Some(Arity::UnaryOrBinary)
+                    && !after_unary_minus =>
+            {
+                unary_minus = true;
+            }
+            (TokenKind::Operator, true) => match previous {
+                Some(previous) if previous.kind == TokenKind::Operator => {
+                    diagnostics.push(Diagnostic::new(
+                        previous.span.start..span.end,
+                        format!(
+                            "unexpected operator '{}' after '{}'",
+                            token.text, previous.text
+                        ),
+                    ));
+                }
+                _ => diagnostics.push(Diagnostic::new(
+                    span,
+                    format!("missing operand before '{}'", token.text),
+                )),
+            },
+            (TokenKind::Comma, true) => {
+                diagnostics.push(Diagnostic::new(span, "missing argument"));
+            }
+            (TokenKind::CloseParenthesis, true) => {
+                diagnostics.push(Diagnostic::new(span.clone(), "missing operand before ')'"));
+                close_parenthesis(&mut groups, span, &mut diagnostics);
+                expect_operand = false;
+            }
+            (TokenKind::Operator, false) => expect_operand = true,
+            // A superscript power applies to the operand before it, so an operator is still expected after it
+            (TokenKind::Superscript, false) => {}
+            (TokenKind::Superscript, true) => {
+                diagnostics.push(Diagnostic::new(
+                    span,
+                    format!("missing base before '{}'", token.text),
+                ));
+            }
+            (TokenKind::CloseParenthesis, false) => {
+                close_parenthesis(&mut groups, span, &mut diagnostics);
+            }
+            (TokenKind::Bar, false) => match groups.last() {
+                Some(Group::Bar(_)) => {
+                    groups.pop();
+                }
+                _ => diagnostics.push(Diagnostic::new(span, "unmatched absolute value bar")),
+            },
+            (TokenKind::Comma, false) => {
+                if matches!(groups.last(), Some(Group::FunctionCall(_))) {
+                    expect_operand = true;
+                } else {
+                    diagnostics.push(Diagnostic::new(
+                        span,
+                        "argument separator outside of function call",
+                    ));
+                }
+            }
+            (TokenKind::Number | TokenKind::Identifier | TokenKind::OpenParenthesis, false) => {
+                diagnostics.push(Diagnostic::new(
+                    span,
+                    format!("missing operator before '{}'", token.text),
+                ));
+
+                if token.kind == TokenKind::OpenParenthesis {
+                    groups.push(Group::Parenthesis(token.span.clone()));
+                    expect_operand = true;
+                }
+            }
+        }
+
+        previous = Some(token);
+    }
+
+    if expect_operand {
+        let end = expr.trim_end().len();
+
+        diagnostics.push(Diagnostic::new(end..end, "unexpected end of expression"));
+    }
+
+    for group in groups {
+        diagnostics.push(match group {
+            Group::Parenthesis(span) | Group::FunctionCall(span) => {
+                Diagnostic::new(span, "unmatched opening parenthesis")
+            }
+            Group::Bar(span) => Diagnostic::new(span, "unmatched absolute value bar"),
+        });
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+
+    diagnostics
+}
+
+// First operator right after another one, except for a minus, which can be the sign of the operand
+pub fn adjacent_operator(expr: &str) -> Option<Diagnostic> {
+    tokenize(expr).windows(2).find_map(|pair| match pair {
+        [first, second]
+            if first.kind == TokenKind::Operator
+                && second.kind == TokenKind::Operator
+                && operators::arity(&second.text) != Some(Arity::UnaryOrBinary) =>
+        {
+            Some(Diagnostic::new(
+                second.span.clone(),
+                format!(
+                    "unexpected operator '{}' after '{}'",
+                    second.text, first.text
+                ),
+            ))
+        }
+        _ => None,
+    })
+}
+
+// Span of every binary operator without whitespace right before and right after it, a sign in front of an operand doesn't need any
+pub fn unspaced_operators(expr: &str) -> Vec<Range<usize>> {
+    let mut unspaced = Vec::new();
+    let mut expect_operand = true;
+
+    for token in tokenize(expr) {
+        match token.kind {
+            TokenKind::Number
+            | TokenKind::Identifier
+            | TokenKind::CloseParenthesis
+            | TokenKind::Superscript => expect_operand = false,
+            // A bar right after an operand closes an absolute value, and opens one anywhere else, so what is expected stays the same
+            TokenKind::Bar => {}
+            TokenKind::OpenParenthesis | TokenKind::Comma | TokenKind::Invalid => {
+                expect_operand = true;
+            }
+            TokenKind::Operator if expect_operand => {}
+            TokenKind::Operator => {
+                let spaced = expr[..token.span.start].ends_with(char::is_whitespace)
+                    && expr[token.span.end..].starts_with(char::is_whitespace);
+
+                if !spaced {
+                    unspaced.push(token.span);
+                }
+
+                expect_operand = true;
+            }
+        }
+    }
+
+    unspaced
+}
+
+fn close_parenthesis(
+    groups: &mut Vec<Group>,
+    span: Range<usize>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    match groups.last() {
+        Some(Group::Parenthesis(_) | Group::FunctionCall(_)) => {
+            groups.pop();
+        }
+        _ => diagnostics.push(Diagnostic::new(span, "unmatched closing parenthesis")),
+    }
+}
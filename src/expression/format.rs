@@ -0,0 +1,260 @@
+use crate::Config;
+
+use super::{roman, Diagnostic, Evaluation, Value, DECIMAL_COMMA};
+
+const RED: &str = "\x1b[1;31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+// Integers below this stay plain even with scientific notation, they are short enough to read either way
+const SCIENTIFIC_THRESHOLD: u64 = 1_000_000;
+// Marks a result in scientific notation which had to be rounded to the significant digits
+const APPROXIMATION_MARK: char = '~';
+
+#[derive(Clone, Copy)]
+pub enum Color {
+    Red,
+    Green,
+}
+
+// The text between the escape codes of the color, or as it is if colors are off
+pub fn paint(text: &str, color: Color, enabled: bool) -> String {
+    if !enabled {
+        return text.to_string();
+    }
+
+    let code = match color {
+        Color::Red => RED,
+        Color::Green => GREEN,
+    };
+
+    format!("{code}{text}{RESET}")
+}
+
+// Text of a successful result, with the digit grouping and annotations the configuration asks for
+pub fn format_evaluation(evaluation: &Evaluation, config: &Config) -> String {
+    let roman = match evaluation.value {
+        Value::Int(value) if config.roman_output => roman::to_roman(value),
+        _ => None,
+    };
+
+    // Falling back to the usual form is pointed out, so it is not taken for a numeral
+    let not_roman = config.roman_output && roman.is_none();
+
+    let mut value = match (evaluation.value, evaluation.literal_base, roman) {
+        (_, _, Some(numeral)) => numeral,
+        (Value::Int(value), Some(radix), _) if !config.decimal_output => radix.format(value),
+        (Value::Int(value), ..)
+            if config
+                .scientific_digits
+                .is_some_and(|_| value.unsigned_abs() >= SCIENTIFIC_THRESHOLD) =>
+        {
+            scientific(value, config.scientific_digits.unwrap_or_default())
+        }
+        _ => {
+            let mut value = evaluation.value.to_string();
+
+            if let Some(separator) = config.decimal_separator {
+                value = value.replace('.', &separator.to_string());
+            }
+
+            if config.group_output_digits {
+                value = group_digits(&value, digit_separator(config));
+            }
+
+            value
+        }
+    };
+
+    if not_roman {
+        value = format!(
+            "{value}  (no Roman numeral, only 1 to {})",
+            roman::MAX_ROMAN
+        );
+    }
+
+    if config.show_operation_count {
+        let operations = evaluation.operations;
+
+        value = format!(
+            "{value}  ({operations} op{})",
+            if operations == 1 { "" } else { "s" }
+        );
+    }
+
+    value
+}
+
+pub fn format_error(error: &anyhow::Error) -> String {
+    format!("Error: {error}")
+}
+
+/// Error message with the line of input it belongs to, and carets under the part it is about
+///
+/// The colors are ANSI escape codes, so they should only be asked for when writing to a terminal.
+pub fn render_diagnostic(input: &str, diagnostic: &Diagnostic, color: bool) -> String {
+    let input = input.trim_end();
+    // Columns are counted in characters, so a multi-byte character before the span doesn't shift the carets
+    let column = input[..diagnostic.span.start.min(input.len())]
+        .chars()
+        .count();
+    let width = input
+        .get(diagnostic.span.clone())
+        .map_or(1, |text| text.chars().count().max(1));
+    let (highlight, reset) = if color { (RED, RESET) } else { ("", "") };
+
+    format!(
+        "{highlight}Error{reset}: {}\n | {input}\n | {}{highlight}{}{reset}",
+        diagnostic.message,
+        " ".repeat(column),
+        "^".repeat(width)
+    )
+}
+
+// Like `1e6` or `-1.5e9`, rounded half away from zero to the significant digits, which makes it marked as approximate
+fn scientific(value: i64, significant_digits: u32) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let magnitude = value.unsigned_abs();
+    let mut exponent = magnitude.ilog10();
+    let dropped_digits = (exponent + 1).saturating_sub(significant_digits);
+    let divisor = 10_u64.pow(dropped_digits);
+    let (mut digits, remainder) = (magnitude / divisor, magnitude % divisor);
+
+    if remainder >= divisor - remainder {
+        digits += 1;
+
+        // Rounding up like 999 to 1000 gains a digit
+        if digits.ilog10() + 1 > significant_digits.min(exponent + 1) {
+            digits /= 10;
+            exponent += 1;
+        }
+    }
+
+    let digits = digits.to_string();
+    let (first, rest) = digits.split_at(1);
+    let rest = rest.trim_end_matches('0');
+    let mark = if remainder == 0 {
+        String::new()
+    } else {
+        APPROXIMATION_MARK.to_string()
+    };
+    let fraction = if rest.is_empty() {
+        String::new()
+    } else {
+        format!(".{rest}")
+    };
+
+    format!("{mark}{sign}{first}{fraction}e{exponent}")
+}
+
+// Only the integer part is grouped, counting from its last digit, so a sign or a fraction is left as it is
+// Separator between the digit groups of results, a point if the comma is the decimal separator
+pub(crate) fn digit_separator(config: &Config) -> char {
+    config
+        .digit_separator
+        .unwrap_or(if config.decimal_separator == Some(DECIMAL_COMMA) {
+            '.'
+        } else {
+            ','
+        })
+}
+
+fn group_digits(value: &str, separator: char) -> String {
+    let digits_start = usize::from(value.starts_with('-'));
+    let digits_end = value[digits_start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(value.len(), |end| digits_start + end);
+    let digits = &value[digits_start..digits_end];
+
+    let mut grouped = String::from(&value[..digits_start]);
+
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+
+        grouped.push(digit);
+    }
+
+    grouped.push_str(&value[digits_end..]);
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_diagnostics() {
+        let diagnostic = Diagnostic {
+            span: 4..5,
+            message: "unexpected operator '*'".to_string(),
+        };
+
+        assert_eq!(
+            render_diagnostic("12+(*3)\n", &diagnostic, false),
+            "Error: unexpected operator '*'\n | 12+(*3)\n |     ^"
+        );
+        assert_eq!(
+            render_diagnostic(
+                "2×(*3)",
+                &Diagnostic {
+                    span: 4..5,
+                    ..diagnostic.clone()
+                },
+                false
+            )
+            .lines()
+            .last(),
+            Some(" |    ^")
+        );
+        assert_eq!(
+            render_diagnostic(
+                "(1",
+                &Diagnostic {
+                    span: 0..2,
+                    ..diagnostic.clone()
+                },
+                true
+            ),
+            "\x1b[1;31mError\x1b[0m: unexpected operator '*'\n | (1\n | \x1b[1;31m^^\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn formats_in_scientific_notation() {
+        assert_eq!(scientific(1_000_000, 3), "1e6");
+        assert_eq!(scientific(10_i64.pow(18), 1), "1e18");
+        assert_eq!(scientific(-2_500_000, 3), "-2.5e6");
+        assert_eq!(scientific(1_234_567, 3), "~1.23e6");
+        assert_eq!(scientific(1_235_000, 3), "~1.24e6");
+        assert_eq!(scientific(9_996_000, 3), "~1e7");
+        assert_eq!(scientific(1_234_567, 10), "1.234567e6");
+        assert_eq!(scientific(i64::MIN, 3), "~-9.22e18");
+
+        let config = Config {
+            scientific_digits: Some(3),
+            ..Config::default()
+        };
+        let evaluation = |value| Evaluation {
+            value: Value::Int(value),
+            truncated: false,
+            operations: 0,
+            max_magnitude: 0,
+            op_stats: Default::default(),
+            literal_base: None,
+        };
+
+        assert_eq!(format_evaluation(&evaluation(999_999), &config), "999999");
+        assert_eq!(format_evaluation(&evaluation(-1_000_000), &config), "-1e6");
+    }
+
+    #[test]
+    fn groups_digits() {
+        assert_eq!(group_digits("1000000", ','), "1,000,000");
+        assert_eq!(group_digits("-1234567", ','), "-1,234,567");
+        assert_eq!(group_digits("42", ','), "42");
+        assert_eq!(group_digits("-100", ','), "-100");
+        assert_eq!(group_digits("1234.5678", ' '), "1 234.5678");
+    }
+}
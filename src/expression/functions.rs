@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+
+use super::solver;
+
+// A recursive function is stopped after this many nested calls, so one without a base case ends with an error instead of a stack overflow
+pub const MAX_CALL_DEPTH: usize = 64;
+
+/// Function defined in a session, like `def double(x) = x*2`
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserFunction {
+    pub parameters: Vec<String>,
+    pub body: String,
+}
+
+/// User-defined functions an expression can call by name
+pub type Functions = BTreeMap<String, UserFunction>;
+
+// Name and function of a definition like `double(x) = x*2`, the body is only checked when the function is defined
+pub fn parse_definition(definition: &str) -> anyhow::Result<(String, UserFunction)> {
+    let invalid = || anyhow!("expected a definition like 'def double(x) = x*2'");
+
+    let (head, body) = definition.split_once('=').ok_or_else(invalid)?;
+    let (name, parameters) = head.trim().split_once('(').ok_or_else(invalid)?;
+    let parameters = parameters.strip_suffix(')').ok_or_else(invalid)?;
+
+    let name = name.trim();
+    let parameters: Vec<String> = parameters
+        .split(',')
+        .map(|parameter| parameter.trim().to_string())
+        .collect();
+
+    if !parameters
+        .iter()
+        .map(String::as_str)
+        .chain([name])
+        .all(is_identifier)
+        || body.trim().is_empty()
+    {
+        return Err(invalid());
+    }
+
+    if solver::is_builtin(name) {
+        return Err(anyhow!("can't redefine builtin function '{name}'"));
+    }
+
+    if let Some((_, parameter)) = parameters
+        .iter()
+        .enumerate()
+        .find(|(index, parameter)| parameters[..*index].contains(parameter))
+    {
+        return Err(anyhow!("parameter '{parameter}' appears more than once"));
+    }
+
+    Ok((
+        name.to_string(),
+        UserFunction {
+            parameters,
+            body: body.trim().to_string(),
+        },
+    ))
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_definitions() {
+        assert_eq!(
+            parse_definition("double(x) = x*2").unwrap(),
+            (
+                "double".to_string(),
+                UserFunction {
+                    parameters: vec!["x".to_string()],
+                    body: "x*2".to_string(),
+                }
+            )
+        );
+        assert_eq!(
+            parse_definition(" hyp( a , b )=a^2+b^2")
+                .unwrap()
+                .1
+                .parameters,
+            ["a", "b"]
+        );
+
+        for invalid in [
+            "double(x)",
+            "double = 2",
+            "double() = 2",
+            "2x(y) = y",
+            "f(x, 1) = x",
+            "f(x) =",
+        ] {
+            assert!(parse_definition(invalid).is_err(), "{invalid}");
+        }
+
+        assert_eq!(
+            parse_definition("abs(x) = x").unwrap_err().to_string(),
+            "can't redefine builtin function 'abs'"
+        );
+        assert_eq!(
+            parse_definition("f(x, x) = x").unwrap_err().to_string(),
+            "parameter 'x' appears more than once"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::anyhow;
+
+use super::ExpressionEvaluator;
+
+// Most results remembered, the oldest ones are forgotten first, as deleting that much at once is rare
+const MAX_REMEMBERED_INPUTS: usize = 64;
+
+// Builtin with a different value for every call, so a result of an input calling it can't be reused
+const RANDOM_FUNCTION: &str = "rand";
+
+/// Evaluates an expression again every time it is changed, like while it is typed into a live display
+///
+/// Created by `ExpressionEvaluator::incremental`. The results of the inputs on the way are kept, so deleting characters gives back a result
+/// without evaluating anything, and the same input twice in a row is only evaluated once. Every result is the same as `ExpressionEvaluator::eval` gives.
+pub struct IncrementalEvaluator<'a> {
+    evaluator: &'a ExpressionEvaluator,
+    // Every input is a prefix of the next one, errors are kept as their messages
+    history: Vec<(String, Result<i64, String>)>,
+}
+
+impl<'a> IncrementalEvaluator<'a> {
+    pub(super) fn new(evaluator: &'a ExpressionEvaluator) -> IncrementalEvaluator<'a> {
+        IncrementalEvaluator {
+            evaluator,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn eval(&mut self, input: &str) -> anyhow::Result<i64> {
+        // Anything after the point where the input was changed belongs to an earlier version of it
+        while self
+            .history
+            .last()
+            .is_some_and(|(previous, _)| !input.starts_with(previous.as_str()))
+        {
+            self.history.pop();
+        }
+
+        if let Some((_, result)) = self
+            .history
+            .last()
+            .filter(|(previous, _)| previous == input)
+        {
+            return result.clone().map_err(|message| anyhow!(message));
+        }
+
+        let result = self.evaluator.eval(input.to_string());
+
+        if !input.contains(RANDOM_FUNCTION) {
+            if self.history.len() == MAX_REMEMBERED_INPUTS {
+                self.history.drain(..1);
+            }
+
+            let remembered = result.as_ref().copied().map_err(|error| error.to_string());
+
+            self.history.push((input.to_string(), remembered));
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_fresh_evaluation() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let mut incremental = evaluator.incremental();
+        let expr = "(12+3)*|4-10|/2^2";
+        let mut inputs: Vec<&str> = (1..=expr.len()).map(|end| &expr[..end]).collect();
+
+        // Deleting back to an earlier input, then typing something else after it
+        inputs.extend([
+            "(12+3)*|4",
+            "(12+3)*|4",
+            "(12+3)*",
+            "(12+3)*7",
+            "1/0",
+            "1/",
+            "1/5",
+        ]);
+
+        for input in inputs {
+            let fresh = evaluator.eval(input.to_string());
+
+            match incremental.eval(input) {
+                Ok(value) => assert_eq!(value, fresh.unwrap(), "{input}"),
+                Err(error) => assert_eq!(error.to_string(), fresh.unwrap_err().to_string()),
+            }
+        }
+    }
+}
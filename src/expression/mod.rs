@@ -1,176 +1,3371 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+    io::Write,
+    ops::{Range, RangeInclusive},
+    sync::{Mutex, PoisonError},
+};
+
 use anyhow::anyhow;
 use regex::Regex;
 
+use crate::{
+    random::Random,
+    session::{Session, ASSIGNMENT_OPERATOR},
+    Config,
+};
+
+#[cfg(feature = "bigint")]
+mod bigint;
+mod builder;
+mod cache;
+mod compiled;
+mod describe;
+mod diagnostics;
+mod format;
+mod functions;
+mod incremental;
+mod number;
+pub mod operators;
+pub mod patterns;
+mod radix;
+mod roman;
+mod simplify;
 mod solver;
+mod spans;
+mod tokenizer;
+mod units;
+mod value;
+
+#[cfg(feature = "bigint")]
+pub use bigint::BigInt;
+pub use builder::ExpressionEvaluatorBuilder;
+use cache::ResultCache;
+pub use compiled::CompiledExpression;
+pub use diagnostics::Diagnostic;
+pub use format::render_diagnostic;
+pub(crate) use format::{format_error, format_evaluation, paint, Color};
+use functions::MAX_CALL_DEPTH;
+pub use functions::{Functions, UserFunction};
+pub use incremental::IncrementalEvaluator;
+pub use number::CalcNumber;
+use patterns::*;
+pub use radix::Radix;
+use solver::{Solver, SolverScratch};
+use tokenizer::tokenize;
+pub use tokenizer::{tokenize_lossy, Token, TokenKind};
+pub use value::Value;
+
+// Characters accepted in the input as an alternative notation, with the text they are replaced with before validation
+const CHARACTER_REPLACEMENTS: &[(char, &str)] = &[
+    ('²', "^2"),
+    ('³', "^3"),
+    ('·', "*"),
+    ('＊', "*"),
+    ('×', "*"),
+    ('÷', "/"),
+];
+
+// Distance of the full-width forms from the ASCII characters they stand for, `！` is U+FF01 and `!` is U+0021
+const FULL_WIDTH_OFFSET: u32 = 0xFEE0;
+
+// Names of the bounds of the integers, which are reserved, so no variable can hide them
+const CONSTANTS: &[&str] = &["MAX", "MIN"];
+
+// Longest range an expression is evaluated over, so a typo in a bound can't keep the evaluator busy
+const MAX_RANGE_LENGTH: i64 = 100_000;
+
+// Builtin with a different value for every call, so its results are never cached
+const RANDOM_FUNCTION: &str = "rand";
+
+// Relative difference up to which two floats are considered equal, so rounding errors don't make a difference
+const FLOAT_TOLERANCE: f64 = 1e-9;
+
+const DECIMAL_COMMA: char = ',';
+// Separates function arguments when the comma is the decimal separator, like in spreadsheets
+const LOCALIZED_ARGUMENT_SEPARATOR: char = ';';
+// Separates the statements of a program, see `ExpressionEvaluator::eval_tuple`
+const STATEMENT_SEPARATOR: char = ';';
+
+/// Values of the variables an expression can refer to by name
+pub type Variables = BTreeMap<String, Value>;
+
+#[derive(Clone)]
+pub struct Evaluation {
+    pub value: Value,
+    /// Set when an integer division discarded a nonzero remainder
+    pub truncated: bool,
+    /// Number of arithmetic operations and function calls it took
+    pub operations: usize,
+    /// Largest absolute value of a product, quotient or partial sum on the way, saturating at `i64::MAX`
+    pub max_magnitude: i64,
+    /// How many of the `operations` were additions, subtractions, multiplications and divisions
+    pub op_stats: OpStats,
+    /// Set when every literal of the expression was written in this base, results are printed in it then
+    pub literal_base: Option<Radix>,
+}
+
+/// Number of arithmetic operations of each kind an evaluation carried out, see `ExpressionEvaluator::eval_with_op_stats`
+///
+/// A term of a sum which was subtracted counts as a subtraction, even if the minus came from a negative value like in `2+(-3)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpStats {
+    pub additions: usize,
+    pub subtractions: usize,
+    pub multiplications: usize,
+    pub divisions: usize,
+}
+
+/// Result of an expression together with the expression, see `ExpressionEvaluator::eval_recorded`
+#[derive(Debug)]
+pub struct EvalRecord {
+    /// Without the surrounding whitespace
+    pub input: String,
+    pub result: anyhow::Result<i64>,
+}
+
+// What an expression can refer to besides its variables
+#[derive(Clone, Copy, Default)]
+struct Context<'a> {
+    functions: Option<&'a Functions>,
+    random: Option<&'a RefCell<Random>>,
+    // Number of user-defined function calls the expression is nested in
+    depth: usize,
+    // Steps of the evaluation are recorded here if set
+    trace: Option<&'a RefCell<Vec<String>>>,
+    // Calls of functions which are neither builtins nor in the functions are handed to it
+    function_resolver: Option<&'a FunctionResolver<'a>>,
+}
+
+/// Value of a call of a function with integer arguments, or `None` if there is no function of that name, see `ExpressionEvaluator::eval_with_function_resolver`
+pub type FunctionResolver<'a> = dyn Fn(&str, &[i64]) -> Option<anyhow::Result<i64>> + 'a;
+
+/// Sign of a result, see `ExpressionEvaluator::eval_signed`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sign {
+    Positive,
+    Negative,
+    Zero,
+}
+
+/// Buffers kept between evaluations, so evaluating many expressions in a loop allocates less
+#[derive(Default)]
+pub struct EvalScratch {
+    normalized: String,
+    rewrite: RewriteScratch,
+}
 
-const EXPRESSION_PATTERN: &str = r"^[0-9\+\-\*\/\(\)]+$";
-const MULTIPLICATION_PATTERN: &str = r"([0-9]+)([\*\/])([-]?[0-9]+)";
-const PARENTHESIZED_SUBEXPRESSION_PATTERN: &str = r"[\(]([^\(\)]+)[\)]";
-const SUBEXPRESSION_PATTERN: &str = r"^[^\(\)]+$";
-const INVALID_PARENTHESES_PATTERN: &str = r"[0-9\)]\(";
+// Buffers of the steps after validation, apart from the normalized input, which errors are still explained with meanwhile
+#[derive(Default)]
+struct RewriteScratch {
+    substituted: String,
+    rewritten: String,
+    solver: SolverScratch,
+}
+
+impl EvalScratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
 
+/// Evaluates expressions, `new` gives one with the default configuration and `builder` one with options
+///
+/// ```
+/// use basic_arithmetic_calculator::ExpressionEvaluator;
+///
+/// let evaluator = ExpressionEvaluator::new().unwrap();
+/// assert_eq!(evaluator.eval("2+3*4".to_string()).unwrap(), 14);
+///
+/// let evaluator = ExpressionEvaluator::builder().wrap_bits(8).build().unwrap();
+/// assert_eq!(evaluator.eval("127+1".to_string()).unwrap(), -128);
+/// ```
 pub struct ExpressionEvaluator {
     expression_re: Regex,
     multiplication_re: Regex,
     parenthesized_subexpr_re: Regex,
     subexpression_re: Regex,
+    conditional_re: Regex,
+    identifier_re: Regex,
     invalid_parentheses_re: Regex,
+    invalid_function_call_re: Regex,
+    invalid_closing_parenthesis_re: Regex,
+    invalid_decimal_point_re: Regex,
+    duration_re: Regex,
+    magnitude_re: Regex,
+    literal_re: Regex,
+    suffixed_literal_re: Regex,
+    // Behind a lock, so an evaluator can still be shared between threads
+    cache: Option<Mutex<ResultCache>>,
+    config: Config,
 }
 
 impl ExpressionEvaluator {
     pub fn new() -> anyhow::Result<Self> {
+        Self::with_config(Config::default())
+    }
+
+    pub fn builder() -> ExpressionEvaluatorBuilder {
+        ExpressionEvaluatorBuilder::new()
+    }
+
+    pub fn with_config(config: Config) -> anyhow::Result<Self> {
+        if let Some(bits) = config.wrap_bits {
+            if !(1..=i64::BITS).contains(&bits) {
+                return Err(anyhow!(
+                    "wrap width must be between 1 and {} bits",
+                    i64::BITS
+                ));
+            }
+        }
+
+        // A suffix like the h of `12h` would also be the unit of a duration
+        if config.radix_suffixes && config.time_units {
+            return Err(anyhow!("radix suffixes can't be combined with time units"));
+        }
+
+        if config.scientific_digits == Some(0) {
+            return Err(anyhow!(
+                "scientific notation needs at least 1 significant digit"
+            ));
+        }
+
+        if let Some(separator) = config.decimal_separator {
+            if separator != '.' && separator != DECIMAL_COMMA {
+                return Err(anyhow!("decimal separator must be '.' or ','"));
+            }
+
+            if separator == DECIMAL_COMMA && !config.float_mode {
+                return Err(anyhow!("decimal comma is only allowed in float mode"));
+            }
+
+            if config.group_output_digits && format::digit_separator(&config) == separator {
+                return Err(anyhow!(
+                    "digit separator and decimal separator can't both be '{separator}'"
+                ));
+            }
+        }
+
         let expression_re = Regex::new(EXPRESSION_PATTERN)?;
-        let multiplication_re = Regex::new(MULTIPLICATION_PATTERN)?;
+        let multiplication_re = Regex::new(&multiplication_pattern())?;
         let parenthesized_subexpr_re = Regex::new(PARENTHESIZED_SUBEXPRESSION_PATTERN)?;
         let subexpression_re = Regex::new(SUBEXPRESSION_PATTERN)?;
+        let conditional_re = Regex::new(CONDITIONAL_PATTERN)?;
+        let identifier_re = Regex::new(IDENTIFIER_PATTERN)?;
         let invalid_parentheses_re = Regex::new(INVALID_PARENTHESES_PATTERN)?;
+        let invalid_function_call_re = Regex::new(INVALID_FUNCTION_CALL_PATTERN)?;
+        let invalid_closing_parenthesis_re = Regex::new(INVALID_CLOSING_PARENTHESIS_PATTERN)?;
+        let invalid_decimal_point_re = Regex::new(INVALID_DECIMAL_POINT_PATTERN)?;
+        let duration_re = Regex::new(units::DURATION_PATTERN)?;
+        let magnitude_re = Regex::new(units::MAGNITUDE_PATTERN)?;
+        let literal_re = Regex::new(radix::LITERAL_PATTERN)?;
+        let suffixed_literal_re = Regex::new(radix::SUFFIXED_LITERAL_PATTERN)?;
 
         Ok(ExpressionEvaluator {
             expression_re,
             multiplication_re,
             parenthesized_subexpr_re,
             subexpression_re,
+            conditional_re,
+            identifier_re,
             invalid_parentheses_re,
+            invalid_function_call_re,
+            invalid_closing_parenthesis_re,
+            invalid_decimal_point_re,
+            duration_re,
+            magnitude_re,
+            literal_re,
+            suffixed_literal_re,
+            cache: config
+                .cache_size
+                .map(|capacity| Mutex::new(ResultCache::new(capacity))),
+            config,
         })
     }
 
-    pub fn eval(&self, mut expr: String) -> anyhow::Result<i64> {
-        expr.retain(|c| !c.is_whitespace());
+    pub fn eval(&self, expr: String) -> anyhow::Result<i64> {
+        self.eval_value(expr)?.to_i64()
+    }
 
-        if !self.expression_re.is_match(&expr) {
-            return Err(anyhow!("not a valid expression"));
+    pub fn eval_value(&self, expr: String) -> anyhow::Result<Value> {
+        Ok(self.eval_detailed(expr)?.value)
+    }
+
+    pub fn eval_detailed(&self, expr: String) -> anyhow::Result<Evaluation> {
+        self.evaluate(&expr, false, &Variables::new(), &mut EvalScratch::new())
+    }
+
+    // Same as eval_detailed, but the buffers for preparing and solving the input are taken from the scratch instead of being allocated for every call
+    pub fn eval_with_scratch(
+        &self,
+        expr: &str,
+        scratch: &mut EvalScratch,
+    ) -> anyhow::Result<Evaluation> {
+        self.evaluate(expr, false, &Variables::new(), scratch)
+    }
+
+    // Names which are not followed by an argument list refer to these variables
+    pub fn eval_with_variables(
+        &self,
+        expr: &str,
+        variables: &Variables,
+    ) -> anyhow::Result<Evaluation> {
+        self.evaluate(expr, false, variables, &mut EvalScratch::new())
+    }
+
+    // Same as eval_with_variables, but the values are looked up when the expression is evaluated, once for every name which is not a constant
+    pub fn eval_with_resolver(
+        &self,
+        expr: &str,
+        resolver: impl Fn(&str) -> Option<i64>,
+    ) -> anyhow::Result<i64> {
+        let mut scratch = EvalScratch::new();
+        let validated = self.validate(expr, &mut scratch)?;
+        let mut variables = Variables::new();
+
+        for captures in self.identifier_re.captures_iter(&validated) {
+            let (Some(name), Some(parenthesis)) = (captures.get(1), captures.get(2)) else {
+                continue;
+            };
+            let name = name.as_str();
+
+            if !parenthesis.is_empty()
+                || variables.contains_key(name)
+                || self.constant(name).is_some()
+            {
+                continue;
+            }
+
+            let value = resolver(name).ok_or_else(|| anyhow!("unknown variable '{name}'"))?;
+
+            variables.insert(name.to_string(), Value::Int(value));
+        }
+
+        self.solve_validated(
+            &validated,
+            &scratch.normalized,
+            false,
+            &variables,
+            Context::default(),
+            &mut scratch.rewrite,
+        )?
+        .value
+        .to_i64()
+    }
+
+    // Same as eval, but the calls of functions which are not builtins are handed to the resolver, a name it returns None for is an unknown function
+    pub fn eval_with_function_resolver(
+        &self,
+        expr: &str,
+        resolver: &FunctionResolver,
+    ) -> anyhow::Result<i64> {
+        let mut scratch = EvalScratch::new();
+        let validated = self.validate(expr, &mut scratch)?;
+        let context = Context {
+            function_resolver: Some(resolver),
+            ..Context::default()
+        };
+
+        self.solve_validated(
+            &validated,
+            &scratch.normalized,
+            false,
+            &Variables::new(),
+            context,
+            &mut scratch.rewrite,
+        )?
+        .value
+        .to_i64()
+    }
+
+    // Same as eval_with_variables, but the user-defined functions can be called too, and the numbers of rand are taken from the generator, so they can be reproduced from its seed
+    pub fn eval_in_session(
+        &self,
+        expr: &str,
+        variables: &Variables,
+        functions: &Functions,
+        random: &RefCell<Random>,
+    ) -> anyhow::Result<Evaluation> {
+        self.eval_traced_in_session(expr, variables, functions, random, None)
+    }
+
+    // Same as eval_in_session, with the steps of the evaluation recorded in the trace if there is one
+    pub(crate) fn eval_traced_in_session(
+        &self,
+        expr: &str,
+        variables: &Variables,
+        functions: &Functions,
+        random: &RefCell<Random>,
+        trace: Option<&RefCell<Vec<String>>>,
+    ) -> anyhow::Result<Evaluation> {
+        let mut scratch = EvalScratch::new();
+        let validated = self.validate(expr, &mut scratch)?;
+        let context = Context {
+            functions: Some(functions),
+            random: Some(random),
+            trace,
+            ..Context::default()
+        };
+
+        self.solve_validated(
+            &validated,
+            &scratch.normalized,
+            false,
+            variables,
+            context,
+            &mut scratch.rewrite,
+        )
+    }
+
+    // Evaluation together with every form the expression took on the way to its value, like `2+3*3`, `2+9` and `11` for `2+3*(1+4/2)`
+    // Exponentiations are shown as calls of pow, which is how they are evaluated
+    pub fn eval_with_trace(&self, expr: &str) -> anyhow::Result<(Evaluation, Vec<String>)> {
+        let mut scratch = EvalScratch::new();
+        let validated = self.validate(expr, &mut scratch)?;
+        let trace = RefCell::new(Vec::new());
+        let context = Context {
+            trace: Some(&trace),
+            ..Context::default()
+        };
+        let evaluation = self.solve_validated(
+            &validated,
+            &scratch.normalized,
+            false,
+            &Variables::new(),
+            context,
+            &mut scratch.rewrite,
+        )?;
+
+        Ok((evaluation, trace.into_inner()))
+    }
+
+    // Checks the body when the function is defined, so a mistake in it is reported right away instead of at every call
+    pub fn define_function(&self, definition: &str) -> anyhow::Result<(String, UserFunction)> {
+        let (name, function) = functions::parse_definition(definition)?;
+
+        let validated = self.validate(&function.body, &mut EvalScratch::new())?;
+
+        self.check_validated_syntax(&function.body, &validated)?;
+
+        Ok((name, function))
+    }
+
+    // Value of the template for every value of the variable in the range, in order, the other variables keep their values
+    pub fn eval_over_range(
+        &self,
+        template: &str,
+        variable: &str,
+        range: RangeInclusive<i64>,
+        variables: &Variables,
+    ) -> anyhow::Result<Vec<Value>> {
+        self.eval_over_range_in(template, variable, range, variables, Context::default())
+    }
+
+    // Same as eval_over_range, with the user-defined functions and the generator of rand of a session
+    pub(crate) fn eval_over_range_in_session(
+        &self,
+        template: &str,
+        variable: &str,
+        range: RangeInclusive<i64>,
+        variables: &Variables,
+        functions: &Functions,
+        random: &RefCell<Random>,
+    ) -> anyhow::Result<Vec<Value>> {
+        let context = Context {
+            functions: Some(functions),
+            random: Some(random),
+            ..Context::default()
+        };
+
+        self.eval_over_range_in(template, variable, range, variables, context)
+    }
+
+    fn eval_over_range_in(
+        &self,
+        template: &str,
+        variable: &str,
+        range: RangeInclusive<i64>,
+        variables: &Variables,
+        context: Context,
+    ) -> anyhow::Result<Vec<Value>> {
+        let length = range.end().saturating_sub(*range.start()).saturating_add(1);
+
+        if length > MAX_RANGE_LENGTH {
+            return Err(anyhow!("range too long"));
         }
 
-        if self.invalid_parentheses_re.is_match(&expr) {
+        let mut variables = variables.clone();
+        let mut scratch = EvalScratch::new();
+        // Only the value of the variable changes, so the template is the same valid or invalid input for every one of them
+        let validated = self.validate(template, &mut scratch)?;
+
+        range
+            .map(|value| {
+                variables.insert(variable.to_string(), Value::Int(value));
+
+                Ok(self
+                    .solve_validated(
+                        &validated,
+                        &scratch.normalized,
+                        false,
+                        &variables,
+                        context,
+                        &mut scratch.rewrite,
+                    )?
+                    .value)
+            })
+            .collect()
+    }
+
+    // Result for every value of the variable, the expression is only validated once however many values there are
+    // If it is invalid, the error is given for every value
+    pub fn eval_over<'a>(
+        &'a self,
+        expr: &str,
+        var: &'a str,
+        values: impl Iterator<Item = i64> + 'a,
+    ) -> impl Iterator<Item = anyhow::Result<i64>> + 'a {
+        let compiled = self.compile(expr);
+        let mut variables = Variables::new();
+
+        values.map(move |value| {
+            variables.insert(var.to_string(), Value::Int(value));
+
+            match &compiled {
+                Ok(compiled) => compiled.eval_with_variables(&variables),
+                Err(error) => Err(anyhow!("{error}")),
+            }
+        })
+    }
+
+    // Divisions are carried out on exact fractions, so the result is only accepted if it is a whole number
+    pub fn eval_exact_int(&self, expr: &str) -> anyhow::Result<i64> {
+        self.evaluate(expr, true, &Variables::new(), &mut EvalScratch::new())?
+            .value
+            .to_i64()
+    }
+
+    // For validating formulas whose results have to stay within bounds, like a volume from 0 to 100
+    pub fn eval_in_range(&self, expr: &str, range: RangeInclusive<i64>) -> anyhow::Result<i64> {
+        let value = self.eval(expr.to_string())?;
+
+        if !range.contains(&value) {
             return Err(anyhow!(
-                "opening parenthesis after digit or closing parenthesis"
+                "result {value} is outside of {}..={}",
+                range.start(),
+                range.end()
             ));
         }
 
-        // Check leading double hyphen, because subsequent transformations can produce it even in case of valid inputs and the solver is able to "solve" it
-        // Therefore, if we want to differentiate between input and solver transformations, we have to do it here
-        if expr.starts_with("--") {
-            return Err(anyhow::anyhow!("starts with double hyphens"));
+        Ok(value)
+    }
+
+    // For logging results, so the caller doesn't have to keep the inputs around
+    pub fn eval_recorded(&self, expr: &str) -> EvalRecord {
+        let input = expr.trim().to_string();
+        let result = self.eval(input.clone());
+
+        EvalRecord { input, result }
+    }
+
+    // Sign and absolute value of the integer result, the magnitude of i64::MIN fits too
+    pub fn eval_signed(&self, expr: &str) -> anyhow::Result<(Sign, u64)> {
+        let value = self.eval(expr.to_string())?;
+        let sign = match value.signum() {
+            1 => Sign::Positive,
+            -1 => Sign::Negative,
+            _ => Sign::Zero,
+        };
+
+        Ok((sign, value.unsigned_abs()))
+    }
+
+    // Integer result together with the largest absolute value on the way to it, to tell how close an evaluation came to overflowing
+    pub fn eval_with_stats(&self, expr: &str) -> anyhow::Result<(i64, i64)> {
+        let evaluation = self.eval_detailed(expr.to_string())?;
+
+        Ok((evaluation.value.to_i64()?, evaluation.max_magnitude))
+    }
+
+    // Integer result together with the number of additions, subtractions, multiplications and divisions it took
+    pub fn eval_with_op_stats(&self, expr: &str) -> anyhow::Result<(i64, OpStats)> {
+        let evaluation = self.eval_detailed(expr.to_string())?;
+
+        Ok((evaluation.value.to_i64()?, evaluation.op_stats))
+    }
+
+    // Writes the result into the buffer without allocating for it, and returns the number of bytes written
+    pub fn eval_into(&self, expr: &str, out: &mut [u8]) -> anyhow::Result<usize> {
+        let value = self.eval_value(expr.to_string())?;
+        let capacity = out.len();
+        let mut remaining = &mut out[..];
+
+        write!(remaining, "{value}").map_err(|_| anyhow!("output buffer too small"))?;
+
+        Ok(capacity - remaining.len())
+    }
+
+    // Formatted result, or the error message if the evaluation failed, ready to be displayed as it is
+    pub fn eval_to_string(&self, expr: &str) -> String {
+        match self.eval_detailed(expr.to_string()) {
+            Ok(evaluation) => format_evaluation(&evaluation, &self.config),
+            Err(error) => format_error(&error),
         }
+    }
 
-        // Solve parenthesized subexpressions, and transform the expression in a way that there are no more parentheses
-        let expr = solver::handle_parentheses(
-            expr,
+    // Exact evaluation like eval_exact_int, with the result written out as a decimal instead of being required to be whole
+    pub fn eval_decimal(&self, expr: &str, places: u32) -> anyhow::Result<String> {
+        self.evaluate(expr, true, &Variables::new(), &mut EvalScratch::new())?
+            .value
+            .to_decimal(places)
+    }
+
+    // Whether the two expressions have the same value, if a float is involved the values only have to be close
+    pub fn equivalent(&self, a: &str, b: &str) -> anyhow::Result<bool> {
+        let a = self.eval_value(a.to_string())?;
+        let b = self.eval_value(b.to_string())?;
+
+        Ok(values_equivalent(a, b))
+    }
+
+    // Integer arithmetic of the given number type instead of values, builtins other than exponentiation and conditionals are not available
+    pub fn eval_as<T: CalcNumber>(&self, expr: &str) -> anyhow::Result<T> {
+        let mut scratch = EvalScratch::new();
+
+        self.prepare(expr, &Variables::new(), &mut scratch)?;
+
+        Solver::<T>::new(
             &self.multiplication_re,
             &self.parenthesized_subexpr_re,
-        )?;
+            &self.subexpression_re,
+            &self.conditional_re,
+            &self.config,
+        )
+        .scratch(Some(&scratch.rewrite.solver))
+        .solve(&mut scratch.rewrite.rewritten)
+    }
 
-        if !self.subexpression_re.is_match(&expr) {
-            return Err(anyhow!("not a valid subexpression"));
-        }
+    // Exact integers of any size, like `2^200`
+    #[cfg(feature = "bigint")]
+    pub fn eval_bigint(&self, expr: &str) -> anyhow::Result<BigInt> {
+        self.eval_as(expr)
+    }
 
-        // After parentheses are removed, the expression is itself a subexpression
-        solver::eval_subexpression(expr, &self.multiplication_re)
+    // Validates the expression only once, for evaluating it with different values of its variables
+    pub fn compile(&self, expr: &str) -> anyhow::Result<CompiledExpression<'_>> {
+        let validated = self.validate(expr, &mut EvalScratch::new())?;
+
+        self.check_validated_syntax(expr, &validated)?;
+
+        CompiledExpression::new(self, expr, validated)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::str::FromStr;
+    // Evaluator of an expression which keeps changing, like while it is typed
+    pub fn incremental(&self) -> IncrementalEvaluator<'_> {
+        IncrementalEvaluator::new(self)
+    }
 
-    use super::*;
+    // Shortest equivalent expression, with every constant subexpression folded into its value, like `x*(2+3)` into `x*5`
+    pub fn simplify(&self, expr: &str) -> anyhow::Result<String> {
+        let validated = self.validate(expr, &mut EvalScratch::new())?;
 
-    const LONG_EXPR : &str = "((1+(-2*(3-(4/(-5+6*(-7-(8/(-9+1))))))))+((11*(-12+13))/(14-(15*(-16+17))))-(18+(-19*(20-(21/(-22+23*(-24-(25/(-26+27))))))))+(28*(-29+(30/(31-(32*(-33+34))))))-(35+(-36*(37-(38/(-39+40*(-41-(42/(-43+44))))))))+(45*(-46+(47/(48-(49*(-50+51))))))-(52+(-53*(54-(55/(-56+57*(-58-(59/(-60+61))))))))+(62*(-63+(64/(65-(66*(-67+68))))))-(69+(-70*(71-(72/(-73+74*(-75-(76/(-77+78))))))))+(79*(-80+(81/(82-(83*(-84+85))))))-(86+(-87*(88-(89/(-90+91*(-92-(93/(-94+95))))))))+(96*(-97+(98/(99-(100*(-101+102))))))+(103+(-104*(105-(106/(-107+108*(-109-(110/(-111+112))))))))+(113*(-114+(115/(116-(117*(-118+119))))))-(120+(-121*(122-(123/(-124+125*(-126-(127/(-128+129))))))))+(130*(-131+(132/(133-(134*(-135+136)))))))";
-    const SHORT_EXPR: &str = "2+3*(1+4/2)";
+        self.check_validated_syntax(expr, &validated)?;
 
-    fn eval_str(expr: &str) -> anyhow::Result<i64> {
-        let evaluator = ExpressionEvaluator::new().unwrap();
+        // Without any names the whole expression is constant, and it is evaluated as usual, so its errors are reported
+        if !tokenize(&validated)
+            .iter()
+            .any(|token| token.kind == TokenKind::Identifier)
+        {
+            return Ok(self.eval_value(expr.to_string())?.to_expression_text());
+        }
 
-        eval_str_custom(&evaluator, expr)
+        simplify::simplify(&validated, |constant| {
+            match self
+                .solve_validated(
+                    constant,
+                    constant,
+                    false,
+                    &Variables::new(),
+                    Context::default(),
+                    &mut RewriteScratch::default(),
+                )
+                .ok()?
+                .value
+            {
+                Value::Float(value) if !value.is_finite() => None,
+                value => Some(value.to_expression_text()),
+            }
+        })
     }
 
-    fn eval_str_custom(evaluator: &ExpressionEvaluator, expr: &str) -> anyhow::Result<i64> {
-        evaluator.eval(String::from_str(expr)?)
+    // Parse tree of the expression as ASCII art, the operands of an operation are indented below it
+    pub fn tree(&self, expr: &str) -> anyhow::Result<String> {
+        let validated = self.validate(expr, &mut EvalScratch::new())?;
+
+        self.check_validated_syntax(expr, &validated)?;
+
+        simplify::render_tree(&validated)
     }
 
-    #[test]
-    fn calculates_correct_result() {
-        let test_data = vec![
-            ("1+1", 2),
-            ("1-1", 0),
-            ("1/1", 1),
-            ("1*1", 1),
-            ("1+(1-1)", 1),
-            ("1000*1000", 1000000),
-            ("-1-1", -2),
-            ("-10/5", -2),
-            ("-10*-10", 100),
-            ("1+-1", 0),
-            ("1--1", 2),
-        ];
+    fn evaluate(
+        &self,
+        expr: &str,
+        exact_division: bool,
+        variables: &Variables,
+        scratch: &mut EvalScratch,
+    ) -> anyhow::Result<Evaluation> {
+        debug!("evaluating '{expr}'");
 
-        for (expr, result) in test_data {
-            assert_eq!(eval_str(expr).unwrap(), result);
+        let result = self.evaluate_cached(expr, exact_division, variables, scratch);
+
+        #[cfg(feature = "logging")]
+        if let Err(error) = &result {
+            debug!("evaluation of '{expr}' failed: {error}");
         }
-    }
 
-    #[test]
-    fn handles_whitespace() {
-        assert_eq!(
-            eval_str(" \t2\n      +3\t*(  1  + 4 /2)\t\t").unwrap(),
-            eval_str("2+3*(1+4/2)").unwrap()
-        );
+        result
     }
 
-    #[test]
-    fn handles_parentheses() {
-        let test_data = vec![
-            ("(1)", 1),
-            ("(1-1)", 0),
-            ("60/(((2+3)))", 12),
-            ("3*(5-((2-4)-(-4)))", 9),
-        ];
+    fn evaluate_cached(
+        &self,
+        expr: &str,
+        exact_division: bool,
+        variables: &Variables,
+        scratch: &mut EvalScratch,
+    ) -> anyhow::Result<Evaluation> {
+        // The value of rand is different every time, and only a plain evaluation without variables gives the same value for the same input
+        let cache = self
+            .cache
+            .as_ref()
+            .filter(|_| !exact_division && variables.is_empty() && !expr.contains(RANDOM_FUNCTION));
 
-        for (expr, result) in test_data {
-            assert_eq!(eval_str(expr).unwrap(), result);
+        let Some(cache) = cache else {
+            return self.evaluate_uncached(expr, exact_division, variables, scratch);
+        };
+
+        // Whitespace is dropped anyway, apart from the checks of the input as it was written, which are done every time
+        self.check_written_form(expr)?;
+
+        let key: String = expr.chars().filter(|c| !c.is_whitespace()).collect();
+
+        if let Some(evaluation) = cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&key)
+        {
+            return Ok(evaluation);
         }
+
+        // Errors are not cached, most of them are found by the validation, which costs little
+        let evaluation = self.evaluate_uncached(expr, exact_division, variables, scratch)?;
+
+        cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key, evaluation.clone());
+
+        Ok(evaluation)
     }
 
-    #[test]
-    fn calculates_integer_division_correctly() {
-        assert_eq!(eval_str("1/2").unwrap(), 0);
-        assert_eq!(eval_str("25/12").unwrap(), 2);
-        assert_eq!(eval_str("3/2*2").unwrap(), 2);
+    fn evaluate_uncached(
+        &self,
+        expr: &str,
+        exact_division: bool,
+        variables: &Variables,
+        scratch: &mut EvalScratch,
+    ) -> anyhow::Result<Evaluation> {
+        let expr = self.validate(expr, scratch)?;
+
+        debug!("validated as '{expr}'");
+
+        self.solve_validated(
+            &expr,
+            &scratch.normalized,
+            exact_division,
+            variables,
+            Context::default(),
+            &mut scratch.rewrite,
+        )
     }
 
-    // Test error cases to guarantee that the program doesn't panic or return a number incorrectly
+    // Evaluation of an input which has already been validated, only the parts depending on the variables are left to do
+    // The source is what errors are explained with, as the validated form can differ from what was written
+    fn solve_validated(
+        &self,
+        validated: &str,
+        source: &str,
+        exact_division: bool,
+        variables: &Variables,
+        context: Context,
+        scratch: &mut RewriteScratch,
+    ) -> anyhow::Result<Evaluation> {
+        self.rewrite(validated, variables, scratch)?;
 
-    #[test]
-    fn rejects_zero_division() {
-        assert!(eval_str("1/0").is_err());
+        self.solve_rewritten(
+            &mut scratch.rewritten,
+            source,
+            exact_division,
+            variables,
+            context,
+            Some(&scratch.solver),
+        )
     }
 
-    #[test]
-    fn handles_syntax_error() {
-        assert!(eval_str("()").is_err());
-        assert!(eval_str("(1+1").is_err());
-        assert!(eval_str("1+1)").is_err());
-        assert!(eval_str("1++1").is_err());
-        assert!(eval_str("2(3+1)").is_err());
-        assert!(eval_str("(2+2)(3+3)").is_err());
-        assert!(eval_str("--1").is_err());
-        assert!(eval_str("1+a").is_err());
-        assert!(eval_str("asdf").is_err());
+    // Same as solve_validated, for an input which is already in the form the solver works on
+    fn solve_rewritten(
+        &self,
+        expr: &mut String,
+        source: &str,
+        exact_division: bool,
+        variables: &Variables,
+        context: Context,
+        scratch: Option<&SolverScratch>,
+    ) -> anyhow::Result<Evaluation> {
+        let call_user_function = |name: &str, arguments: &[Value]| {
+            if let Some(function) = context.functions.and_then(|functions| functions.get(name)) {
+                return Some(
+                    self.call_user_function(name, function, arguments, variables, context),
+                );
+            }
+
+            let resolver = context.function_resolver?;
+            let arguments: Vec<i64> =
+                match arguments.iter().map(|argument| argument.to_i64()).collect() {
+                    Ok(arguments) => arguments,
+                    Err(error) => return Some(Err(error)),
+                };
+
+            Some(resolver(name, &arguments)?.map(Value::Int))
+        };
+
+        let solver = Solver::new(
+            &self.multiplication_re,
+            &self.parenthesized_subexpr_re,
+            &self.subexpression_re,
+            &self.conditional_re,
+            &self.config,
+        )
+        .exact_division(exact_division)
+        .random(context.random)
+        .trace(context.trace)
+        .scratch(scratch)
+        .user_functions(Some(&call_user_function));
+
+        let value = solver
+            .solve(expr)
+            .map_err(|error| self.explain_division_by_zero(error, source, variables))?;
+
+        Ok(Evaluation {
+            value,
+            truncated: solver.truncated(),
+            operations: solver.operations(),
+            max_magnitude: solver.max_magnitude(),
+            op_stats: solver.op_stats(),
+            literal_base: radix::literal_base(source, &self.literal_re),
+        })
     }
 
-    #[test]
-    fn rejects_float() {
-        assert!(eval_str("1.0+1").is_err());
-        assert!(eval_str("3/2.0").is_err());
+    // The arguments are bound to the parameters, which hide the variables of the same name
+    fn call_user_function(
+        &self,
+        name: &str,
+        function: &UserFunction,
+        arguments: &[Value],
+        variables: &Variables,
+        context: Context,
+    ) -> anyhow::Result<Value> {
+        let arity = function.parameters.len();
+
+        if arguments.len() != arity {
+            return Err(anyhow!(
+                "{name} expects {arity} argument{}, got {}",
+                if arity == 1 { "" } else { "s" },
+                arguments.len()
+            ));
+        }
+
+        if context.depth == MAX_CALL_DEPTH {
+            return Err(anyhow!("function calls nested too deeply"));
+        }
+
+        let mut variables = variables.clone();
+        variables.extend(
+            function
+                .parameters
+                .iter()
+                .cloned()
+                .zip(arguments.iter().copied()),
+        );
+
+        let mut scratch = EvalScratch::new();
+        let validated = self.validate(&function.body, &mut scratch)?;
+        // The steps of the body are not steps of the expression calling the function
+        let context = Context {
+            depth: context.depth + 1,
+            trace: None,
+            ..context
+        };
+
+        Ok(self
+            .solve_validated(
+                &validated,
+                &scratch.normalized,
+                false,
+                &variables,
+                context,
+                &mut scratch.rewrite,
+            )?
+            .value)
     }
 
-    #[test]
-    fn performance_short() {
-        let evaluator = ExpressionEvaluator::new().unwrap();
+    // Subexpressions are replaced by their values before dividing, so the error would only show the zero they turned into
+    fn explain_division_by_zero(
+        &self,
+        error: anyhow::Error,
+        expr: &str,
+        variables: &Variables,
+    ) -> anyhow::Error {
+        if error.to_string() != "division by zero" {
+            return error;
+        }
 
-        for _ in 0..1000 {
-            assert!(eval_str_custom(&evaluator, SHORT_EXPR).is_ok());
+        let zero_divisor = spans::parenthesized_groups(expr)
+            .into_iter()
+            .filter(|group| expr[..group.start].ends_with('/'))
+            .map(|group| &expr[group])
+            .find(|divisor| {
+                self.eval_with_variables(divisor, variables)
+                    .is_ok_and(|evaluation| evaluation.value.is_zero())
+            });
+
+        match zero_divisor {
+            Some(divisor) => anyhow!("division by zero, the divisor {divisor} is 0"),
+            None => error,
+        }
+    }
+
+    // Validates the input, and rewrites it into the form the solver works on, which is left in the scratch
+    fn prepare(
+        &self,
+        expr: &str,
+        variables: &Variables,
+        scratch: &mut EvalScratch,
+    ) -> anyhow::Result<()> {
+        let expr = self.validate(expr, scratch)?;
+
+        self.rewrite(&expr, variables, &mut scratch.rewrite)
+    }
+
+    // Everything which doesn't depend on the values of the variables
+    fn validate(&self, expr: &str, scratch: &mut EvalScratch) -> anyhow::Result<String> {
+        self.check_written_form(expr)?;
+
+        scratch.normalized.clear();
+        normalize_characters(expr, &mut scratch.normalized);
+
+        let decimal;
+        let unsuffixed;
+        let mut expr = scratch.normalized.as_str();
+
+        if self.config.radix_suffixes {
+            unsuffixed = radix::replace_suffixed_literals(expr, &self.suffixed_literal_re)?;
+            expr = &unsuffixed;
         }
+
+        if expr.contains(['x', 'X', 'b', 'B']) {
+            decimal = radix::replace_literals(expr, &self.literal_re)?;
+            expr = &decimal;
+        }
+
+        let localized;
+
+        if self.config.decimal_separator == Some(DECIMAL_COMMA) {
+            localized = replace_decimal_commas(expr)?;
+            expr = &localized;
+        }
+
+        let without_magnitudes;
+
+        if self.config.magnitude_suffixes {
+            without_magnitudes = units::replace_magnitudes(expr, &self.magnitude_re)?;
+            expr = &without_magnitudes;
+        }
+
+        let without_units;
+
+        if self.config.time_units {
+            without_units = units::replace_durations(expr, &self.duration_re)?;
+            expr = &without_units;
+        }
+
+        if !self.expression_re.is_match(expr) {
+            return Err(anyhow!("not a valid expression"));
+        }
+
+        if expr.contains('.') {
+            if !self.config.float_mode {
+                return Err(anyhow!("decimal literals are only allowed in float mode"));
+            }
+
+            if self.invalid_decimal_point_re.is_match(expr) {
+                return Err(anyhow!("misplaced decimal point"));
+            }
+        }
+
+        // Absolute value bars are turned into calls of the abs builtin, so the rest of the evaluation only has to deal with parentheses
+        let expr = solver::replace_absolute_value_bars(expr)?;
+
+        if expr.contains('(') && self.invalid_parentheses_re.is_match(&expr) {
+            return Err(anyhow!(
+                "opening parenthesis after digit or closing parenthesis"
+            ));
+        }
+
+        if self.invalid_function_call_re.is_match(&expr) {
+            return Err(anyhow!("function name after digit or closing parenthesis"));
+        }
+
+        if self.invalid_closing_parenthesis_re.is_match(&expr) {
+            return Err(anyhow!("digit after closing parenthesis"));
+        }
+
+        // Check leading double hyphen, because subsequent transformations can produce it even in case of valid inputs and the solver is able to "solve" it
+        // Therefore, if we want to differentiate between input and solver transformations, we have to do it here
+        if expr.starts_with("--") && !self.config.allow_double_negation {
+            return Err(anyhow::anyhow!("starts with double hyphens"));
+        }
+
+        if self.config.require_full_parens {
+            simplify::check_full_parens(&expr)?;
+        }
+
+        Ok(expr)
+    }
+
+    // Checks of the input as it was written, before its whitespace is dropped
+    fn check_written_form(&self, expr: &str) -> anyhow::Result<()> {
+        // Checked first, so an oversized input costs nothing but its length
+        if self
+            .config
+            .max_length
+            .is_some_and(|max_length| expr.len() > max_length)
+        {
+            return Err(anyhow!("expression too long"));
+        }
+
+        // Whitespace is gone once the characters are normalized, so this has to look at the input as it was written
+        if self.config.require_operator_spaces {
+            if let Some(span) = diagnostics::unspaced_operators(expr).first() {
+                return Err(anyhow!(
+                    "operator '{}' at column {} needs spaces around it",
+                    &expr[span.clone()],
+                    expr[..span.start].chars().count() + 1
+                ));
+            }
+        }
+
+        // The solver would only fail on a number it can't parse, so the operators are pointed out here
+        if let Some(diagnostic) = diagnostics::adjacent_operator(expr) {
+            return Err(at_column(expr, &diagnostic));
+        }
+
+        // The rewritten form has lost the whitespace and got new parentheses from the exponentiations, so the position is only right in the input
+        if let Some(index) = find_unmatched_parenthesis(expr) {
+            return Err(anyhow!(
+                "unmatched parenthesis at position {}",
+                expr[..index].chars().count() + 1
+            ));
+        }
+
+        Ok(())
+    }
+
+    // The form the solver works on is left in the rewritten buffer of the scratch
+    fn rewrite(
+        &self,
+        expr: &str,
+        variables: &Variables,
+        scratch: &mut RewriteScratch,
+    ) -> anyhow::Result<()> {
+        self.substitute_variables(expr, variables, &mut scratch.substituted)?;
+
+        // Exponentiations are turned into calls of the pow builtin as well, because a negative base coming from a parenthesized subexpression would otherwise lose its grouping
+        solver::replace_exponentiations(
+            solver::strip_enclosing_parentheses(&scratch.substituted),
+            &mut scratch.rewritten,
+        )
+    }
+
+    // Every variable is replaced by its parenthesized value, so a negative value keeps its sign as a single operand
+    fn substitute_variables(
+        &self,
+        expr: &str,
+        variables: &Variables,
+        result: &mut String,
+    ) -> anyhow::Result<()> {
+        self.substitute_names(
+            expr,
+            |name| {
+                self.constant(name)
+                    .or_else(|| variables.get(name).copied())
+                    .map(|value| value.to_expression_text())
+                    .ok_or_else(|| unknown_name(name))
+            },
+            result,
+        )
+    }
+
+    // Every name which is not a function call is replaced by the text given for it, in parentheses
+    fn substitute_names(
+        &self,
+        expr: &str,
+        mut text_of: impl FnMut(&str) -> anyhow::Result<String>,
+        result: &mut String,
+    ) -> anyhow::Result<()> {
+        result.clear();
+
+        let mut last_end = 0;
+
+        for captures in self.identifier_re.captures_iter(expr) {
+            let (Some(name), Some(parenthesis)) = (captures.get(1), captures.get(2)) else {
+                continue;
+            };
+
+            if !parenthesis.is_empty() {
+                continue;
+            }
+
+            result.push_str(&expr[last_end..name.start()]);
+            result.push('(');
+            result.push_str(&text_of(name.as_str())?);
+            result.push(')');
+            last_end = name.end();
+        }
+
+        result.push_str(&expr[last_end..]);
+
+        Ok(())
+    }
+
+    // Bounds of the integers of the configured width, or of i64 without one
+    pub(crate) fn constant(&self, name: &str) -> Option<Value> {
+        let bits = self.config.wrap_bits.unwrap_or(i64::BITS);
+        let max = i64::MAX >> (i64::BITS - bits);
+
+        match name {
+            "MAX" => Some(Value::Int(max)),
+            "MIN" => Some(Value::Int(-max - 1)),
+            _ => None,
+        }
+    }
+
+    // Lazily evaluates every non-blank line of the script, paired with its line number starting from 1
+    // A line can assign a variable like `x = 3` in a session, which the lines after it can use
+    pub fn eval_script<'a>(
+        &'a self,
+        src: &'a str,
+    ) -> impl Iterator<Item = (usize, anyhow::Result<i64>)> + 'a {
+        let mut variables = Variables::new();
+
+        src.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(move |(index, line)| (index + 1, self.eval_script_line(line, &mut variables)))
+    }
+
+    // The value of an assignment is the value it stored, a failed one leaves the variable as it was
+    fn eval_script_line(&self, line: &str, variables: &mut Variables) -> anyhow::Result<i64> {
+        let Some(name) = Session::assigned_variable(line) else {
+            return self.eval_with_variables(line, variables)?.value.to_i64();
+        };
+
+        if self.constant(name).is_some() {
+            return Err(anyhow!("can't assign to constant '{name}'"));
+        }
+
+        let (_, expr) = line.split_once(ASSIGNMENT_OPERATOR).unwrap_or_default();
+        let value = self.eval_with_variables(expr, variables)?.value;
+
+        variables.insert(name.to_string(), value);
+        value.to_i64()
+    }
+
+    // Value of every statement of a program like `1+1; 2*2`, in order, nothing is returned if any of them fails
+    pub fn eval_tuple(&self, program: &str) -> anyhow::Result<Vec<i64>> {
+        split_statements(program)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, statement)| !statement.trim().is_empty())
+            .map(|(index, statement)| {
+                self.eval(statement.to_string())
+                    .map_err(|error| anyhow!("statement {}: {error}", index + 1))
+            })
+            .collect()
+    }
+
+    // Natural-language reading of the expression, token by token
+    pub fn describe(&self, expr: &str) -> anyhow::Result<String> {
+        describe::describe(expr)
+    }
+
+    // Reports every syntax problem of the expression instead of stopping at the first one, without evaluating anything
+    pub fn diagnostics(&self, expr: &str) -> Vec<Diagnostic> {
+        diagnostics::check(expr)
+    }
+
+    // Checks the syntax without evaluating, and that every name which is not a function call is one of the allowed variables
+    pub fn validate_with_allowed_vars(
+        &self,
+        expr: &str,
+        allowed: &HashSet<String>,
+    ) -> anyhow::Result<()> {
+        let tokens = tokenize(expr);
+
+        for (index, token) in tokens.iter().enumerate() {
+            let is_call = tokens
+                .get(index + 1)
+                .is_some_and(|next| next.kind == TokenKind::OpenParenthesis);
+
+            // The reserved constants are not variables, so they are always allowed
+            if token.kind == TokenKind::Identifier
+                && !is_call
+                && !allowed.contains(token.text.as_ref())
+                && self.constant(&token.text).is_none()
+            {
+                return Err(anyhow!(
+                    "variable '{}' at column {} is not allowed",
+                    token.text,
+                    expr[..token.span.start].chars().count() + 1
+                ));
+            }
+        }
+
+        // The variables are known to be allowed, so only the other problems are left
+        self.check_syntax(expr)
+    }
+
+    // First problem the diagnostics found which is not about an unknown name, as names can be variables
+    fn check_syntax(&self, expr: &str) -> anyhow::Result<()> {
+        match self
+            .diagnostics(expr)
+            .into_iter()
+            .find(|diagnostic| !diagnostic.message.starts_with("unknown identifier"))
+        {
+            Some(diagnostic) => Err(at_column(expr, &diagnostic)),
+            None => Ok(()),
+        }
+    }
+
+    // The diagnostics only know the plain syntax, so an input the configuration rewrote, like `2h` with time_units, is checked in its validated form
+    // Otherwise the columns of the errors are the ones of the input as it was written
+    fn check_validated_syntax(&self, expr: &str, validated: &str) -> anyhow::Result<()> {
+        let rewritten = !expr
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .eq(validated.chars());
+
+        self.check_syntax(if rewritten { validated } else { expr })
+    }
+
+    // Value of every parenthesized subexpression, keyed by its span in the original input including the parentheses
+    // Groups which can't be evaluated on their own, like ones in a branch that is not taken, are left out
+    pub fn eval_spans(&self, expr: &str) -> anyhow::Result<Vec<(Range<usize>, i64)>> {
+        self.eval(expr.to_string())?;
+
+        Ok(spans::parenthesized_groups(expr)
+            .into_iter()
+            .filter_map(|span| {
+                let inner = expr[span.start + 1..span.end - 1].to_string();
+
+                self.eval(inner).ok().map(|value| (span, value))
+            })
+            .collect())
+    }
+}
+
+// Error for a name which is neither a constant nor one of the variables
+fn unknown_name(name: &str) -> anyhow::Error {
+    match name {
+        reference if reference.starts_with('$') => anyhow!("no result {reference}"),
+        name => anyhow!("unknown variable '{name}'"),
+    }
+}
+
+// Columns are counted in characters from 1, like in an editor
+fn at_column(expr: &str, diagnostic: &Diagnostic) -> anyhow::Error {
+    anyhow!(
+        "{} at column {}",
+        diagnostic.message,
+        expr[..diagnostic.span.start].chars().count() + 1
+    )
+}
+
+pub(crate) fn values_equivalent(a: Value, b: Value) -> bool {
+    match (a, b) {
+        (Value::Float(_), _) | (_, Value::Float(_)) => {
+            let (a, b) = (a.as_f64(), b.as_f64());
+
+            (a - b).abs() <= FLOAT_TOLERANCE * a.abs().max(b.abs()).max(1.0)
+        }
+        _ => a == b,
+    }
+}
+
+// Only a separator outside of parentheses ends a statement, inside them it can separate the arguments of a call when the decimal separator is a comma
+fn split_statements(program: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (index, c) in program.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            STATEMENT_SEPARATOR if depth == 0 => {
+                statements.push(&program[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+
+    statements.push(&program[start..]);
+
+    statements
+}
+
+// Whitespace is dropped as well, because it never changes the meaning of an expression
+fn normalize_characters(expr: &str, result: &mut String) {
+    for c in expr.chars().filter(|c| !c.is_whitespace()).map(half_width) {
+        match replacement(c) {
+            Some(replacement) => result.push_str(replacement),
+            None => result.push(c),
+        }
+    }
+}
+
+// First closing parenthesis without an opening one, or else the first opening one which is never closed
+fn find_unmatched_parenthesis(expr: &str) -> Option<usize> {
+    let mut open = Vec::new();
+
+    for (index, c) in expr.char_indices() {
+        match half_width(c) {
+            '(' => open.push(index),
+            ')' if open.pop().is_none() => return Some(index),
+            _ => {}
+        }
+    }
+
+    open.first().copied()
+}
+
+// Pasted text can have the full-width forms of the ASCII characters, like `２` typed on an East Asian keyboard
+fn half_width(c: char) -> char {
+    match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - FULL_WIDTH_OFFSET).unwrap_or(c),
+        _ => c,
+    }
+}
+
+// A decimal comma swaps the roles of the comma and the point, so the argument separator needs another character
+// Points only group digits, so they are dropped once their place is checked
+fn replace_decimal_commas(expr: &str) -> anyhow::Result<String> {
+    let bytes = expr.as_bytes();
+    let mut result = String::with_capacity(expr.len());
+
+    // Groups of exactly three digits follow a leading group of one to three, so `12.34` is not taken for `1234`
+    for digits in expr.split(|c: char| !c.is_ascii_digit() && c != '.') {
+        let mut groups = digits.split('.');
+        let leading = groups.next().unwrap_or_default();
+
+        if digits.contains('.')
+            && (!(1..=3).contains(&leading.len()) || groups.any(|group| group.len() != 3))
+        {
+            return Err(anyhow!("misplaced digit group separator"));
+        }
+    }
+    // Digits can only be grouped before the decimal comma of a literal
+    let mut in_fraction = false;
+
+    for (index, c) in expr.char_indices() {
+        match c {
+            '.' => {
+                let between_digits = index > 0
+                    && bytes[index - 1].is_ascii_digit()
+                    && bytes.get(index + 1).is_some_and(u8::is_ascii_digit);
+
+                if in_fraction || !between_digits {
+                    return Err(anyhow!("misplaced digit group separator"));
+                }
+            }
+            ',' => {
+                result.push('.');
+                in_fraction = true;
+            }
+            LOCALIZED_ARGUMENT_SEPARATOR => result.push(','),
+            _ => result.push(c),
+        }
+
+        in_fraction &= c.is_ascii_digit() || c == ',';
+    }
+
+    Ok(result)
+}
+
+// Text an alternative character stands for
+fn replacement(c: char) -> Option<&'static str> {
+    CHARACTER_REPLACEMENTS
+        .iter()
+        .find(|(alternative, _)| *alternative == c)
+        .map(|(_, replacement)| *replacement)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const LONG_EXPR : &str = "((1+(-2*(3-(4/(-5+6*(-7-(8/(-9+1))))))))+((11*(-12+13))/(14-(15*(-16+17))))-(18+(-19*(20-(21/(-22+23*(-24-(25/(-26+27))))))))+(28*(-29+(30/(31-(32*(-33+34))))))-(35+(-36*(37-(38/(-39+40*(-41-(42/(-43+44))))))))+(45*(-46+(47/(48-(49*(-50+51))))))-(52+(-53*(54-(55/(-56+57*(-58-(59/(-60+61))))))))+(62*(-63+(64/(65-(66*(-67+68))))))-(69+(-70*(71-(72/(-73+74*(-75-(76/(-77+78))))))))+(79*(-80+(81/(82-(83*(-84+85))))))-(86+(-87*(88-(89/(-90+91*(-92-(93/(-94+95))))))))+(96*(-97+(98/(99-(100*(-101+102))))))+(103+(-104*(105-(106/(-107+108*(-109-(110/(-111+112))))))))+(113*(-114+(115/(116-(117*(-118+119))))))-(120+(-121*(122-(123/(-124+125*(-126-(127/(-128+129))))))))+(130*(-131+(132/(133-(134*(-135+136)))))))";
+    const SHORT_EXPR: &str = "2+3*(1+4/2)";
+
+    fn eval_str(expr: &str) -> anyhow::Result<i64> {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        eval_str_custom(&evaluator, expr)
+    }
+
+    fn evaluator_with(configure: impl FnOnce(&mut Config)) -> ExpressionEvaluator {
+        let mut config = Config::default();
+
+        configure(&mut config);
+
+        ExpressionEvaluator::with_config(config).unwrap()
+    }
+
+    fn eval_str_custom(evaluator: &ExpressionEvaluator, expr: &str) -> anyhow::Result<i64> {
+        evaluator.eval(String::from_str(expr)?)
+    }
+
+    #[test]
+    fn prints_results_in_base_of_literals() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(evaluator.eval_to_string("0xF0 + 0x0F"), "0xff");
+        assert_eq!(evaluator.eval_to_string("0x10 - 0x11"), "-0x1");
+        assert_eq!(evaluator.eval_to_string("0b101 * 0b10"), "0b1010");
+        assert_eq!(evaluator.eval_to_string("0xF0 + 0b1"), "241");
+        assert_eq!(evaluator.eval_to_string("0x10 + 1"), "17");
+        assert_eq!(evaluator.eval("0xff".to_string()).unwrap(), 255);
+        assert_eq!(evaluator.diagnostics("0xF0 * 0b1"), Vec::new());
+        assert_eq!(
+            evaluator.eval_to_string("0xfg"),
+            "Error: invalid literal '0xfg'"
+        );
+
+        let evaluator = ExpressionEvaluator::builder()
+            .decimal_output(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(evaluator.eval_to_string("0xF0 + 0x0F"), "255");
+    }
+
+    #[test]
+    fn rejects_adjacent_operators() {
+        assert_eq!(
+            eval_str("2*/3").unwrap_err().to_string(),
+            "unexpected operator '/' after '*' at column 3"
+        );
+        assert_eq!(
+            eval_str("2//3").unwrap_err().to_string(),
+            "unexpected operator '/' after '/' at column 3"
+        );
+        assert_eq!(
+            eval_str("5 + * 2").unwrap_err().to_string(),
+            "unexpected operator '*' after '+' at column 5"
+        );
+        assert!(eval_str("1 <= 2 < = 3").is_err());
+
+        assert_eq!(eval_str("1*-2").unwrap(), -2);
+        assert_eq!(eval_str("2--3").unwrap(), 5);
+        assert_eq!(eval_str("2^3*4/-2").unwrap(), -16);
+        assert_eq!(eval_str("1 <= -2").unwrap(), 0);
+    }
+
+    #[test]
+    fn simplifies_expressions() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let simplify = |expr| evaluator.simplify(expr).unwrap();
+
+        assert_eq!(simplify("2+3*4"), "14");
+        assert_eq!(simplify("x+0"), "x");
+        assert_eq!(simplify("0+x"), "x");
+        assert_eq!(simplify("x-0"), "x");
+        assert_eq!(simplify("x*1"), "x");
+        assert_eq!(simplify("1*x"), "x");
+        assert_eq!(simplify("x*0"), "0");
+        assert_eq!(simplify("(y+2)*0+x"), "x");
+        assert_eq!(simplify("x*(2+3)-4/2"), "x*5-2");
+        assert_eq!(simplify("x * (3-2) + (1-1)"), "x");
+        assert_eq!(simplify("(x+1)*(2-3)"), "(x+1)*(-1)");
+        assert_eq!(simplify("(x-(y-z))^(1+1)"), "(x-(y-z))^2");
+        assert_eq!(simplify("|-3|*x + abs(y)"), "3*x+abs(y)");
+        assert_eq!(simplify("1<x<2+1"), "1<x<3");
+        assert_eq!(simplify("rand(1,6)*x"), "rand(1,6)*x");
+        assert_eq!(simplify("x/(1-1)"), "x/0");
+
+        assert_eq!(
+            evaluator.simplify("1/0").unwrap_err().to_string(),
+            "division by zero"
+        );
+        assert!(evaluator.simplify("x*").is_err());
+    }
+
+    #[test]
+    fn renders_parse_trees() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let tree = |expr| evaluator.tree(expr).unwrap();
+
+        assert_eq!(tree("2+3*4"), "+\n|-- 2\n`-- *\n    |-- 3\n    `-- 4");
+        assert_eq!(
+            tree("(1-x)*pow(-2, y)"),
+            "*\n\
+             |-- -\n\
+             |   |-- 1\n\
+             |   `-- x\n\
+             `-- pow()\n    \
+                 |-- -\n    \
+                 |   `-- 2\n    \
+                 `-- y"
+        );
+        assert_eq!(tree("1 < x <= 3"), "< <=\n|-- 1\n|-- x\n`-- 3");
+        assert_eq!(tree("x+0"), "+\n|-- x\n`-- 0");
+        assert_eq!(tree("7"), "7");
+        assert!(evaluator.tree("2*").is_err());
+    }
+
+    #[test]
+    fn resolves_names_on_evaluation() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let lookups = std::cell::Cell::new(0);
+        let resolver = |name: &str| {
+            lookups.set(lookups.get() + 1);
+
+            match name {
+                "speed" => Some(60),
+                "time" => Some(3),
+                _ => None,
+            }
+        };
+
+        assert_eq!(
+            evaluator.eval_with_resolver("speed*2", resolver).unwrap(),
+            120
+        );
+        assert_eq!(
+            evaluator
+                .eval_with_resolver("speed*time - speed + MAX*0", resolver)
+                .unwrap(),
+            120
+        );
+        assert_eq!(lookups.get(), 3);
+        assert_eq!(
+            evaluator
+                .eval_with_resolver("speed/distance", resolver)
+                .unwrap_err()
+                .to_string(),
+            "unknown variable 'distance'"
+        );
+    }
+
+    #[test]
+    fn evaluates_over_values() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        let squares: Vec<i64> = evaluator
+            .eval_over("x*x", "x", 0..=5)
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+
+        assert_eq!(squares, [0, 1, 4, 9, 16, 25]);
+
+        let results: Vec<String> = evaluator
+            .eval_over("6/x", "x", [2, 0].into_iter())
+            .chain(evaluator.eval_over("x*", "x", 1..3))
+            .map(|result| result.map_or_else(|error| error.to_string(), |value| value.to_string()))
+            .collect();
+
+        assert_eq!(results[..2], ["3", "division by zero"]);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[2], results[3]);
+        assert_eq!(evaluator.eval_over("y", "x", 1..2).count(), 1);
+    }
+
+    #[test]
+    fn records_inputs_with_results() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        let record = evaluator.eval_recorded("  2+3*4\n");
+        assert_eq!(record.input, "2+3*4");
+        assert_eq!(record.result.unwrap(), 14);
+
+        let record = evaluator.eval_recorded("1/0");
+        assert_eq!(record.input, "1/0");
+        assert_eq!(record.result.unwrap_err().to_string(), "division by zero");
+    }
+
+    #[test]
+    fn evaluates_sign_and_magnitude() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(evaluator.eval_signed("2*3").unwrap(), (Sign::Positive, 6));
+        assert_eq!(evaluator.eval_signed("2-3").unwrap(), (Sign::Negative, 1));
+        assert_eq!(evaluator.eval_signed("3-3").unwrap(), (Sign::Zero, 0));
+        assert_eq!(
+            evaluator.eval_signed("-9223372036854775807-1").unwrap(),
+            (Sign::Negative, 1 << 63)
+        );
+        assert!(evaluator.eval_signed("1/0").is_err());
+    }
+
+    #[test]
+    fn reports_largest_intermediate_magnitude() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(
+            evaluator.eval_with_stats("(100*100)-9999").unwrap(),
+            (1, 10000)
+        );
+        assert_eq!(evaluator.eval_with_stats("2-5").unwrap(), (-3, 3));
+        assert_eq!(evaluator.eval_with_stats("-7*3/21").unwrap(), (-1, 21));
+        assert_eq!(evaluator.eval_with_stats("42").unwrap(), (42, 42));
+        assert_eq!(
+            evaluator.eval_with_stats("-9223372036854775807-1").unwrap(),
+            (i64::MIN, i64::MAX)
+        );
+        assert!(evaluator.eval_with_stats("1/0").is_err());
+    }
+
+    #[test]
+    fn counts_each_kind_of_operation() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let op_stats = |expr| evaluator.eval_with_op_stats(expr).unwrap();
+
+        assert_eq!(
+            op_stats("2+3*4-1"),
+            (
+                13,
+                OpStats {
+                    additions: 1,
+                    subtractions: 1,
+                    multiplications: 1,
+                    divisions: 0,
+                }
+            )
+        );
+        assert_eq!(
+            op_stats("-8/2/2-(1+1)*3").1,
+            OpStats {
+                additions: 1,
+                subtractions: 1,
+                multiplications: 1,
+                divisions: 2,
+            }
+        );
+        assert_eq!(op_stats("-5").1, OpStats::default());
+    }
+
+    #[test]
+    fn evaluates_integer_bounds() {
+        assert_eq!(eval_str("MAX").unwrap(), i64::MAX);
+        assert_eq!(eval_str("MIN").unwrap(), i64::MIN);
+        assert_eq!(eval_str("MAX-1").unwrap(), i64::MAX - 1);
+        assert_eq!(eval_str("MIN+MAX").unwrap(), -1);
+        assert_eq!(
+            eval_str("MAX+1").unwrap_err().to_string(),
+            "integer overflow"
+        );
+        assert_eq!(
+            eval_str("MIN-1").unwrap_err().to_string(),
+            "integer overflow"
+        );
+        assert_eq!(eval_str("MIN*1").unwrap(), i64::MIN);
+        assert_eq!(eval_str("MIN/2").unwrap(), -4611686018427387904);
+        assert_eq!(
+            ExpressionEvaluator::new()
+                .unwrap()
+                .eval_decimal("MIN/3", 5)
+                .unwrap(),
+            "-3074457345618258602.66666"
+        );
+        assert_eq!(
+            eval_str("2*MIN/2").unwrap_err().to_string(),
+            "integer overflow"
+        );
+        for expr in ["MIN/-1", "-MIN", "0-MIN"] {
+            assert_eq!(
+                eval_str(expr).unwrap_err().to_string(),
+                "integer overflow",
+                "{expr}"
+            );
+        }
+
+        let wrap_8 = evaluator_with(|config| config.wrap_bits = Some(8));
+
+        assert_eq!(eval_str_custom(&wrap_8, "MAX").unwrap(), 127);
+        assert_eq!(eval_str_custom(&wrap_8, "MIN").unwrap(), -128);
+        assert_eq!(eval_str_custom(&wrap_8, "MAX+1").unwrap(), -128);
+
+        let variables = Variables::from([("MAX".to_string(), Value::Int(1))]);
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(
+            evaluator
+                .eval_with_variables("MAX", &variables)
+                .unwrap()
+                .value,
+            Value::Int(i64::MAX)
+        );
+    }
+
+    #[test]
+    fn traces_evaluation_steps() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let trace = |expr| evaluator.eval_with_trace(expr).unwrap().1;
+
+        assert_eq!(trace("2+3*(1+4/2)"), ["2+3*(1+4/2)", "2+3*3", "2+9", "11"]);
+        assert_eq!(trace("2*3+4*5-1"), ["2*3+4*5-1", "6+20-1", "25"]);
+        assert_eq!(trace("(1+1)*(2+2)"), ["(1+1)*(2+2)", "2*4", "8"]);
+        assert_eq!(trace("7"), ["7"]);
+        assert_eq!(
+            trace("if(1<2, 10, 1/0)+1"),
+            ["if(1<2,10,1/0)+1", "(10)+1", "10+1", "11"]
+        );
+        assert!(evaluator.eval_with_trace("1/0").is_err());
+    }
+
+    #[test]
+    fn constructs_with_default_config() {
+        let evaluators = [
+            ExpressionEvaluator::new().unwrap(),
+            ExpressionEvaluator::with_config(Config::default()).unwrap(),
+            ExpressionEvaluator::builder().build().unwrap(),
+            ExpressionEvaluatorBuilder::from(Config::default())
+                .build()
+                .unwrap(),
+        ];
+
+        for expr in [
+            "2+3*(1+4/2)",
+            "7/2",
+            "-(2^3)",
+            "1.5",
+            "--1",
+            "2h",
+            "1000000*1000000",
+        ] {
+            let expected = evaluators[0].eval_to_string(expr);
+
+            for evaluator in &evaluators {
+                assert_eq!(evaluator.eval_to_string(expr), expected);
+            }
+        }
+
+        assert_eq!(evaluators[0].eval_to_string("7/2"), "3");
+        assert_eq!(
+            evaluators[0].eval_to_string("1000000*1000000"),
+            "1000000000000"
+        );
+        assert!(eval_str("1.5").is_err());
+        assert!(eval_str("--1").is_err());
+    }
+
+    #[test]
+    fn calculates_correct_result() {
+        let test_data = vec![
+            ("1+1", 2),
+            ("1-1", 0),
+            ("1/1", 1),
+            ("1*1", 1),
+            ("1+(1-1)", 1),
+            ("1000*1000", 1000000),
+            ("-1-1", -2),
+            ("-10/5", -2),
+            ("-10*-10", 100),
+            ("1+-1", 0),
+            ("1--1", 2),
+        ];
+
+        for (expr, result) in test_data {
+            assert_eq!(eval_str(expr).unwrap(), result);
+        }
+    }
+
+    #[test]
+    fn handles_whitespace() {
+        assert_eq!(
+            eval_str(" \t2\n      +3\t*(  1  + 4 /2)\t\t").unwrap(),
+            eval_str("2+3*(1+4/2)").unwrap()
+        );
+    }
+
+    #[test]
+    fn whitespace_never_changes_meaning() {
+        for expr in ["3 - 5", "3-5", "3 -5", "3- 5", " 3  -  5 "] {
+            assert_eq!(eval_str(expr).unwrap(), -2);
+        }
+
+        assert_eq!(eval_str("3 - -5").unwrap(), 8);
+        assert_eq!(eval_str("3 * -5").unwrap(), -15);
+    }
+
+    #[test]
+    fn evaluates_multiplication_chains_left_to_right() {
+        assert_eq!(eval_str("8/2/2/2").unwrap(), 1);
+        assert_eq!(eval_str("2*3/4*5").unwrap(), 5);
+        assert_eq!(eval_str("12*-7/18/25").unwrap(), 0);
+        assert_eq!(eval_str("100/10/5*3").unwrap(), 6);
+    }
+
+    #[test]
+    fn cancels_negations_left_by_replacements() {
+        assert_eq!(eval_str("2*-(-3)").unwrap(), 6);
+        assert_eq!(eval_str("1+-(-2)").unwrap(), 3);
+        assert_eq!(eval_str("1+-2*-6").unwrap(), 13);
+        assert_eq!(eval_str("-4-(-7)*(-14)*-15").unwrap(), 1466);
+        assert_eq!(eval_str("-(-(-3))").unwrap(), -3);
+        assert!(eval_str("1++1").is_err());
+    }
+
+    #[test]
+    fn whitespace_insertion_preserves_results() {
+        const WHITESPACE: &[&str] = &[" ", "\t", "  ", "\n", " \t "];
+
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        // Pseudo-random but deterministic insertion points, so failures are reproducible
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next_random = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for expr in [
+            SHORT_EXPR,
+            "1--1",
+            "-10*-10",
+            "2^3^2-|1-4|",
+            "if(1-1, 5, powmod(2, 10, 7))",
+            "3*(5-((2-4)-(-4)))",
+            "1/0",
+            "2(3+1)",
+        ] {
+            let expected = eval_str_custom(&evaluator, expr).ok();
+
+            for _ in 0..50 {
+                let mut spaced = String::new();
+
+                for c in expr.chars() {
+                    if next_random() % 3 == 0 {
+                        spaced.push_str(WHITESPACE[next_random() as usize % WHITESPACE.len()]);
+                    }
+
+                    spaced.push(c);
+                }
+
+                assert_eq!(
+                    eval_str_custom(&evaluator, &spaced).ok(),
+                    expected,
+                    "{spaced:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn handles_parentheses() {
+        let test_data = vec![
+            ("(1)", 1),
+            ("(1-1)", 0),
+            ("60/(((2+3)))", 12),
+            ("3*(5-((2-4)-(-4)))", 9),
+        ];
+
+        for (expr, result) in test_data {
+            assert_eq!(eval_str(expr).unwrap(), result);
+        }
+    }
+
+    #[test]
+    fn calculates_integer_division_correctly() {
+        assert_eq!(eval_str("1/2").unwrap(), 0);
+        assert_eq!(eval_str("25/12").unwrap(), 2);
+        assert_eq!(eval_str("3/2*2").unwrap(), 2);
+    }
+
+    // Test error cases to guarantee that the program doesn't panic or return a number incorrectly
+
+    #[test]
+    fn rejects_zero_division() {
+        assert!(eval_str("1/0").is_err());
+    }
+
+    #[test]
+    fn handles_syntax_error() {
+        assert!(eval_str("()").is_err());
+        assert!(eval_str("(1+1").is_err());
+        assert!(eval_str("1+1)").is_err());
+        assert!(eval_str("1++1").is_err());
+        assert!(eval_str("2(3+1)").is_err());
+        assert!(eval_str("(2+2)(3+3)").is_err());
+        assert!(eval_str("(1)2").is_err());
+        assert!(eval_str("--1").is_err());
+        assert!(eval_str("1+a").is_err());
+        assert!(eval_str("asdf").is_err());
+    }
+
+    #[test]
+    fn reports_position_of_unmatched_parenthesis() {
+        let error = |expr| eval_str(expr).unwrap_err().to_string();
+
+        assert_eq!(error("(1+2"), "unmatched parenthesis at position 1");
+        assert_eq!(error("1+2)"), "unmatched parenthesis at position 4");
+        assert_eq!(error("(1+2))*3"), "unmatched parenthesis at position 6");
+        assert_eq!(error("2*((1+2)"), "unmatched parenthesis at position 3");
+        assert_eq!(error("abs(2"), "unmatched parenthesis at position 4");
+        assert_eq!(error("1 + (2"), "unmatched parenthesis at position 5");
+        assert_eq!(error("2^2+(1"), "unmatched parenthesis at position 5");
+        assert_eq!(error(" 2 ^ (1+2))"), "unmatched parenthesis at position 11");
+        assert_eq!(error("（1+2"), "unmatched parenthesis at position 1");
+    }
+
+    #[test]
+    fn rejects_float() {
+        assert!(eval_str("1.0+1").is_err());
+        assert!(eval_str("3/2.0").is_err());
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert!(eval_str("9223372036854775807+1").is_err());
+        assert!(eval_str("-9223372036854775807-2").is_err());
+        assert!(eval_str("3037000500*3037000500").is_err());
+        assert!(eval_str("(-9223372036854775807-1)/-1").is_err());
+        assert!(eval_str("|-9223372036854775807-1|").is_err());
+        assert!(eval_str("99999999999999999999").is_err());
+        assert_eq!(eval_str("-9223372036854775807-1").unwrap(), i64::MIN);
+    }
+
+    #[test]
+    fn evaluates_if_builtin() {
+        let test_data = vec![
+            ("if(1, 10, 20)", 10),
+            ("if(0, 10, 20)", 20),
+            ("if(-3, 10, 20)", 10),
+            ("if(2-2, 1, 2)*3", 6),
+            ("1-if(0, 1, -2)", 3),
+            ("if((1+1)*0, 1, (2+3)*2)", 10),
+            ("if(1, if(0, 1, 2), 3)", 2),
+            ("if(if(1, 0, 1), 1, 2)", 2),
+        ];
+
+        for (expr, result) in test_data {
+            assert_eq!(eval_str(expr).unwrap(), result);
+        }
+    }
+
+    #[test]
+    fn short_circuits_if_builtin() {
+        assert_eq!(eval_str("if(1, 5, 10/0)").unwrap(), 5);
+        assert_eq!(eval_str("if(0, (1/0), 7)").unwrap(), 7);
+        assert_eq!(eval_str("if(1, 5, if(1/0, 1, 2))").unwrap(), 5);
+        assert!(eval_str("if(0, 5, 10/0)").is_err());
+        assert!(eval_str("if(1/0, 1, 2)").is_err());
+    }
+
+    #[test]
+    fn evaluates_deeply_nested_expressions() {
+        const DEPTH: usize = 5000;
+
+        let nested_conditions = format!("{}1{}", "if(".repeat(DEPTH), ", 2, 3)".repeat(DEPTH));
+
+        assert_eq!(eval_str(&nested_conditions).unwrap(), 2);
+
+        let nested_parentheses = format!("{}1{}", "(1+".repeat(DEPTH), ")".repeat(DEPTH));
+
+        assert_eq!(eval_str(&nested_parentheses).unwrap(), DEPTH as i64 + 1);
+    }
+
+    #[test]
+    fn rejects_invalid_function_calls() {
+        assert!(eval_str("if(1, 2)").is_err());
+        assert!(eval_str("if(1, 2, 3, 4)").is_err());
+        assert!(eval_str("if(1, 2, 3").is_err());
+        assert!(eval_str("2if(1, 2, 3)").is_err());
+        assert!(eval_str("foo(1)").is_err());
+        assert!(eval_str("(1, 2)").is_err());
+        assert!(eval_str("1, 2").is_err());
+    }
+
+    #[test]
+    fn evaluates_exponentiation() {
+        let test_data = vec![
+            ("2^10", 1024),
+            ("2^3^2", 512),
+            ("(2^3)^2", 64),
+            ("-2^2", -4),
+            ("(0-2)^2", 4),
+            ("(0-2)^3", -8),
+            ("2*3^2", 18),
+            ("3^2*2", 18),
+            ("2^(1+2)", 8),
+            ("2^-0", 1),
+            ("|-3|^2", 9),
+            ("abs(-2)^3", 8),
+            ("pow(3, 3)", 27),
+            ("0^0", 1),
+        ];
+
+        for (expr, result) in test_data {
+            assert_eq!(eval_str(expr).unwrap(), result);
+        }
+
+        assert!(eval_str("2^-1").is_err());
+        assert!(eval_str("2^63").is_err());
+        assert!(eval_str("^2").is_err());
+        assert!(eval_str("2^").is_err());
+        assert!(eval_str("2^^2").is_err());
+        assert_eq!(
+            evaluator_with(|config| config.wrap_bits = Some(8))
+                .eval(String::from_str("2^8+3").unwrap())
+                .unwrap(),
+            3
+        );
+    }
+
+    #[test]
+    fn evaluates_superscript_powers() {
+        assert_eq!(eval_str("5²").unwrap(), 25);
+        assert_eq!(eval_str("2³").unwrap(), 8);
+        assert_eq!(eval_str("(1+1)³").unwrap(), 8);
+        assert_eq!(eval_str("10²+1").unwrap(), 101);
+        assert_eq!(eval_str("2*3²").unwrap(), 18);
+        assert_eq!(eval_str("-3²").unwrap(), -9);
+        assert!(eval_str("²").is_err());
+        assert!(eval_str("(10^9)³").is_err());
+    }
+
+    #[test]
+    fn evaluates_alternative_operators() {
+        assert_eq!(eval_str("2·3").unwrap(), 6);
+        assert_eq!(eval_str("2＊3").unwrap(), 6);
+        assert_eq!(eval_str("2 × 3 + 1").unwrap(), 7);
+        assert_eq!(eval_str("12÷4").unwrap(), 3);
+        assert!(eval_str("2··3").is_err());
+        assert_eq!(eval_str("２＋３").unwrap(), 5);
+        assert_eq!(eval_str("（１＋１）＊２").unwrap(), 4);
+        assert_eq!(eval_str("１２　÷　４").unwrap(), 3);
+
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(
+            evaluator.describe("2·3÷4").unwrap(),
+            "2 times 3 divided by 4"
+        );
+        assert!(evaluator.diagnostics("2 × 3").is_empty());
+    }
+
+    #[test]
+    fn evaluates_absolute_value_bars() {
+        let test_data = vec![
+            ("|-3|", 3),
+            ("|3-7|", 4),
+            ("|3|", 3),
+            ("2*|1-5|", 8),
+            ("|1|-|2|", -1),
+            ("|(2-5)*2|", 6),
+            ("|1-|2-5||", 2),
+            ("||-3||", 3),
+            ("abs(-5)", 5),
+        ];
+
+        for (expr, result) in test_data {
+            assert_eq!(eval_str(expr).unwrap(), result);
+        }
+    }
+
+    #[test]
+    fn rejects_unmatched_absolute_value_bars() {
+        assert!(eval_str("|3").is_err());
+        assert!(eval_str("3|").is_err());
+        assert!(eval_str("2|3|").is_err());
+        assert!(eval_str("|3|4").is_err());
+        assert!(eval_str("||").is_err());
+    }
+
+    #[test]
+    fn evaluates_script_lines() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        let results = evaluator
+            .eval_script("1+1\n\n  \n2*3\n1/0\n10/(2+3)")
+            .map(|(line, result)| (line, result.ok()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            results,
+            vec![(1, Some(2)), (4, Some(6)), (5, None), (6, Some(2))]
+        );
+
+        let results = evaluator
+            .eval_script("x = 3\nx*2\ny = x+1\nx = y/0\nx*y\nMAX = 1\nx == 3\nz")
+            .map(|(line, result)| (line, result.map_err(|error| error.to_string())))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            results,
+            vec![
+                (1, Ok(3)),
+                (2, Ok(6)),
+                (3, Ok(4)),
+                (4, Err("division by zero".to_string())),
+                (5, Ok(12)),
+                (6, Err("can't assign to constant 'MAX'".to_string())),
+                (7, Ok(1)),
+                (8, Err("unknown variable 'z'".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_functions_of_resolver() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let resolver = |name: &str, arguments: &[i64]| match (name, arguments) {
+            ("square", [x]) => Some(Ok(x * x)),
+            ("square", _) => Some(Err(anyhow!("square expects 1 argument"))),
+            _ => None,
+        };
+        let eval = |expr| evaluator.eval_with_function_resolver(expr, &resolver);
+
+        assert_eq!(eval("square(3)+1").unwrap(), 10);
+        assert_eq!(eval("square(square(2))*abs(-2)").unwrap(), 32);
+        assert_eq!(
+            eval("square(1,2)").unwrap_err().to_string(),
+            "square expects 1 argument"
+        );
+        assert_eq!(
+            eval("cube(2)").unwrap_err().to_string(),
+            "unknown function 'cube'"
+        );
+    }
+
+    #[test]
+    fn evaluates_all_statements_or_none() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(evaluator.eval_tuple("1+1; 2*2; 3*3").unwrap(), [2, 4, 9]);
+        assert_eq!(evaluator.eval_tuple("pow(2,3);;(1+2)*3;").unwrap(), [8, 9]);
+        assert_eq!(
+            evaluator
+                .eval_tuple("1+1; 1/0; 3*3")
+                .unwrap_err()
+                .to_string(),
+            "statement 2: division by zero"
+        );
+
+        let evaluator = ExpressionEvaluator::builder()
+            .float_mode(true)
+            .decimal_separator(',')
+            .build()
+            .unwrap();
+
+        assert_eq!(evaluator.eval_tuple("pow(2;3); 1+1").unwrap(), [8, 2]);
+    }
+
+    #[test]
+    fn describes_expressions() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        let test_data = vec![
+            ("2+3*4", "2 plus 3 times 4"),
+            (
+                "(1+2)/3",
+                "open paren 1 plus 2 close paren divided by 3",
+            ),
+            ("-10*-10", "negative 10 times negative 10"),
+            ("1--1", "1 minus negative 1"),
+            ("2^3 - 5²", "2 to the power of 3 minus 5 squared"),
+            (
+                "powmod(2, 3, |-5|)",
+                "powmod open paren 2 comma 3 comma absolute value of negative 5 end absolute value close paren",
+            ),
+            ("|x|", "absolute value of x end absolute value"),
+            ("|x - y|*2", "absolute value of x minus y end absolute value times 2"),
+            (
+                "||x|-$1|",
+                "absolute value of absolute value of x end absolute value minus $1 end absolute value",
+            ),
+            ("x-1", "x minus 1"),
+        ];
+
+        for (expr, description) in test_data {
+            assert_eq!(evaluator.describe(expr).unwrap(), description);
+        }
+
+        assert!(evaluator.describe("1+@").is_err());
+        assert!(evaluator.describe("  ").is_err());
+    }
+
+    #[test]
+    fn reports_every_diagnostic() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        let diagnostics = evaluator.diagnostics("1++a)");
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.span.clone())
+                .collect::<Vec<_>>(),
+            vec![1..3, 3..4, 4..5]
+        );
+        assert_eq!(diagnostics[0].message, "unexpected operator '+' after '+'");
+
+        let diagnostics = evaluator.diagnostics("(2 * / 3 + @");
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.span.clone())
+                .collect::<Vec<_>>(),
+            vec![0..1, 3..6, 11..12, 12..12]
+        );
+    }
+
+    #[test]
+    fn reports_no_diagnostics_for_valid_expressions() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        for expr in [
+            SHORT_EXPR,
+            LONG_EXPR,
+            "1--1",
+            "-10*-10",
+            "if(1, 2, |3-4|)",
+            "|1-|2-5||",
+            "1/0",
+            "2^-3^2",
+            "(1+1)³*5²",
+            "MAX/2-MIN",
+        ] {
+            assert_eq!(evaluator.diagnostics(expr), vec![]);
+        }
+
+        for expr in [
+            "--1", "2(3+1)", "(1)2", "()", "1,2", "|3", "3|", "²", "2^^2",
+        ] {
+            assert!(!evaluator.diagnostics(expr).is_empty());
+        }
+    }
+
+    #[test]
+    fn reports_subexpression_spans() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(evaluator.eval_spans(SHORT_EXPR).unwrap(), vec![(4..11, 3)]);
+        assert_eq!(
+            evaluator
+                .eval_spans("2 * (3 + (4 - 1)) - abs((-5))")
+                .unwrap(),
+            vec![(4..17, 6), (9..16, 3), (24..28, -5)]
+        );
+        assert_eq!(
+            evaluator.eval_spans("if(0, (1/0), (2))").unwrap(),
+            vec![(13..16, 2)]
+        );
+        assert!(evaluator.eval_spans("7").unwrap().is_empty());
+        assert!(evaluator.eval_spans("(1+2").is_err());
+    }
+
+    #[test]
+    fn evaluates_powmod_builtin() {
+        let test_data = vec![
+            ("powmod(2, 100, 1000)", 376),
+            ("powmod(3, 200, 13)", 9),
+            ("powmod(4, 13, 497)", 445),
+            ("powmod(-2, 3, 5)", 2),
+            ("powmod(5, 0, 7)", 1),
+            ("powmod(5, 0, 1)", 0),
+            ("powmod(2, 10, -1000)", 24),
+            (
+                "powmod(9223372036854775807, 12345, 9223372036854775783)",
+                1369929176135165477,
+            ),
+            ("1+powmod(2, 2+2, (3*5))", 2),
+        ];
+
+        for (expr, result) in test_data {
+            assert_eq!(eval_str(expr).unwrap(), result);
+        }
+    }
+
+    #[test]
+    fn evaluates_sign_builtin() {
+        assert_eq!(eval_str("sign(-42)").unwrap(), -1);
+        assert_eq!(eval_str("sign(0)").unwrap(), 0);
+        assert_eq!(eval_str("sign(7)").unwrap(), 1);
+        assert_eq!(eval_str("sign(3-10)*5").unwrap(), -5);
+        assert_eq!(
+            eval_str("sign(1, 2)").unwrap_err().to_string(),
+            "sign expects 1 argument, got 2"
+        );
+
+        let float = evaluator_with(|config| config.set_float_arithmetic(true));
+        let sign = |expr: &str| float.eval_value(expr.to_string()).unwrap();
+
+        assert_eq!(sign("sign(-0.0)"), Value::Int(0));
+        assert_eq!(sign("sign(0.0*-1)"), Value::Int(0));
+        assert_eq!(sign("sign(-0.5)"), Value::Int(-1));
+        assert_eq!(sign("sign(1/3)"), Value::Int(1));
+    }
+
+    #[test]
+    fn rejects_invalid_powmod_arguments() {
+        assert!(eval_str("powmod(2, 10, 0)").is_err());
+        assert!(eval_str("powmod(2, -1, 7)").is_err());
+        assert!(eval_str("powmod(2, 10)").is_err());
+        assert!(eval_str("abs(1, 2)").is_err());
+    }
+
+    #[test]
+    fn wraps_to_configured_width() {
+        let wrap_8 = evaluator_with(|config| config.wrap_bits = Some(8));
+
+        assert_eq!(eval_str_custom(&wrap_8, "200+100").unwrap(), 44);
+        assert_eq!(eval_str_custom(&wrap_8, "127+1").unwrap(), -128);
+        assert_eq!(eval_str_custom(&wrap_8, "-100-100").unwrap(), 56);
+        assert_eq!(eval_str_custom(&wrap_8, "16*16+1").unwrap(), 1);
+        assert_eq!(eval_str_custom(&wrap_8, "-128/-1").unwrap(), -128);
+        assert_eq!(eval_str_custom(&wrap_8, "(100+100)/2").unwrap(), -28);
+
+        let wrap_16 = evaluator_with(|config| config.wrap_bits = Some(16));
+
+        assert_eq!(eval_str_custom(&wrap_16, "30000+30000").unwrap(), -5536);
+        assert_eq!(eval_str_custom(&wrap_16, "-32768-1").unwrap(), 32767);
+        assert_eq!(eval_str_custom(&wrap_16, "256*256").unwrap(), 0);
+        assert_eq!(eval_str_custom(&wrap_16, "200+100").unwrap(), 300);
+    }
+
+    #[test]
+    fn rejects_invalid_wrap_width() {
+        for bits in [0, 65] {
+            let config = Config {
+                wrap_bits: Some(bits),
+                ..Config::default()
+            };
+
+            assert!(ExpressionEvaluator::with_config(config).is_err());
+        }
+    }
+
+    #[test]
+    fn strips_enclosing_parentheses() {
+        let test_data = vec![
+            ("(((1+1)))", Some(2)),
+            ("(1+1)*(2+2)", Some(8)),
+            ("((1+1)*(2+2))", Some(8)),
+            ("(-(2))", Some(-2)),
+            ("((2)^2)", Some(4)),
+            ("()", None),
+            ("((1+1)", None),
+        ];
+
+        for (expr, expected) in test_data {
+            assert_eq!(eval_str(expr).ok(), expected, "{expr}");
+        }
+
+        assert_eq!(solver::strip_enclosing_parentheses("(((1)))"), "1");
+        assert_eq!(solver::strip_enclosing_parentheses("(1)+(2)"), "(1)+(2)");
+        assert_eq!(solver::strip_enclosing_parentheses("abs(1)"), "abs(1)");
+    }
+
+    #[test]
+    fn allows_double_negation() {
+        let evaluator = evaluator_with(|config| config.allow_double_negation = true);
+
+        assert_eq!(eval_str_custom(&evaluator, "--1").unwrap(), 1);
+        assert_eq!(eval_str_custom(&evaluator, "---1").unwrap(), -1);
+        assert_eq!(eval_str_custom(&evaluator, "1--1").unwrap(), 2);
+        assert_eq!(eval_str_custom(&evaluator, "--2*3").unwrap(), 6);
+
+        assert!(eval_str("--1").is_err());
+        assert_eq!(eval_str("1--1").unwrap(), 2);
+    }
+
+    #[test]
+    fn limits_number_of_terms() {
+        let evaluator = evaluator_with(|config| config.max_terms = Some(3));
+
+        assert_eq!(eval_str_custom(&evaluator, "1+2+3").unwrap(), 6);
+        assert_eq!(eval_str_custom(&evaluator, "-1-2-3").unwrap(), -6);
+        assert_eq!(eval_str_custom(&evaluator, "1*2*3*4-5").unwrap(), 19);
+        assert_eq!(eval_str_custom(&evaluator, "(1+2+3)+(4+5+6)").unwrap(), 21);
+
+        let error = eval_str_custom(&evaluator, "1+2+3+4").unwrap_err();
+
+        assert_eq!(error.to_string(), "too many terms");
+        assert!(eval_str_custom(&evaluator, "(1-2+3-4)").is_err());
+        assert!(eval_str_custom(&evaluator, "-1-2-3-4").is_err());
+    }
+
+    #[test]
+    fn promotes_division_to_float() {
+        let evaluator = evaluator_with(|config| config.divide_promotes_to_float = true);
+
+        let eval_value = |expr: &str| {
+            evaluator
+                .eval_value(String::from_str(expr).unwrap())
+                .unwrap()
+        };
+
+        assert_eq!(eval_value("5/2"), Value::Float(2.5));
+        assert_eq!(eval_value("5/2+1"), Value::Float(3.5));
+        assert_eq!(eval_value("1-5/2*2"), Value::Float(-4.0));
+        assert_eq!(eval_value("(6/2)"), Value::Float(3.0));
+        assert_eq!(eval_value("5+2"), Value::Int(7));
+        assert_eq!(eval_value("|1-7/2|"), Value::Float(2.5));
+        assert_eq!(eval_value("1/4/5"), Value::Float(0.05));
+
+        assert_eq!(eval_str_custom(&evaluator, "(6/2)").unwrap(), 3);
+        assert!(eval_str_custom(&evaluator, "5/2").is_err());
+        assert!(eval_str_custom(&evaluator, "1/0").is_err());
+        assert!(eval_str_custom(&evaluator, "powmod(2, 4/2, 5)").is_err());
+
+        assert_eq!(eval_str("5/2").unwrap(), 2);
+    }
+
+    #[test]
+    fn evaluates_float_builtins() {
+        let evaluator = evaluator_with(|config| config.float_mode = true);
+
+        let eval_float = |expr: &str| {
+            evaluator
+                .eval_value(String::from_str(expr).unwrap())
+                .unwrap()
+                .as_f64()
+        };
+
+        assert!((eval_float("log2(1024)") - 10.0).abs() < 1e-12);
+        assert!((eval_float("log10(1000)") - 3.0).abs() < 1e-12);
+        assert!((eval_float("ln(2.718281828459045)") - 1.0).abs() < 1e-12);
+        assert!((eval_float("2*log2(8)^2") - 18.0).abs() < 1e-12);
+        assert!((eval_float("1.5*4") - 6.0).abs() < 1e-12);
+
+        for expr in ["ln(0)", "log2(-8)", "log10(1-1)"] {
+            assert!(evaluator
+                .eval_value(String::from_str(expr).unwrap())
+                .unwrap_err()
+                .to_string()
+                .contains("expects a positive argument"));
+        }
+
+        assert!(eval_str_custom(&evaluator, "log2(1,2)").is_err());
+        assert!(eval_str("log2(1024)").is_err());
+        assert!(eval_str("1.5*4").is_err());
+    }
+
+    #[test]
+    fn rejects_misplaced_decimal_points() {
+        let evaluator = evaluator_with(|config| config.float_mode = true);
+
+        for expr in ["1.", ".5", "1.2.3", "2*.5", "1.+2", "1.5(2)"] {
+            assert!(eval_str_custom(&evaluator, expr).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn detects_truncated_division() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        let truncated = |expr: &str| {
+            evaluator
+                .eval_detailed(String::from_str(expr).unwrap())
+                .unwrap()
+                .truncated
+        };
+
+        assert!(truncated("7/2"));
+        assert!(truncated("1+(10/3)*3"));
+        assert!(truncated("-7/2"));
+        assert!(!truncated("6/2"));
+        assert!(!truncated("7*2"));
+        assert!(!truncated("if(1, 1, 7/2)"));
+
+        let evaluator = evaluator_with(|config| config.divide_promotes_to_float = true);
+
+        assert!(
+            !evaluator
+                .eval_detailed(String::from_str("7/2").unwrap())
+                .unwrap()
+                .truncated
+        );
+    }
+
+    #[test]
+    fn evaluates_exact_integers() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let test_data = vec![
+            ("6/3", 2),
+            ("(6/4)*2", 3),
+            ("6/4*2", 3),
+            ("1/3+1/3+1/3", 1),
+            ("7/2-1/2", 3),
+            ("(1/2)^2*4", 1),
+            ("abs(-3/2)*2", 3),
+            ("|1/2-1|*4", 2),
+        ];
+
+        for (expr, expected) in test_data {
+            assert_eq!(evaluator.eval_exact_int(expr).unwrap(), expected, "{expr}");
+        }
+    }
+
+    #[test]
+    fn checks_range_of_results() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(evaluator.eval_in_range("50+25", 0..=100).unwrap(), 75);
+        assert_eq!(evaluator.eval_in_range("10*10", 0..=100).unwrap(), 100);
+        assert_eq!(
+            evaluator
+                .eval_in_range("50*3", 0..=100)
+                .unwrap_err()
+                .to_string(),
+            "result 150 is outside of 0..=100"
+        );
+        assert_eq!(
+            evaluator
+                .eval_in_range("-1", 0..=100)
+                .unwrap_err()
+                .to_string(),
+            "result -1 is outside of 0..=100"
+        );
+        assert!(evaluator.eval_in_range("1/0", 0..=100).is_err());
+    }
+
+    #[test]
+    fn rejects_inexact_integers() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let test_data = vec![
+            ("3/2", "result is not an integer: 3/2"),
+            ("-3/2", "result is not an integer: -3/2"),
+            ("1/3+1/3", "result is not an integer: 2/3"),
+            ("1/0", "division by zero"),
+            ("powmod(1/2,2,5)", "powmod expects integer arguments"),
+        ];
+
+        for (expr, expected) in test_data {
+            assert_eq!(
+                evaluator.eval_exact_int(expr).unwrap_err().to_string(),
+                expected,
+                "{expr}"
+            );
+        }
+    }
+
+    #[test]
+    fn evaluates_decimal_expansions() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let test_data = vec![
+            ("10/3", 4, "3.3333"),
+            ("2/3", 4, "0.6666"),
+            ("1/4", 4, "0.2500"),
+            ("1/8", 3, "0.125"),
+            ("-10/4", 2, "-2.50"),
+            ("-1/3", 3, "-0.333"),
+            ("22/7", 0, "3"),
+            ("6/3", 2, "2.00"),
+            ("1/3+1/6", 5, "0.50000"),
+        ];
+
+        for (expr, places, expected) in test_data {
+            assert_eq!(
+                evaluator.eval_decimal(expr, places).unwrap(),
+                expected,
+                "{expr}"
+            );
+        }
+
+        assert!(evaluator.eval_decimal("1/0", 2).is_err());
+        assert!(evaluator_with(|config| config.float_mode = true)
+            .eval_decimal("1.5", 2)
+            .is_err());
+    }
+
+    #[test]
+    fn counts_operations() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let test_data = vec![
+            ("7", 0),
+            ("-7", 0),
+            ("2+3*4", 2),
+            ("1-2+3", 2),
+            ("-1+2", 1),
+            ("2*(3+4)-5", 3),
+            ("abs(-2)^2", 2),
+            ("if(1, 2+2, 3)", 1),
+        ];
+
+        for (expr, expected) in test_data {
+            let evaluation = evaluator.eval_detailed(expr.to_string()).unwrap();
+
+            assert_eq!(evaluation.operations, expected, "{expr}");
+        }
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let variables = Variables::from([
+            ("x".to_string(), Value::Int(-3)),
+            ("abs".to_string(), Value::Int(10)),
+        ]);
+
+        let eval = |expr: &str| {
+            evaluator
+                .eval_with_variables(expr, &variables)
+                .map(|evaluation| evaluation.value)
+        };
+
+        assert_eq!(eval("x").unwrap(), Value::Int(-3));
+        assert_eq!(eval("-x").unwrap(), Value::Int(3));
+        assert_eq!(eval("x^2").unwrap(), Value::Int(9));
+        assert_eq!(eval("abs(x)*abs").unwrap(), Value::Int(30));
+        assert_eq!(eval("if(x, x, 0)").unwrap(), Value::Int(-3));
+        assert_eq!(eval("y+1").unwrap_err().to_string(), "unknown variable 'y'");
+        assert!(eval("2x").is_err());
+    }
+
+    #[test]
+    fn reuses_scratch_buffers() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let mut scratch = EvalScratch::new();
+
+        for (expr, expected) in [
+            (LONG_EXPR, eval_str(LONG_EXPR).unwrap()),
+            (SHORT_EXPR, 11),
+            ("1 + 1", 2),
+            ("2^3>7", 1),
+            ("if(1, 2, 3)*-(-4)", 8),
+        ] {
+            for _ in 0..3 {
+                let evaluation = evaluator.eval_with_scratch(expr, &mut scratch).unwrap();
+
+                assert_eq!(evaluation.value, Value::Int(expected), "{expr}");
+            }
+        }
+
+        // The buffers are still as large as the longest expression needed them
+        let longest = solver::strip_enclosing_parentheses(LONG_EXPR).len();
+
+        assert!(scratch.rewrite.substituted.capacity() >= longest);
+        assert!(scratch.rewrite.rewritten.capacity() >= longest);
+
+        assert!(evaluator.eval_with_scratch("1/0", &mut scratch).is_err());
+        assert_eq!(
+            evaluator
+                .eval_with_scratch("2*3", &mut scratch)
+                .unwrap()
+                .value,
+            Value::Int(6)
+        );
+    }
+
+    // Deliberately narrow, so overflows are easy to reach
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Small(i16);
+
+    impl std::fmt::Display for Small {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl CalcNumber for Small {
+        fn parse(text: &str) -> anyhow::Result<Self> {
+            Ok(Small(text.parse()?))
+        }
+
+        fn checked_add(self, other: Self) -> Option<Self> {
+            self.0.checked_add(other.0).map(Small)
+        }
+
+        fn checked_sub(self, other: Self) -> Option<Self> {
+            self.0.checked_sub(other.0).map(Small)
+        }
+
+        fn checked_mul(self, other: Self) -> Option<Self> {
+            self.0.checked_mul(other.0).map(Small)
+        }
+
+        fn checked_div(self, other: Self) -> Option<Self> {
+            self.0.checked_div(other.0).map(Small)
+        }
+
+        fn checked_pow(self, exponent: u32) -> Option<Self> {
+            self.0.checked_pow(exponent).map(Small)
+        }
+    }
+
+    #[test]
+    fn evaluates_with_number_backends() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        for expr in [SHORT_EXPR, "8/2/2-(3-10)*-2", "-(-(4))*3+0", "1-2*-(-3)"] {
+            let expected = eval_str(expr).ok();
+
+            assert_eq!(evaluator.eval_as::<i64>(expr).ok(), expected, "{expr}");
+            assert_eq!(
+                evaluator
+                    .eval_as::<Small>(expr)
+                    .ok()
+                    .map(|value| value.0.into()),
+                expected,
+                "{expr}"
+            );
+        }
+
+        assert_eq!(evaluator.eval_as::<i64>("200*200").unwrap(), 40000);
+        assert!(evaluator.eval_as::<Small>("200*200").is_err());
+        assert!(evaluator.eval_as::<Small>("1/0").is_err());
+        assert_eq!(evaluator.eval_as::<i64>("2^3^2-(1+1)^2").unwrap(), 508);
+        assert!(evaluator.eval_as::<Small>("2^15").is_err());
+        assert!(evaluator.eval_as::<i64>("2^(0-1)").is_err());
+        assert!(evaluator.eval_as::<i64>("|-3|").is_err());
+        assert!(evaluator.eval_as::<i64>("(1,2)").is_err());
+        assert_eq!(evaluator.eval_as::<i64>("if(2^3>7,5,1/0)").unwrap(), 5);
+        assert_eq!(evaluator.eval_as::<Small>("-5<3<=3").unwrap().0, 1);
+
+        let strict = evaluator_with(|config| config.strict_division = true);
+
+        assert_eq!(strict.eval_as::<i64>("8/2").unwrap(), 4);
+        assert_eq!(
+            strict.eval_as::<i64>("7/2").unwrap_err().to_string(),
+            "non-exact integer division"
+        );
+
+        let wrap_8 = evaluator_with(|config| config.wrap_bits = Some(8));
+
+        assert_eq!(wrap_8.eval_as::<i64>("127+1").unwrap(), -128);
+        assert_eq!(wrap_8.eval_as::<Small>("-129*3").unwrap().0, 125);
+        assert_eq!(wrap_8.eval_as::<i64>("2^9+1").unwrap(), 1);
+
+        let max_terms = evaluator_with(|config| config.max_terms = Some(2));
+
+        assert_eq!(max_terms.eval_as::<i64>("1+2").unwrap(), 3);
+        assert_eq!(
+            max_terms.eval_as::<i64>("1+2+3").unwrap_err().to_string(),
+            "too many terms"
+        );
+    }
+
+    #[cfg(feature = "i128")]
+    #[test]
+    fn evaluates_with_i128_backend() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(
+            evaluator.eval_as::<i128>("9223372036854775807+1").unwrap(),
+            9223372036854775808
+        );
+        assert!(evaluator.eval_as::<i64>("9223372036854775807+1").is_err());
+        assert_eq!(evaluator.eval_as::<i128>(SHORT_EXPR).unwrap(), 11);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn evaluates_big_integers() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(
+            evaluator
+                .eval_bigint("9223372036854775807+1")
+                .unwrap()
+                .to_string(),
+            "9223372036854775808"
+        );
+        assert_eq!(
+            evaluator.eval_bigint("2^200").unwrap().to_string(),
+            "1606938044258990275541962092341162602522202993782792835301376"
+        );
+        assert_eq!(
+            evaluator
+                .eval_bigint("(2^100+1)/2^99*-1")
+                .unwrap()
+                .to_string(),
+            "-2"
+        );
+        assert_eq!(evaluator.eval_bigint(SHORT_EXPR).unwrap().to_string(), "11");
+        assert!(evaluator.eval_bigint("1/(2^70-2^70)").is_err());
+        assert_eq!(evaluator.eval_bigint("2^70>2^69").unwrap().to_string(), "1");
+        assert!(evaluator_with(|config| config.strict_division = true)
+            .eval_bigint("2^70/3")
+            .is_err());
+        assert_eq!(
+            evaluator_with(|config| config.wrap_bits = Some(8))
+                .eval_bigint("2^70-1")
+                .unwrap()
+                .to_string(),
+            "-1"
+        );
+    }
+
+    #[test]
+    fn parses_decimal_commas() {
+        let european = |config: &mut Config| {
+            config.float_mode = true;
+            config.decimal_separator = Some(',');
+        };
+        let evaluator = evaluator_with(european);
+
+        assert_eq!(evaluator.eval_to_string("3,14"), "3,14");
+        assert_eq!(
+            evaluator.eval_value("3,14+0,86".into()).unwrap(),
+            Value::Float(4.0)
+        );
+        assert_eq!(
+            evaluator.eval_value("1.000,50 * 2".into()).unwrap(),
+            Value::Float(2001.0)
+        );
+        assert_eq!(
+            evaluator.eval_value("pow(1,5; 2)".into()).unwrap(),
+            Value::Float(2.25)
+        );
+        assert_eq!(evaluator.eval_to_string("0,5+1"), "1,5");
+        assert_eq!(
+            evaluator.eval_value("12.345.678,5".into()).unwrap(),
+            Value::Float(12345678.5)
+        );
+        for expr in ["12.34", "1.00,5", "1.0000,5", "1234.567", "2*1.2345"] {
+            assert_eq!(
+                evaluator.eval_value(expr.into()).unwrap_err().to_string(),
+                "misplaced digit group separator",
+                "{expr}"
+            );
+        }
+        assert!(evaluator.eval_value("1,000.50".into()).is_err());
+        assert!(evaluator.eval_value("1..0".into()).is_err());
+        assert!(evaluator.eval_value(".5".into()).is_err());
+        assert!(evaluator.eval_value("pow(2,3)".into()).is_err());
+
+        let point = evaluator_with(|config| {
+            config.float_mode = true;
+            config.decimal_separator = Some('.');
+        });
+
+        assert_eq!(point.eval_to_string("3.14"), "3.14");
+
+        let grouped = evaluator_with(|config| {
+            european(config);
+            config.group_output_digits = true;
+        });
+
+        assert_eq!(grouped.eval_to_string("1234567,5"), "1.234.567,5");
+    }
+
+    #[test]
+    fn rejects_conflicting_separators() {
+        let build = |configure: fn(&mut Config)| {
+            let mut config = Config {
+                float_mode: true,
+                decimal_separator: Some(','),
+                ..Config::default()
+            };
+
+            configure(&mut config);
+            ExpressionEvaluator::with_config(config)
+        };
+
+        assert!(build(|_| {}).is_ok());
+        assert!(build(|config| config.float_mode = false).is_err());
+        assert!(build(|config| config.decimal_separator = Some(';')).is_err());
+        assert!(build(|config| config.group_output_digits = true).is_ok());
+        assert!(build(|config| {
+            config.group_output_digits = true;
+            config.digit_separator = Some(',');
+        })
+        .is_err());
+        assert!(build(|config| {
+            config.group_output_digits = true;
+            config.digit_separator = Some('.');
+        })
+        .is_ok());
+        assert!(ExpressionEvaluatorBuilder::new()
+            .decimal_separator(',')
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn compares_expressions() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert!(evaluator.equivalent("2+3*4", "14").unwrap());
+        assert!(evaluator.equivalent(SHORT_EXPR, "(1+10)*1").unwrap());
+        assert!(evaluator.equivalent("7/2", "3").unwrap());
+        assert!(!evaluator.equivalent("2+3*4", "20").unwrap());
+        assert!(!evaluator.equivalent("-1", "1").unwrap());
+        assert!(evaluator.equivalent("1/0", "1").is_err());
+        assert!(evaluator.equivalent("1", "(").is_err());
+
+        let float = evaluator_with(|config| config.float_mode = true);
+
+        assert!(float.equivalent("0.1+0.2", "0.3").unwrap());
+        assert!(float.equivalent("1.5*2", "3").unwrap());
+        assert!(float.equivalent("10^12+0.0001", "10^12").unwrap());
+        assert!(!float.equivalent("0.1+0.2", "0.31").unwrap());
+    }
+
+    #[test]
+    fn rejects_long_expressions() {
+        let evaluator = ExpressionEvaluator::builder()
+            .max_length(11)
+            .build()
+            .unwrap();
+
+        assert_eq!(eval_str_custom(&evaluator, SHORT_EXPR).unwrap(), 11);
+        assert_eq!(eval_str_custom(&evaluator, "1+1").unwrap(), 2);
+        assert_eq!(
+            eval_str_custom(&evaluator, "2+3*(1+4/2) ")
+                .unwrap_err()
+                .to_string(),
+            "expression too long"
+        );
+        assert!(eval_str_custom(&evaluator, LONG_EXPR).is_err());
+        assert!(eval_str(LONG_EXPR).is_ok());
+    }
+
+    #[test]
+    fn reads_magnitude_suffixes() {
+        let evaluator = ExpressionEvaluator::builder()
+            .magnitude_suffixes(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(eval_str_custom(&evaluator, "2k").unwrap(), 2_000);
+        assert_eq!(eval_str_custom(&evaluator, "3M").unwrap(), 3_000_000);
+        assert_eq!(eval_str_custom(&evaluator, "1G").unwrap(), 1_000_000_000);
+        assert_eq!(eval_str_custom(&evaluator, "4Ki").unwrap(), 4_096);
+        assert_eq!(eval_str_custom(&evaluator, "1Mi/1Ki").unwrap(), 1_024);
+        assert_eq!(eval_str_custom(&evaluator, "10G").unwrap(), 10_000_000_000);
+        assert_eq!(
+            eval_str_custom(&evaluator, "100G*100G")
+                .unwrap_err()
+                .to_string(),
+            "integer overflow"
+        );
+
+        assert!(eval_str("2k").is_err());
+
+        let compiled = evaluator.compile("2k+x").unwrap();
+
+        assert_eq!(
+            compiled
+                .eval_with_variables(&Variables::from([("x".to_string(), Value::Int(1))]))
+                .unwrap(),
+            2_001
+        );
+        assert_eq!(evaluator.tree("2k+1").unwrap(), "+\n|-- 2000\n`-- 1");
+        assert_eq!(evaluator.simplify("2k+x").unwrap(), "2000+x");
+    }
+
+    #[test]
+    fn rejects_inexact_divisions_in_strict_mode() {
+        let evaluator = ExpressionEvaluator::builder()
+            .strict_division(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(eval_str_custom(&evaluator, "6/2").unwrap(), 3);
+        assert_eq!(eval_str_custom(&evaluator, "-12/4/3").unwrap(), -1);
+        assert_eq!(
+            eval_str_custom(&evaluator, "7/2").unwrap_err().to_string(),
+            "non-exact integer division"
+        );
+        assert_eq!(
+            eval_str_custom(&evaluator, "7/0").unwrap_err().to_string(),
+            "division by zero"
+        );
+
+        assert_eq!(eval_str("6/2").unwrap(), 3);
+        assert_eq!(eval_str("7/2").unwrap(), 3);
+    }
+
+    #[test]
+    fn reads_radix_suffixes() {
+        let evaluator = ExpressionEvaluator::builder()
+            .radix_suffixes(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(eval_str_custom(&evaluator, "1010b").unwrap(), 10);
+        assert_eq!(eval_str_custom(&evaluator, "17o").unwrap(), 15);
+        assert_eq!(eval_str_custom(&evaluator, "0FFh").unwrap(), 255);
+        assert_eq!(eval_str_custom(&evaluator, "0x10+0b10+10b").unwrap(), 20);
+        assert_eq!(
+            eval_str_custom(&evaluator, "19o").unwrap_err().to_string(),
+            "invalid literal '19o'"
+        );
+        assert!(eval_str_custom(&evaluator, "FFh").is_err());
+
+        let compiled = evaluator.compile("0FFh").unwrap();
+
+        assert_eq!(
+            compiled.eval_with_variables(&Variables::new()).unwrap(),
+            255
+        );
+        assert_eq!(evaluator.tree("0FFh-1010b").unwrap(), "-\n|-- 255\n`-- 10");
+        assert_eq!(evaluator.simplify("x*10b").unwrap(), "x*2");
+
+        assert!(eval_str("1010b").is_err());
+        assert!(ExpressionEvaluator::builder()
+            .radix_suffixes(true)
+            .time_units(true)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn caches_results() {
+        let evaluator = ExpressionEvaluator::builder()
+            .cache_size(8)
+            .build()
+            .unwrap();
+        let cached = || evaluator.cache.as_ref().unwrap().lock().unwrap().len();
+
+        assert_eq!(eval_str_custom(&evaluator, "2+3*4").unwrap(), 14);
+        assert_eq!(eval_str_custom(&evaluator, " 2 + 3 * 4 ").unwrap(), 14);
+        assert_eq!(cached(), 1);
+
+        assert!(eval_str_custom(&evaluator, "1/0").is_err());
+        assert!(eval_str_custom(&evaluator, "rand(1,6)").is_ok());
+        assert_eq!(
+            evaluator
+                .eval_with_variables("x+1", &Variables::from([("x".to_string(), Value::Int(1))]))
+                .unwrap()
+                .value,
+            Value::Int(2)
+        );
+        assert_eq!(cached(), 1);
+
+        // The input is still checked as it was written, even if the result is known
+        let evaluator = ExpressionEvaluator::builder()
+            .cache_size(8)
+            .require_operator_spaces(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(eval_str_custom(&evaluator, "2 + 3").unwrap(), 5);
+        assert!(eval_str_custom(&evaluator, "2+3").is_err());
+    }
+
+    #[test]
+    fn requires_full_parentheses() {
+        let evaluator = ExpressionEvaluator::builder()
+            .require_full_parens(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(eval_str_custom(&evaluator, "(1+(2*3))").unwrap(), 7);
+        assert_eq!(eval_str_custom(&evaluator, "1+2").unwrap(), 3);
+        assert_eq!(
+            eval_str_custom(&evaluator, "-(2^2)+pow(2,(3-1))").unwrap(),
+            0
+        );
+        assert_eq!(eval_str_custom(&evaluator, "(1+1)<3").unwrap(), 1);
+        assert_eq!(
+            eval_str_custom(&evaluator, "1+2*3")
+                .unwrap_err()
+                .to_string(),
+            "operands of '+' have to be parenthesized"
+        );
+        assert!(eval_str_custom(&evaluator, "1-2-3").is_err());
+        assert!(eval_str_custom(&evaluator, "2^3^2").is_err());
+        assert!(eval_str_custom(&evaluator, "-2^2").is_err());
+        assert!(eval_str_custom(&evaluator, "1+1<3").is_err());
+
+        assert_eq!(eval_str("1+2*3").unwrap(), 7);
+    }
+
+    #[test]
+    fn requires_operator_spaces() {
+        let evaluator = ExpressionEvaluator::builder()
+            .require_operator_spaces(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(eval_str_custom(&evaluator, "2 + 3").unwrap(), 5);
+        assert_eq!(eval_str_custom(&evaluator, "-2 * (3 - -1)").unwrap(), -8);
+        assert_eq!(
+            eval_str_custom(&evaluator, "|-2| ^ 2 <= abs(-4)").unwrap(),
+            1
+        );
+        assert_eq!(
+            eval_str_custom(&evaluator, "2+3").unwrap_err().to_string(),
+            "operator '+' at column 2 needs spaces around it"
+        );
+        assert!(eval_str_custom(&evaluator, "2 +3").is_err());
+        assert!(eval_str_custom(&evaluator, "2 + 3*4").is_err());
+        assert!(eval_str_custom(&evaluator, "1 <=2").is_err());
+
+        assert_eq!(eval_str("2+3").unwrap(), 5);
+    }
+
+    #[test]
+    fn explains_division_by_zero() {
+        let test_data = vec![
+            ("6/(3-3)", "division by zero, the divisor (3-3) is 0"),
+            ("1/(2-2)", "division by zero, the divisor (2-2) is 0"),
+            ("10/(5*0)", "division by zero, the divisor (5*0) is 0"),
+            (
+                "1 + 8/(2*(1-1))",
+                "division by zero, the divisor (2*(1-1)) is 0",
+            ),
+            ("(1)/0", "division by zero"),
+            ("1/0+(1)", "division by zero"),
+        ];
+
+        for (expr, expected) in test_data {
+            assert_eq!(eval_str(expr).unwrap_err().to_string(), expected, "{expr}");
+        }
+    }
+
+    #[test]
+    fn evaluates_into_buffer() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let mut out = [0; 8];
+
+        let written = evaluator.eval_into("2+3*4", &mut out).unwrap();
+        assert_eq!(&out[..written], b"14");
+
+        let written = evaluator.eval_into("-1234567", &mut out).unwrap();
+        assert_eq!(&out[..written], b"-1234567");
+
+        assert_eq!(
+            evaluator
+                .eval_into("123456789", &mut out)
+                .unwrap_err()
+                .to_string(),
+            "output buffer too small"
+        );
+        assert!(evaluator.eval_into("1/0", &mut out).is_err());
+        assert!(evaluator.eval_into("1", &mut []).is_err());
+    }
+
+    #[test]
+    fn evaluates_durations() {
+        let evaluator = evaluator_with(|config| config.time_units = true);
+
+        assert_eq!(eval_str_custom(&evaluator, "2h+30m").unwrap(), 150);
+        assert_eq!(eval_str_custom(&evaluator, "90m + 2h").unwrap(), 210);
+        assert_eq!(eval_str_custom(&evaluator, "(1d - 4h) / 2").unwrap(), 600);
+        assert_eq!(eval_str_custom(&evaluator, "2+3*4").unwrap(), 14);
+        assert_eq!(
+            eval_str_custom(&evaluator, "2h + 15")
+                .unwrap_err()
+                .to_string(),
+            "number 15 without a time unit"
+        );
+        assert!(eval_str_custom(&evaluator, "2 parsecs").is_err());
+        assert!(eval_str("2h+30m").is_err());
+
+        let compiled = evaluator.compile("2h+30m").unwrap();
+
+        assert_eq!(
+            compiled.eval_with_variables(&Variables::new()).unwrap(),
+            150
+        );
+        assert!(evaluator.compile("2h+").is_err());
+    }
+
+    #[test]
+    fn validates_allowed_variables() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let allowed = HashSet::from(["price".to_string(), "quantity".to_string()]);
+        let validate = |expr| evaluator.validate_with_allowed_vars(expr, &allowed);
+
+        assert!(validate("price * quantity").is_ok());
+        assert!(validate("abs(price - 10) + pow(quantity, 2)").is_ok());
+        assert!(validate("1+2").is_ok());
+        assert!(validate("MAX - price").is_ok());
+        assert!(validate("min(MIN, quantity)").is_ok());
+        assert_eq!(
+            validate("price * tax + discount").unwrap_err().to_string(),
+            "variable 'tax' at column 9 is not allowed"
+        );
+        assert_eq!(
+            validate("$1 + price").unwrap_err().to_string(),
+            "variable '$1' at column 1 is not allowed"
+        );
+        assert!(validate("price +").is_err());
+        assert!(validate("(quantity").is_err());
+    }
+
+    #[test]
+    fn evaluates_comparison_chains() {
+        let test_data = vec![
+            ("1<2", 1),
+            ("2<1", 0),
+            ("1<2<3", 1),
+            ("3>2>1", 1),
+            ("1<2>3", 0),
+            ("3<2<1", 0),
+            ("1<=1<2", 1),
+            ("2>=3", 0),
+            ("1+1==2", 1),
+            ("2*3!=6", 0),
+            ("(1<2)+(2<1)*5", 1),
+            ("if(2^3>=8, 10, 20)", 10),
+            ("-1 < -(-1)", 1),
+            ("1/2 < 1", 1),
+        ];
+
+        for (expr, expected) in test_data {
+            assert_eq!(eval_str(expr).unwrap(), expected, "{expr}");
+        }
+
+        assert!(eval_str("1<").is_err());
+        assert!(eval_str("<1").is_err());
+        assert!(eval_str("1=2").is_err());
+        assert!(eval_str("1!2").is_err());
+        assert!(eval_str("1<<2").is_err());
+
+        let exact = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(exact.eval_exact_int("1/3 < 1/2").unwrap(), 1);
+        assert_eq!(exact.eval_exact_int("2/4 == 1/2").unwrap(), 1);
+    }
+
+    #[test]
+    fn evaluates_over_ranges() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+        let variables = Variables::from([("c".to_string(), Value::Int(10))]);
+        let over = |template, range| {
+            evaluator
+                .eval_over_range(template, "x", range, &variables)
+                .map(|values| {
+                    values
+                        .into_iter()
+                        .map(|value| value.to_i64().unwrap())
+                        .collect::<Vec<_>>()
+                })
+        };
+
+        assert_eq!(over("x^2", 1..=4).unwrap(), [1, 4, 9, 16]);
+        assert_eq!(over("c - x", -1..=1).unwrap(), [11, 10, 9]);
+        assert_eq!(over("x", RangeInclusive::new(3, 2)).unwrap(), []);
+        assert!(over("1/x", -1..=1).is_err());
+        assert!(over("y", 1..=2).is_err());
+        assert!(over("x", 0..=i64::MAX).is_err());
+    }
+
+    #[test]
+    fn evaluates_to_string() {
+        assert_eq!(
+            ExpressionEvaluator::new().unwrap().eval_to_string("2+3*4"),
+            "14"
+        );
+        assert_eq!(
+            ExpressionEvaluator::new().unwrap().eval_to_string("1/0"),
+            "Error: division by zero"
+        );
+
+        let evaluator = evaluator_with(|config| {
+            config.group_output_digits = true;
+            config.show_operation_count = true;
+        });
+
+        assert_eq!(evaluator.eval_to_string("1000*1000"), "1,000,000  (1 op)");
+        assert_eq!(
+            evaluator.eval_to_string("(1"),
+            "Error: unmatched parenthesis at position 1"
+        );
+    }
+
+    #[test]
+    fn formats_values() {
+        assert_eq!(Value::Int(-3).to_string(), "-3");
+        assert_eq!(Value::Float(2.5).to_string(), "2.5");
+        assert_eq!(Value::Float(3.0).to_string(), "3.0");
+        assert_eq!(Value::Float(1e21).to_string(), "1000000000000000000000.0");
+        assert_eq!(Value::Float(1e-7).to_string(), "0.0000001");
+    }
+
+    #[test]
+    fn builds_configured_evaluator() {
+        let evaluator = ExpressionEvaluator::builder()
+            .wrap_bits(8)
+            .max_terms(2)
+            .build()
+            .unwrap();
+
+        assert_eq!(eval_str_custom(&evaluator, "200+100").unwrap(), 44);
+        assert!(eval_str_custom(&evaluator, "1+2+3").is_err());
+
+        let evaluator = ExpressionEvaluator::builder()
+            .divide_promotes_to_float(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            evaluator
+                .eval_value(String::from_str("5/2").unwrap())
+                .unwrap(),
+            Value::Float(2.5)
+        );
+
+        assert!(ExpressionEvaluator::builder().wrap_bits(0).build().is_err());
+
+        let evaluator = ExpressionEvaluator::builder()
+            .scientific_digits(2)
+            .build()
+            .unwrap();
+
+        assert_eq!(evaluator.eval_to_string("3*10^8"), "3e8");
+        assert!(ExpressionEvaluator::builder()
+            .scientific_digits(0)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn performance_short() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        for _ in 0..1000 {
+            assert!(eval_str_custom(&evaluator, SHORT_EXPR).is_ok());
+        }
+    }
+
+    #[test]
+    fn evaluates_flat_expressions_like_nested_ones() {
+        let test_data = vec![
+            "2+3*4", "-7", "1-2-3", "10/3*3", "-2*-3", "8/2/2", "5--5", "2^3^2", "1-5/2*2",
+        ];
+
+        for expr in test_data {
+            assert_eq!(
+                eval_str(expr).unwrap(),
+                eval_str(&format!("0+({expr})")).unwrap(),
+                "{expr}"
+            );
+        }
+
+        assert!(eval_str("2+*3").is_err());
+        assert!(eval_str("2+").is_err());
     }
 
     #[test]
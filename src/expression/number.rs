@@ -0,0 +1,208 @@
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use anyhow::anyhow;
+
+use super::solver::{self, Operand, Solver, SolverResult};
+
+/// Integer type an expression can be evaluated with, see `ExpressionEvaluator::eval_as`
+///
+/// The checked operations return `None` on overflow and, for a division, on a zero divisor.
+pub trait CalcNumber: Clone + PartialEq + Display {
+    fn parse(text: &str) -> anyhow::Result<Self>;
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn checked_div(self, other: Self) -> Option<Self>;
+    fn checked_pow(self, exponent: u32) -> Option<Self>;
+}
+
+impl CalcNumber for i64 {
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        Ok(i64::from_str(text)?)
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        i64::checked_add(self, other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        i64::checked_sub(self, other)
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        i64::checked_mul(self, other)
+    }
+
+    fn checked_div(self, other: Self) -> Option<Self> {
+        i64::checked_div(self, other)
+    }
+
+    fn checked_pow(self, exponent: u32) -> Option<Self> {
+        i64::checked_pow(self, exponent)
+    }
+}
+
+#[cfg(feature = "i128")]
+impl CalcNumber for i128 {
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        Ok(i128::from_str(text)?)
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        i128::checked_add(self, other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        i128::checked_sub(self, other)
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        i128::checked_mul(self, other)
+    }
+
+    fn checked_div(self, other: Self) -> Option<Self> {
+        i128::checked_div(self, other)
+    }
+
+    fn checked_pow(self, exponent: u32) -> Option<Self> {
+        i128::checked_pow(self, exponent)
+    }
+}
+
+// The solver calculates with the number type as it does with values, only a few of the builtins are defined on integers of any type
+// An overflow of the number type itself is always an error, a configured width only wraps what the type can hold
+impl<T: CalcNumber> Operand for T {
+    fn from_text(text: &str) -> SolverResult<Self> {
+        T::parse(text)
+    }
+
+    fn from_i64(value: i64) -> SolverResult<Self> {
+        T::parse(&value.to_string())
+    }
+
+    fn to_expression_text(&self) -> String {
+        self.to_string()
+    }
+
+    fn is_zero(&self) -> bool {
+        T::parse("0").is_ok_and(|zero| *self == zero)
+    }
+
+    // The number type has no conversions, but its text is an integer like any other
+    fn magnitude(&self) -> i64 {
+        self.to_string()
+            .trim_start_matches('-')
+            .parse()
+            .unwrap_or(i64::MAX)
+    }
+
+    // Opposite signs decide by themselves, and the difference of two numbers of the same sign can't overflow
+    fn ordering(&self, other: &Self) -> Option<Ordering> {
+        match (is_negative(self), is_negative(other)) {
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            _ => {
+                let difference = self.clone().checked_sub(other.clone())?;
+
+                Some(match difference {
+                    _ if difference.is_zero() => Ordering::Equal,
+                    _ if is_negative(&difference) => Ordering::Less,
+                    _ => Ordering::Greater,
+                })
+            }
+        }
+    }
+
+    fn add(solver: &Solver<'_, Self>, first: Self, second: Self) -> SolverResult<Self> {
+        wrap(solver, checked(first.checked_add(second))?)
+    }
+
+    fn multiply(
+        solver: &Solver<'_, Self>,
+        operator: &str,
+        first: Self,
+        second: Self,
+    ) -> SolverResult<Self> {
+        let result = match operator {
+            "*" => checked(first.checked_mul(second))?,
+            "/" => {
+                let quotient = checked(first.clone().checked_div(second.clone()))?;
+                let remainder =
+                    checked(first.checked_sub(checked(quotient.clone().checked_mul(second))?))?;
+
+                if !remainder.is_zero() {
+                    solver.discard_remainder()?;
+                }
+
+                quotient
+            }
+            _ => return Err(anyhow!("invalid operator for multiplication")),
+        };
+
+        wrap(solver, result)
+    }
+
+    fn call_builtin(
+        solver: &Solver<'_, Self>,
+        name: &str,
+        arguments: &[Self],
+    ) -> Option<SolverResult<Self>> {
+        match (name, arguments) {
+            ("pow", [base, exponent]) => Some(pow(solver, base.clone(), exponent)),
+            _ if name != "pow" && solver::is_builtin(name) => Some(Err(anyhow!(
+                "function '{name}' is not supported by this number type"
+            ))),
+            _ => None,
+        }
+    }
+}
+
+fn pow<T: CalcNumber>(solver: &Solver<'_, T>, base: T, exponent: &T) -> SolverResult<T> {
+    let exponent = exponent.to_string();
+
+    if exponent.starts_with('-') {
+        return Err(anyhow!("negative exponent"));
+    }
+
+    let exponent = exponent
+        .parse()
+        .map_err(|_| anyhow!("exponent too large"))?;
+
+    wrap(solver, checked(base.checked_pow(exponent))?)
+}
+
+// Two's complement wrapping like that of values, with nothing but the arithmetic of the number type
+// The value is split into a multiple of half the range and a remainder, and an odd multiple moves the remainder by half the range, so that even i64 can wrap to 64 bits
+// A type which can't hold half the range can't hold a value outside of it either
+fn wrap<T: CalcNumber>(solver: &Solver<'_, T>, value: T) -> SolverResult<T> {
+    let two = T::parse("2")?;
+
+    let Some(half) = solver
+        .config()
+        .wrap_bits
+        .and_then(|bits| two.clone().checked_pow(bits - 1))
+    else {
+        return Ok(value);
+    };
+
+    let multiple = checked(value.clone().checked_div(half.clone()))?;
+    let remainder =
+        checked(value.checked_sub(checked(multiple.clone().checked_mul(half.clone()))?))?;
+    let halved = checked(multiple.clone().checked_div(two.clone()))?;
+
+    if checked(multiple.checked_sub(checked(halved.checked_mul(two))?))?.is_zero() {
+        Ok(remainder)
+    } else if is_negative(&remainder) {
+        checked(remainder.checked_add(half))
+    } else {
+        checked(remainder.checked_sub(half))
+    }
+}
+
+fn checked<T>(value: Option<T>) -> SolverResult<T> {
+    value.ok_or_else(|| anyhow!("integer overflow"))
+}
+
+fn is_negative<T: CalcNumber>(value: &T) -> bool {
+    value.to_string().starts_with('-')
+}
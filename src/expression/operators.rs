@@ -0,0 +1,195 @@
+// Precedence and associativity of the operators, the solver and the simplifier both take them from here
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+/// Number of operands an operator can have
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arity {
+    /// Only between two operands, like `*`
+    Binary,
+    /// Also before a single operand, like the minus of `-2`
+    UnaryOrBinary,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OperatorInfo {
+    pub symbol: &'static str,
+    /// Operators with a higher precedence bind tighter
+    pub precedence: u8,
+    pub associativity: Associativity,
+    pub arity: Arity,
+    /// Precedence when the operator is written before a single operand, like the minus in `-2`
+    pub unary_precedence: Option<u8>,
+}
+
+// A unary minus binds looser than an exponentiation, so `-2^2` is `-4`
+// Comparisons bind loosest, and a chain of them like `1<2<3` holds if every adjacent pair does
+/// Every operator, each symbol once
+pub const OPERATORS: &[OperatorInfo] = &[
+    OperatorInfo {
+        symbol: "<",
+        precedence: 1,
+        associativity: Associativity::Left,
+        arity: Arity::Binary,
+        unary_precedence: None,
+    },
+    OperatorInfo {
+        symbol: "<=",
+        precedence: 1,
+        associativity: Associativity::Left,
+        arity: Arity::Binary,
+        unary_precedence: None,
+    },
+    OperatorInfo {
+        symbol: ">",
+        precedence: 1,
+        associativity: Associativity::Left,
+        arity: Arity::Binary,
+        unary_precedence: None,
+    },
+    OperatorInfo {
+        symbol: ">=",
+        precedence: 1,
+        associativity: Associativity::Left,
+        arity: Arity::Binary,
+        unary_precedence: None,
+    },
+    OperatorInfo {
+        symbol: "==",
+        precedence: 1,
+        associativity: Associativity::Left,
+        arity: Arity::Binary,
+        unary_precedence: None,
+    },
+    OperatorInfo {
+        symbol: "!=",
+        precedence: 1,
+        associativity: Associativity::Left,
+        arity: Arity::Binary,
+        unary_precedence: None,
+    },
+    OperatorInfo {
+        symbol: "+",
+        precedence: 2,
+        associativity: Associativity::Left,
+        arity: Arity::Binary,
+        unary_precedence: None,
+    },
+    OperatorInfo {
+        symbol: "-",
+        precedence: 2,
+        associativity: Associativity::Left,
+        arity: Arity::UnaryOrBinary,
+        unary_precedence: Some(4),
+    },
+    OperatorInfo {
+        symbol: "*",
+        precedence: 3,
+        associativity: Associativity::Left,
+        arity: Arity::Binary,
+        unary_precedence: None,
+    },
+    OperatorInfo {
+        symbol: "/",
+        precedence: 3,
+        associativity: Associativity::Left,
+        arity: Arity::Binary,
+        unary_precedence: None,
+    },
+    OperatorInfo {
+        symbol: "^",
+        precedence: 5,
+        associativity: Associativity::Right,
+        arity: Arity::Binary,
+        unary_precedence: None,
+    },
+];
+
+pub fn find(symbol: &str) -> Option<&'static OperatorInfo> {
+    OPERATORS.iter().find(|operator| operator.symbol == symbol)
+}
+
+/// Precedence of the binary operator, or 0 if the symbol isn't an operator
+pub fn precedence(symbol: &str) -> u8 {
+    find(symbol).map_or(0, |operator| operator.precedence)
+}
+
+/// Precedence of the operator before a single operand, or 0 if it can't be written there
+pub fn unary_precedence(symbol: &str) -> u8 {
+    find(symbol)
+        .and_then(|operator| operator.unary_precedence)
+        .unwrap_or(0)
+}
+
+pub fn associativity(symbol: &str) -> Option<Associativity> {
+    find(symbol).map(|operator| operator.associativity)
+}
+
+pub fn arity(symbol: &str) -> Option<Arity> {
+    find(symbol).map(|operator| operator.arity)
+}
+
+/// Symbols of the operators which bind as tightly as this one, itself included, like `*` and `/`
+pub fn peers(symbol: &str) -> impl Iterator<Item = &'static str> {
+    let precedence = precedence(symbol);
+
+    OPERATORS
+        .iter()
+        .filter(move |operator| operator.precedence == precedence)
+        .map(|operator| operator.symbol)
+}
+
+// Whether an operator starts with this character, `!` and `=` only start comparisons like `!=`
+pub fn is_operator(c: char) -> bool {
+    OPERATORS
+        .iter()
+        .any(|operator| operator.symbol.starts_with(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_operators() {
+        assert!(precedence("*") > precedence("+"));
+        assert_eq!(precedence("*"), precedence("/"));
+        assert_eq!(precedence("+"), precedence("-"));
+        assert!(precedence("^") > precedence("*"));
+        assert_eq!(precedence("%"), 0);
+
+        assert_eq!(associativity("^"), Some(Associativity::Right));
+        assert_eq!(associativity("-"), Some(Associativity::Left));
+        assert!(unary_precedence("-") > precedence("*"));
+        assert!(unary_precedence("-") < precedence("^"));
+        assert_eq!(unary_precedence("+"), 0);
+        assert_eq!(arity("-"), Some(Arity::UnaryOrBinary));
+        assert_eq!(arity("*"), Some(Arity::Binary));
+        assert_eq!(arity("%"), None);
+        assert!(precedence("<") < precedence("+"));
+        assert_eq!(precedence("=="), precedence(">="));
+        assert_eq!(peers("/").collect::<Vec<_>>(), ["*", "/"]);
+        assert!(is_operator('^'));
+        assert!(is_operator('!'));
+        assert!(!is_operator('('));
+
+        for operator in OPERATORS {
+            let count = OPERATORS
+                .iter()
+                .filter(|other| other.symbol == operator.symbol)
+                .count();
+
+            assert_eq!(count, 1, "{}", operator.symbol);
+            assert_eq!(
+                operator.arity == Arity::UnaryOrBinary,
+                operator.unary_precedence.is_some(),
+                "{}",
+                operator.symbol
+            );
+        }
+    }
+}
@@ -0,0 +1,72 @@
+// Regular expressions the evaluator checks and rewrites the input with
+// They work on the input after whitespace is removed and alternative characters like `×` are replaced
+
+use super::operators;
+
+// Number as the solver writes it, a colon separates the numerator and denominator of an exact fraction
+const NUMBER_PATTERN: &str = r"[0-9]+(?:[\.:][0-9]+)?";
+
+/// Every character an expression can consist of
+pub const EXPRESSION_PATTERN: &str = r"^[0-9A-Za-z_\$,\.\|\+\-\*\/\^\(\)<>=!]+$";
+/// Innermost pair of parentheses, with the name of the function if it is a call
+pub const PARENTHESIZED_SUBEXPRESSION_PATTERN: &str =
+    r"([A-Za-z_][0-9A-Za-z_]*|)[\(]([^\(\)]+)[\)]";
+/// Expression without parentheses, names or functions left
+pub const SUBEXPRESSION_PATTERN: &str = r"^[0-9\.:\+\-\*\/<>=!]+$";
+/// Start of a conditional
+pub const CONDITIONAL_PATTERN: &str = r"\bif\(";
+/// Variable or function name, or a result reference like `$2`, with the parenthesis after it if it is a call
+pub const IDENTIFIER_PATTERN: &str = r"(\$[0-9]+|[A-Za-z_][0-9A-Za-z_]*)(\(?)";
+/// Opening parenthesis right after a number or a closing parenthesis, like `2(3)`
+pub const INVALID_PARENTHESES_PATTERN: &str = r"(?:^|[^0-9A-Za-z_])[0-9\.]+\(|\)\(";
+/// Decimal point without digits on both sides, or a number with two of them
+pub const INVALID_DECIMAL_POINT_PATTERN: &str = r"(?:^|[^0-9])\.|\.(?:$|[^0-9])|\.[0-9]+\.";
+/// Name right after a number or a closing parenthesis, like `2x`
+pub const INVALID_FUNCTION_CALL_PATTERN: &str = r"[0-9\)][A-Za-z_\$]";
+/// Number right after a closing parenthesis, like `(2)3`
+pub const INVALID_CLOSING_PARENTHESIS_PATTERN: &str = r"\)[0-9]";
+
+/// An operation of two numbers which binds like a multiplication, either one may be negative
+///
+/// The hyphen before the first one is a subtraction instead when a number comes right before it, the solver tells the two apart.
+pub fn multiplication_pattern() -> String {
+    let operators: String = operators::peers("*").map(regex::escape).collect();
+
+    format!(r"(-?{NUMBER_PATTERN})([{operators}])(-?{NUMBER_PATTERN})")
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    #[test]
+    fn compiles_patterns() {
+        let expression_re = Regex::new(EXPRESSION_PATTERN).unwrap();
+
+        assert!(expression_re.is_match("2+3*(1+4/2)"));
+        assert!(expression_re.is_match("abs(x_1)^2<=$1"));
+        assert!(!expression_re.is_match("2 + 3"));
+        assert!(!expression_re.is_match("2%3"));
+
+        assert_eq!(
+            multiplication_pattern(),
+            r"(-?[0-9]+(?:[\.:][0-9]+)?)([\*/])(-?[0-9]+(?:[\.:][0-9]+)?)"
+        );
+
+        for pattern in [
+            &multiplication_pattern(),
+            PARENTHESIZED_SUBEXPRESSION_PATTERN,
+            SUBEXPRESSION_PATTERN,
+            CONDITIONAL_PATTERN,
+            IDENTIFIER_PATTERN,
+            INVALID_PARENTHESES_PATTERN,
+            INVALID_DECIMAL_POINT_PATTERN,
+            INVALID_FUNCTION_CALL_PATTERN,
+            INVALID_CLOSING_PARENTHESIS_PATTERN,
+        ] {
+            assert!(Regex::new(pattern).is_ok(), "{pattern}");
+        }
+    }
+}
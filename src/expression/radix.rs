@@ -0,0 +1,204 @@
+use anyhow::anyhow;
+use regex::Regex;
+
+// Every number, with the character before it, so digits of a name like `log2` are not taken for one
+pub const LITERAL_PATTERN: &str =
+    r"(^|[^0-9A-Za-z_\$\.])(0[xX][0-9A-Za-z_]*|0[bB][0-9A-Za-z_]*|[0-9][0-9\.]*)";
+
+// Literal with its base after the digits, like `1010b` or `0FFh`, which starts with a decimal digit, so it can't be taken for a name
+pub const SUFFIXED_LITERAL_PATTERN: &str = r"(^|[^0-9A-Za-z_\$\.])([0-9][0-9A-Za-z_]*)([hHbBoO])\b";
+
+/// Base of the literals of an expression other than ten, see `Evaluation::literal_base`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Radix {
+    Hexadecimal,
+    Binary,
+}
+
+impl Radix {
+    fn of(literal: &str) -> Option<Radix> {
+        match literal.get(..2) {
+            Some("0x" | "0X") => Some(Radix::Hexadecimal),
+            Some("0b" | "0B") => Some(Radix::Binary),
+            _ => None,
+        }
+    }
+
+    fn base(self) -> u32 {
+        match self {
+            Radix::Hexadecimal => 16,
+            Radix::Binary => 2,
+        }
+    }
+
+    // Same prefix as the literals, the sign is written in front of it, so the digits are always the magnitude
+    pub fn format(self, value: i64) -> String {
+        let sign = if value < 0 { "-" } else { "" };
+        let magnitude = value.unsigned_abs();
+
+        match self {
+            Radix::Hexadecimal => format!("{sign}{magnitude:#x}"),
+            Radix::Binary => format!("{sign}{magnitude:#b}"),
+        }
+    }
+}
+
+// Turns the hexadecimal and binary literals into decimal ones, like `0xff` into `255`
+pub fn replace_literals(expr: &str, literal_re: &Regex) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(expr.len());
+    let mut last_end = 0;
+
+    for captures in literal_re.captures_iter(expr) {
+        let Some(literal) = captures.get(2) else {
+            continue;
+        };
+
+        let Some(radix) = Radix::of(literal.as_str()) else {
+            continue;
+        };
+
+        let digits = &literal.as_str()[2..];
+        let value = u64::from_str_radix(digits, radix.base())
+            .ok()
+            .filter(|_| !digits.starts_with('+'))
+            .ok_or_else(|| anyhow!("invalid literal '{}'", literal.as_str()))?;
+        let value = i64::try_from(value)
+            .map_err(|_| anyhow!("literal '{}' is too large", literal.as_str()))?;
+
+        result.push_str(&expr[last_end..literal.start()]);
+        result.push_str(&value.to_string());
+        last_end = literal.end();
+    }
+
+    result.push_str(&expr[last_end..]);
+
+    Ok(result)
+}
+
+// Turns the literals with a base suffix into decimal ones, like `17o` into `15`
+pub fn replace_suffixed_literals(
+    expr: &str,
+    suffixed_literal_re: &Regex,
+) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(expr.len());
+    let mut last_end = 0;
+
+    for captures in suffixed_literal_re.captures_iter(expr) {
+        let (Some(digits), Some(suffix)) = (captures.get(2), captures.get(3)) else {
+            continue;
+        };
+
+        // The x of a hexadecimal prefix is not a digit of any base, so a literal like `0x1b` is left for replace_literals
+        if Radix::of(digits.as_str()) == Some(Radix::Hexadecimal) {
+            continue;
+        }
+
+        let base = match suffix.as_str() {
+            "h" | "H" => 16,
+            "o" | "O" => 8,
+            _ => 2,
+        };
+        let literal = &expr[digits.start()..suffix.end()];
+        let value = u64::from_str_radix(digits.as_str(), base)
+            .map_err(|_| anyhow!("invalid literal '{literal}'"))?;
+        let value =
+            i64::try_from(value).map_err(|_| anyhow!("literal '{literal}' is too large"))?;
+
+        result.push_str(&expr[last_end..digits.start()]);
+        result.push_str(&value.to_string());
+        last_end = suffix.end();
+    }
+
+    result.push_str(&expr[last_end..]);
+
+    Ok(result)
+}
+
+// The base shared by every literal, if all of them are written in the same base other than ten
+pub fn literal_base(expr: &str, literal_re: &Regex) -> Option<Radix> {
+    let mut bases = literal_re
+        .captures_iter(expr)
+        .filter_map(|captures| captures.get(2))
+        .map(|literal| Radix::of(literal.as_str()));
+
+    let first = bases.next()??;
+
+    bases.all(|base| base == Some(first)).then_some(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_literals() {
+        let literal_re = Regex::new(LITERAL_PATTERN).unwrap();
+        let replace = |expr| replace_literals(expr, &literal_re);
+
+        assert_eq!(replace("0xF0+0x0f").unwrap(), "240+15");
+        assert_eq!(replace("0b101*-0B1").unwrap(), "5*-1");
+        assert_eq!(replace("log2(8)+$1").unwrap(), "log2(8)+$1");
+        assert_eq!(replace("0x7fffffffffffffff").unwrap(), i64::MAX.to_string());
+
+        assert_eq!(
+            replace("0x").unwrap_err().to_string(),
+            "invalid literal '0x'"
+        );
+        assert_eq!(
+            replace("0b102").unwrap_err().to_string(),
+            "invalid literal '0b102'"
+        );
+        assert_eq!(
+            replace("0x8000000000000000").unwrap_err().to_string(),
+            "literal '0x8000000000000000' is too large"
+        );
+    }
+
+    #[test]
+    fn replaces_suffixed_literals() {
+        let suffixed_literal_re = Regex::new(SUFFIXED_LITERAL_PATTERN).unwrap();
+        let replace = |expr| replace_suffixed_literals(expr, &suffixed_literal_re);
+
+        assert_eq!(replace("1010b+17o*0FFh").unwrap(), "10+15*255");
+        assert_eq!(replace("-0BH+(10O)").unwrap(), "-11+(8)");
+        assert_eq!(
+            replace("12+0x1b+x1b+log2(8)").unwrap(),
+            "12+0x1b+x1b+log2(8)"
+        );
+        assert_eq!(replace("7FFFFFFFFFFFFFFFh").unwrap(), i64::MAX.to_string());
+
+        assert_eq!(
+            replace("102b").unwrap_err().to_string(),
+            "invalid literal '102b'"
+        );
+        assert_eq!(
+            replace("18o").unwrap_err().to_string(),
+            "invalid literal '18o'"
+        );
+        assert_eq!(
+            replace("1G0h").unwrap_err().to_string(),
+            "invalid literal '1G0h'"
+        );
+        assert_eq!(
+            replace("8000000000000000h").unwrap_err().to_string(),
+            "literal '8000000000000000h' is too large"
+        );
+    }
+
+    #[test]
+    fn finds_shared_base() {
+        let literal_re = Regex::new(LITERAL_PATTERN).unwrap();
+        let base = |expr| literal_base(expr, &literal_re);
+
+        assert_eq!(base("0xF0+0x0F"), Some(Radix::Hexadecimal));
+        assert_eq!(base("-0b1*(0b10)"), Some(Radix::Binary));
+        assert_eq!(base("0xF0+0b1"), None);
+        assert_eq!(base("0xF0+1"), None);
+        assert_eq!(base("1+2"), None);
+        assert_eq!(base("x"), None);
+
+        assert_eq!(Radix::Hexadecimal.format(255), "0xff");
+        assert_eq!(Radix::Binary.format(-6), "-0b110");
+        assert_eq!(Radix::Hexadecimal.format(i64::MIN), "-0x8000000000000000");
+    }
+}
@@ -0,0 +1,53 @@
+// Values of the numerals, with the subtractive pairs like `IV`, largest first
+const NUMERALS: &[(u16, &str)] = &[
+    (1000, "M"),
+    (900, "CM"),
+    (500, "D"),
+    (400, "CD"),
+    (100, "C"),
+    (90, "XC"),
+    (50, "L"),
+    (40, "XL"),
+    (10, "X"),
+    (9, "IX"),
+    (5, "V"),
+    (4, "IV"),
+    (1, "I"),
+];
+
+// Largest value which can be written without a numeral above M
+pub const MAX_ROMAN: i64 = 3999;
+
+// Roman numeral of values from 1 to 3999, there is no numeral for zero or negative values
+pub fn to_roman(value: i64) -> Option<String> {
+    let mut rest = u16::try_from(value)
+        .ok()
+        .filter(|value| (1..=MAX_ROMAN as u16).contains(value))?;
+    let mut numeral = String::new();
+
+    for (value, letters) in NUMERALS {
+        while rest >= *value {
+            numeral.push_str(letters);
+            rest -= value;
+        }
+    }
+
+    Some(numeral)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_to_roman_numerals() {
+        assert_eq!(to_roman(4).as_deref(), Some("IV"));
+        assert_eq!(to_roman(14).as_deref(), Some("XIV"));
+        assert_eq!(to_roman(1994).as_deref(), Some("MCMXCIV"));
+        assert_eq!(to_roman(3999).as_deref(), Some("MMMCMXCIX"));
+        assert_eq!(to_roman(1).as_deref(), Some("I"));
+        assert_eq!(to_roman(0), None);
+        assert_eq!(to_roman(-5), None);
+        assert_eq!(to_roman(4000), None);
+    }
+}
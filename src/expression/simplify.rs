@@ -0,0 +1,373 @@
+use std::iter::Peekable;
+
+use anyhow::anyhow;
+
+use super::{
+    operators::{self, Associativity},
+    tokenizer::{tokenize, Token, TokenKind},
+};
+
+// Precedence of the operands which never need parentheses, like numbers and function calls
+const OPERAND_PRECEDENCE: u8 = u8::MAX;
+
+// Precedence of a negation, which is also how a negative number binds
+fn negation_precedence() -> u8 {
+    operators::unary_precedence("-")
+}
+
+fn comparison_precedence() -> u8 {
+    operators::precedence("<")
+}
+
+// Builtin whose calls are never folded, as they have a different value every time
+const RANDOM_FUNCTION: &str = "rand";
+
+enum Node {
+    Number(String),
+    Name(String),
+    Negation(Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+    // A chain like `1<x<3` holds if every adjacent pair does, so it can't be split into binary operations
+    Comparisons(Vec<Node>, Vec<&'static str>),
+    Call(String, Vec<Node>),
+}
+
+impl Node {
+    fn is_number(&self, value: f64) -> bool {
+        matches!(self, Node::Number(text) if text.parse::<f64>().is_ok_and(|number| number == value))
+    }
+
+    fn is_constant(&self) -> bool {
+        matches!(self, Node::Number(_))
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Node::Number(text) if text.starts_with('-') => negation_precedence(),
+            Node::Number(_) | Node::Name(_) | Node::Call(..) => OPERAND_PRECEDENCE,
+            Node::Negation(_) => negation_precedence(),
+            Node::Binary(operator, ..) => operators::precedence(operator),
+            Node::Comparisons(..) => comparison_precedence(),
+        }
+    }
+
+    // Parentheses are only written where the precedence and associativity of the operators need them
+    fn print(&self) -> String {
+        match self {
+            Node::Number(text) | Node::Name(text) => text.clone(),
+            Node::Negation(operand) => {
+                format!(
+                    "-{}",
+                    operand.print_grouped(operand.precedence() <= negation_precedence())
+                )
+            }
+            Node::Binary(operator, left, right) => {
+                let precedence = operators::precedence(operator);
+                let associativity = operators::associativity(operator);
+
+                format!(
+                    "{}{operator}{}",
+                    left.print_grouped(
+                        left.precedence() < precedence
+                            || left.precedence() == precedence
+                                && associativity == Some(Associativity::Right)
+                    ),
+                    // Negative operands on the right are grouped too, so no two operators are ever adjacent
+                    right.print_grouped(
+                        right.precedence() < precedence
+                            || right.precedence() == negation_precedence()
+                            || right.precedence() == precedence
+                                && associativity == Some(Associativity::Left)
+                    )
+                )
+            }
+            Node::Comparisons(operands, comparisons) => {
+                let mut text =
+                    operands[0].print_grouped(operands[0].precedence() <= comparison_precedence());
+
+                for (comparison, operand) in comparisons.iter().zip(&operands[1..]) {
+                    text.push_str(comparison);
+                    text.push_str(
+                        &operand.print_grouped(operand.precedence() <= comparison_precedence()),
+                    );
+                }
+
+                text
+            }
+            Node::Call(name, arguments) => format!(
+                "{name}({})",
+                arguments
+                    .iter()
+                    .map(Node::print)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+
+    fn print_grouped(&self, grouped: bool) -> String {
+        if grouped {
+            format!("({})", self.print())
+        } else {
+            self.print()
+        }
+    }
+
+    // Operator of an operation, a chain of comparisons has all of its operators
+    fn label(&self) -> String {
+        match self {
+            Node::Number(text) | Node::Name(text) => text.clone(),
+            Node::Negation(_) => "-".to_string(),
+            Node::Binary(operator, ..) => operator.to_string(),
+            Node::Comparisons(_, comparisons) => comparisons.join(" "),
+            Node::Call(name, _) => format!("{name}()"),
+        }
+    }
+
+    fn children(&self) -> Vec<&Node> {
+        match self {
+            Node::Number(_) | Node::Name(_) => Vec::new(),
+            Node::Negation(operand) => vec![operand],
+            Node::Binary(_, left, right) => vec![left, right],
+            Node::Comparisons(operands, _) | Node::Call(_, operands) => operands.iter().collect(),
+        }
+    }
+
+    // The prefix continues the lines of the ancestors which still have children below
+    fn render_children(&self, prefix: &str, lines: &mut Vec<String>) {
+        let children = self.children();
+
+        for (index, child) in children.iter().enumerate() {
+            let last = index + 1 == children.len();
+            let (branch, continuation) = if last {
+                ("`-- ", "    ")
+            } else {
+                ("|-- ", "|   ")
+            };
+
+            lines.push(format!("{prefix}{branch}{}", child.label()));
+            child.render_children(&format!("{prefix}{continuation}"), lines);
+        }
+    }
+}
+
+struct Simplifier<'a, I: Iterator<Item = Token<'a>>, F> {
+    tokens: Peekable<I>,
+    // Value of a constant subexpression, or `None` if it is left as it is, like one dividing by zero
+    // Nothing is folded without it, so the tree is the expression as it was written
+    evaluate: Option<F>,
+    // Reject an operand which is an operation without parentheses around it, like `2*3` in `1+2*3`
+    full_parens: bool,
+}
+
+impl<'a, I, F> Simplifier<'a, I, F>
+where
+    I: Iterator<Item = Token<'a>>,
+    F: Fn(&str) -> Option<String>,
+{
+    fn expression(&mut self) -> anyhow::Result<Node> {
+        let (first, mut grouped) = self.binary(comparison_precedence() + 1)?;
+        let mut operands = vec![first];
+        let mut comparisons = Vec::new();
+
+        while let Some(comparison) =
+            self.operator(|precedence| precedence == comparison_precedence())
+        {
+            let (operand, operand_grouped) = self.binary(comparison_precedence() + 1)?;
+
+            if grouped || operand_grouped {
+                self.check_parenthesized(comparison)?;
+            }
+
+            comparisons.push(comparison);
+            operands.push(operand);
+            grouped = false;
+        }
+
+        if comparisons.is_empty() {
+            return Ok(operands.remove(0));
+        }
+
+        Ok(self.fold(Node::Comparisons(operands, comparisons)))
+    }
+
+    // Also tells whether operations were grouped by their precedence, which only parentheses written around them would have made explicit
+    fn binary(&mut self, min_precedence: u8) -> anyhow::Result<(Node, bool)> {
+        let mut left = self.unary()?;
+        let mut grouped = false;
+
+        while let Some(operator) = self.operator(|precedence| {
+            precedence >= min_precedence && precedence > comparison_precedence()
+        }) {
+            let precedence = operators::precedence(operator);
+            let (right, right_grouped) = match operators::associativity(operator) {
+                Some(Associativity::Right) => self.binary(precedence)?,
+                _ => self.binary(precedence + 1)?,
+            };
+
+            if grouped || right_grouped {
+                self.check_parenthesized(operator)?;
+            }
+
+            left = self.fold(Node::Binary(operator, Box::new(left), Box::new(right)));
+            grouped = true;
+        }
+
+        Ok((left, grouped))
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Node> {
+        if self
+            .tokens
+            .next_if(|token| token.kind == TokenKind::Operator && token.text == "-")
+            .is_some()
+        {
+            let (operand, grouped) = self.binary(negation_precedence())?;
+
+            if grouped {
+                self.check_parenthesized("-")?;
+            }
+
+            return Ok(self.fold(Node::Negation(Box::new(operand))));
+        }
+
+        let token = self
+            .tokens
+            .next()
+            .ok_or_else(|| anyhow!("missing operand"))?;
+
+        match token.kind {
+            TokenKind::Number => Ok(Node::Number(token.text.to_string())),
+            TokenKind::Identifier if self.next_is(TokenKind::OpenParenthesis) => {
+                self.tokens.next();
+
+                let mut arguments = vec![self.expression()?];
+
+                while self
+                    .tokens
+                    .next_if(|token| token.kind == TokenKind::Comma)
+                    .is_some()
+                {
+                    arguments.push(self.expression()?);
+                }
+
+                self.expect(TokenKind::CloseParenthesis)?;
+
+                Ok(self.fold(Node::Call(token.text.to_string(), arguments)))
+            }
+            TokenKind::Identifier => Ok(Node::Name(token.text.to_string())),
+            TokenKind::OpenParenthesis => {
+                let inner = self.expression()?;
+
+                self.expect(TokenKind::CloseParenthesis)?;
+
+                Ok(inner)
+            }
+            _ => Err(anyhow!("unexpected '{}'", token.text)),
+        }
+    }
+
+    // Next token if it is an operator with a precedence accepted by the filter
+    fn operator(&mut self, accepts: impl Fn(u8) -> bool) -> Option<&'static str> {
+        let token = self.tokens.next_if(|token| {
+            token.kind == TokenKind::Operator && accepts(operators::precedence(&token.text))
+        })?;
+
+        operators::find(&token.text).map(|operator| operator.symbol)
+    }
+
+    fn check_parenthesized(&self, operator: &str) -> anyhow::Result<()> {
+        if self.full_parens {
+            return Err(anyhow!("operands of '{operator}' have to be parenthesized"));
+        }
+
+        Ok(())
+    }
+
+    fn next_is(&mut self, kind: TokenKind) -> bool {
+        self.tokens.peek().is_some_and(|token| token.kind == kind)
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> anyhow::Result<()> {
+        match self.tokens.next() {
+            Some(token) if token.kind == kind => Ok(()),
+            Some(token) => Err(anyhow!("unexpected '{}'", token.text)),
+            None => Err(anyhow!("unexpected end of expression")),
+        }
+    }
+
+    // Operands are already folded, so only this node is left to look at
+    fn fold(&self, node: Node) -> Node {
+        let Some(evaluate) = &self.evaluate else {
+            return node;
+        };
+
+        let constant = match &node {
+            Node::Negation(operand) => operand.is_constant(),
+            Node::Binary(_, left, right) => left.is_constant() && right.is_constant(),
+            Node::Comparisons(operands, _) => operands.iter().all(Node::is_constant),
+            Node::Call(name, arguments) => {
+                name != RANDOM_FUNCTION && arguments.iter().all(Node::is_constant)
+            }
+            Node::Number(_) | Node::Name(_) => false,
+        };
+
+        if constant {
+            if let Some(value) = evaluate(&node.print()) {
+                return Node::Number(value);
+            }
+        }
+
+        match node {
+            Node::Binary("+", left, right) if right.is_number(0.0) => *left,
+            Node::Binary("+", left, right) if left.is_number(0.0) => *right,
+            Node::Binary("-", left, right) if right.is_number(0.0) => *left,
+            Node::Binary("*", left, right) if left.is_number(0.0) || right.is_number(0.0) => {
+                Node::Number("0".to_string())
+            }
+            Node::Binary("*", left, right) if right.is_number(1.0) => *left,
+            Node::Binary("*", left, right) if left.is_number(1.0) => *right,
+            node => node,
+        }
+    }
+}
+
+fn parse(
+    expr: &str,
+    evaluate: Option<impl Fn(&str) -> Option<String>>,
+    full_parens: bool,
+) -> anyhow::Result<Node> {
+    let mut simplifier = Simplifier {
+        tokens: tokenize(expr).into_iter().peekable(),
+        evaluate,
+        full_parens,
+    };
+
+    let node = simplifier.expression()?;
+
+    if let Some(token) = simplifier.tokens.next() {
+        return Err(anyhow!("unexpected '{}'", token.text));
+    }
+
+    Ok(node)
+}
+
+// Folds the constant subexpressions of a validated expression, and drops the operations which don't change their other operand, like `x+0` and `x*1`
+pub fn simplify(expr: &str, evaluate: impl Fn(&str) -> Option<String>) -> anyhow::Result<String> {
+    Ok(parse(expr, Some(evaluate), false)?.print())
+}
+
+// Every operation of a validated expression on a line of its own, below the operation it is an operand of
+pub fn render_tree(expr: &str) -> anyhow::Result<String> {
+    let node = parse(expr, None::<fn(&str) -> Option<String>>, false)?;
+    let mut lines = vec![node.label()];
+
+    node.render_children("", &mut lines);
+
+    Ok(lines.join("\n"))
+}
+
+// Fails if the precedence or associativity of the operators decided how any operation of a validated expression is grouped, so `(1+(2*3))` passes but `1+2*3` doesn't
+pub fn check_full_parens(expr: &str) -> anyhow::Result<()> {
+    parse(expr, None::<fn(&str) -> Option<String>>, true).map(|_| ())
+}
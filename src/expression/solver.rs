@@ -1,147 +1,1239 @@
-use std::{num::ParseIntError, str::FromStr};
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    fmt,
+    str::FromStr,
+};
 
 use anyhow::anyhow;
 use regex::{Captures, Regex};
 
+use super::{operators, OpStats, Value};
+use crate::{random::Random, Config};
+
 pub type SolverResult<T, E = anyhow::Error> = anyhow::Result<T, E>;
 
-pub fn eval_subexpression(expr: String, multiplication_re: &Regex) -> SolverResult<i64> {
-    let expr = handle_multiplications(expr, &multiplication_re)?;
+// Result of calling a function which is not a builtin, `None` if there is no such function
+pub type FunctionCall<'a, N = Value> = &'a dyn Fn(&str, &[N]) -> Option<SolverResult<N>>;
+
+// Numerator and denominator of an exact value
+type Ratio = (i128, i128);
 
-    Ok(handle_summations(expr)?)
+// What the passes of the solver calculate with, values, or the integers of a number type
+// The arithmetic is given the solver, for its configuration and for recording truncated divisions
+pub trait Operand: Clone + fmt::Display {
+    fn from_text(text: &str) -> SolverResult<Self>;
+    fn from_i64(value: i64) -> SolverResult<Self>;
+    // Unlike the displayed form, this one is parsed back by the solver as a single operand
+    fn to_expression_text(&self) -> String;
+    fn is_zero(&self) -> bool;
+    // Absolute value, saturating at i64::MAX
+    fn magnitude(&self) -> i64;
+    // `None` if the two are unordered
+    fn ordering(&self, other: &Self) -> Option<Ordering>;
+    fn add(solver: &Solver<'_, Self>, first: Self, second: Self) -> SolverResult<Self>;
+    // A product or a quotient, the divisor of a quotient is never zero
+    fn multiply(
+        solver: &Solver<'_, Self>,
+        operator: &str,
+        first: Self,
+        second: Self,
+    ) -> SolverResult<Self>;
+    // `None` if there is no such builtin, or it doesn't take that many arguments
+    fn call_builtin(
+        solver: &Solver<'_, Self>,
+        name: &str,
+        arguments: &[Self],
+    ) -> Option<SolverResult<Self>>;
 }
 
-pub fn handle_parentheses(
-    expr: String,
-    multiplication_re: &Regex,
-    parenthesized_subexpr_re: &Regex,
-) -> SolverResult<String> {
-    calculate_and_replace(expr, parenthesized_subexpr_re, &|captures| {
-        calculate_replacement_for_subexpression(&captures, multiplication_re)
-    })
+const CONDITIONAL_PREFIX: &str = "if(";
+
+// Name and number of arguments of every builtin function
+const BUILTIN_FUNCTIONS: &[(&str, usize)] = &[
+    ("abs", 1),
+    ("if", 3),
+    ("pow", 2),
+    ("powmod", 3),
+    ("rand", 2),
+    ("sign", 1),
+];
+
+// Builtins which are only available in float mode, because they rarely have an integer result
+const FLOAT_BUILTIN_FUNCTIONS: &[(&str, usize)] = &[("ln", 1), ("log10", 1), ("log2", 1)];
+
+pub struct Solver<'a, N = Value> {
+    multiplication_re: &'a Regex,
+    parenthesized_subexpr_re: &'a Regex,
+    subexpression_re: &'a Regex,
+    conditional_re: &'a Regex,
+    config: &'a Config,
+    exact_division: bool,
+    // Numbers of rand, a generator seeded from entropy is used for every call if not set
+    random: Option<&'a RefCell<Random>>,
+    user_functions: Option<FunctionCall<'a, N>>,
+    truncated: Cell<bool>,
+    operations: Cell<usize>,
+    max_magnitude: Cell<i64>,
+    op_stats: Cell<OpStats>,
+    // Every form the expression takes on the way to its value, if set
+    trace: Option<&'a RefCell<Vec<String>>>,
+    // Number of subexpressions being solved for the expression, only the steps of the whole expression are traced
+    nesting: Cell<usize>,
+    // Lists of replacements kept from earlier evaluations, new ones are allocated if not set
+    scratch: Option<&'a SolverScratch>,
+}
+
+// A pass takes a list of replacements for as long as it runs, so the passes of nested subexpressions take other ones
+#[derive(Default)]
+pub struct SolverScratch {
+    replacements: RefCell<Vec<Vec<Replacement>>>,
 }
 
-struct BinaryOperation<'a> {
-    first_operand: i64,
+impl<'a, N: Operand> Solver<'a, N> {
+    pub fn new(
+        multiplication_re: &'a Regex,
+        parenthesized_subexpr_re: &'a Regex,
+        subexpression_re: &'a Regex,
+        conditional_re: &'a Regex,
+        config: &'a Config,
+    ) -> Self {
+        Solver {
+            multiplication_re,
+            parenthesized_subexpr_re,
+            subexpression_re,
+            conditional_re,
+            config,
+            exact_division: false,
+            random: None,
+            user_functions: None,
+            truncated: Cell::new(false),
+            operations: Cell::new(0),
+            max_magnitude: Cell::new(0),
+            op_stats: Cell::new(OpStats::default()),
+            trace: None,
+            nesting: Cell::new(0),
+            scratch: None,
+        }
+    }
+
+    // Integer division produces a rational instead of truncating, so the whole evaluation stays exact
+    pub fn exact_division(mut self, exact_division: bool) -> Self {
+        self.exact_division = exact_division;
+        self
+    }
+
+    pub fn random(mut self, random: Option<&'a RefCell<Random>>) -> Self {
+        self.random = random;
+        self
+    }
+
+    pub fn user_functions(mut self, user_functions: Option<FunctionCall<'a, N>>) -> Self {
+        self.user_functions = user_functions;
+        self
+    }
+
+    pub fn trace(mut self, trace: Option<&'a RefCell<Vec<String>>>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    pub fn scratch(mut self, scratch: Option<&'a SolverScratch>) -> Self {
+        self.scratch = scratch;
+        self
+    }
+
+    pub fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    // Whether any integer division of the solved expressions discarded a nonzero remainder
+    pub fn truncated(&self) -> bool {
+        self.truncated.get()
+    }
+
+    // With strict division a nonzero remainder is an error, otherwise it is discarded
+    pub fn discard_remainder(&self) -> SolverResult<()> {
+        if self.config.strict_division {
+            return Err(anyhow!("non-exact integer division"));
+        }
+
+        self.truncated.set(true);
+
+        Ok(())
+    }
+
+    // Number of arithmetic operations and function calls carried out so far, a sign in front of a term is not counted
+    pub fn operations(&self) -> usize {
+        self.operations.get()
+    }
+
+    fn count_operations(&self, count: usize) {
+        self.operations.set(self.operations.get() + count);
+    }
+
+    // Largest absolute value of the products, quotients and partial sums so far, saturating at i64::MAX
+    pub fn max_magnitude(&self) -> i64 {
+        self.max_magnitude.get()
+    }
+
+    pub fn op_stats(&self) -> OpStats {
+        self.op_stats.get()
+    }
+
+    fn count_op(&self, count: impl FnOnce(&mut OpStats)) {
+        let mut op_stats = self.op_stats.get();
+
+        count(&mut op_stats);
+        self.op_stats.set(op_stats);
+    }
+
+    // A pass which changed nothing visible is not a step
+    fn record_step(&self, expr: &str) {
+        let Some(trace) = self.trace.filter(|_| self.nesting.get() == 0) else {
+            return;
+        };
+
+        let mut steps = trace.borrow_mut();
+
+        if steps.last().is_none_or(|last| last != expr) {
+            steps.push(expr.to_string());
+        }
+    }
+
+    fn finish_pass(&self, expr: &str) {
+        debug!("solver pass gave '{expr}'");
+
+        self.record_step(expr);
+    }
+
+    fn nested<T>(&self, solve: impl FnOnce() -> SolverResult<T>) -> SolverResult<T> {
+        self.nesting.set(self.nesting.get() + 1);
+
+        let result = solve();
+
+        self.nesting.set(self.nesting.get() - 1);
+
+        result
+    }
+
+    fn record_magnitude(&self, value: N) -> N {
+        self.max_magnitude
+            .set(self.max_magnitude.get().max(value.magnitude()));
+
+        value
+    }
+
+    // The expression is solved in place, what is left of it is only of use as a buffer
+    pub fn solve(&self, expr: &mut String) -> SolverResult<N> {
+        self.record_step(expr);
+
+        let value = self.solve_steps(expr)?;
+
+        self.record_step(&value.to_string());
+
+        Ok(value)
+    }
+
+    fn solve_steps(&self, expr: &mut String) -> SolverResult<N> {
+        // Without parentheses there can't be conditionals or function calls either, so the expression is already a subexpression
+        if !expr.contains(['(', ')']) {
+            if !self.subexpression_re.is_match(expr) {
+                return Err(anyhow!("not a valid subexpression"));
+            }
+
+            return self.eval_subexpression(expr);
+        }
+
+        // Conditionals are resolved before anything else, so that the branch which is not taken never gets evaluated
+        self.handle_conditionals(expr)?;
+
+        self.record_step(expr);
+
+        // Solve parenthesized subexpressions, and transform the expression in a way that there are no more parentheses
+        self.handle_parentheses(expr)?;
+
+        if !self.subexpression_re.is_match(expr) {
+            return Err(anyhow!("not a valid subexpression"));
+        }
+
+        // After parentheses are removed, the expression is itself a subexpression
+        self.eval_subexpression(expr)
+    }
+
+    fn eval_subexpression(&self, expr: &mut String) -> SolverResult<N> {
+        if expr.contains(is_comparison_character) {
+            return self.eval_comparisons(expr);
+        }
+
+        self.eval_arithmetic(expr)
+    }
+
+    // A chain like 1<2<3 is true if every adjacent pair is, like in maths, so it is not 1<2 compared with 3
+    // Truth values are 1 and 0, every operand is evaluated even if an earlier pair is already false
+    fn eval_comparisons(&self, expr: &str) -> SolverResult<N> {
+        let mut operands = Vec::new();
+        let mut comparisons = Vec::new();
+        let mut rest = expr;
+
+        while let Some(index) = rest.find(is_comparison_character) {
+            // The longest one, so that `<=` is not taken for `<`
+            let operator = operators::peers("<")
+                .filter(|operator| rest[index..].starts_with(operator))
+                .max_by_key(|operator| operator.len())
+                .ok_or_else(|| anyhow!("invalid comparison operator"))?;
+
+            operands.push(&rest[..index]);
+            comparisons.push(operator);
+            rest = &rest[index + operator.len()..];
+        }
+
+        operands.push(rest);
+
+        let values = operands
+            .into_iter()
+            .map(|operand| match operand {
+                "" => Err(anyhow!("missing operand of comparison")),
+                operand => self.nested(|| self.eval_arithmetic(&mut operand.to_string())),
+            })
+            .collect::<SolverResult<Vec<N>>>()?;
+
+        self.count_operations(comparisons.len());
+
+        let holds = comparisons
+            .iter()
+            .zip(values.windows(2))
+            .all(|(operator, pair)| compare(operator, &pair[0], &pair[1]));
+        let value = N::from_i64(i64::from(holds))?;
+
+        self.finish_pass(&value.to_string());
+
+        Ok(value)
+    }
+
+    fn eval_arithmetic(&self, expr: &mut String) -> SolverResult<N> {
+        cancel_double_negations(expr);
+        self.handle_multiplications(expr)?;
+        // A negative product can end up after a sign too, like 1+-2*-6 becoming 1+--12
+        cancel_double_negations(expr);
+
+        self.handle_summations(expr)
+    }
+
+    // Conditions are always evaluated, so a conditional nested in the condition of another one is resolved first, instead of recursing into solve for every level of nesting
+    // The branches are only looked at once the condition is known, then the whole call is replaced by the taken branch, so the other one is never evaluated
+    fn handle_conditionals(&self, expr: &mut String) -> SolverResult<()> {
+        // Start of every conditional whose condition is being resolved, outermost first
+        // Replacements only happen after these positions, so they stay valid while the nested conditionals are resolved
+        let mut pending: Vec<usize> = Vec::new();
+
+        loop {
+            let Some(&start) = pending.last() else {
+                match self.conditional_re.find(expr) {
+                    Some(outermost) => {
+                        pending.push(outermost.start());
+                        continue;
+                    }
+                    None => break,
+                }
+            };
+
+            let arguments_start = start + CONDITIONAL_PREFIX.len();
+
+            if let Some(inner) = self
+                .conditional_re
+                .find_at(expr, arguments_start)
+                .filter(|inner| is_first_argument_prefix(&expr[arguments_start..inner.start()]))
+            {
+                pending.push(inner.start());
+                continue;
+            }
+
+            let arguments_end = find_closing_parenthesis(expr, arguments_start)?;
+            let arguments = split_arguments(&expr[arguments_start..arguments_end]);
+
+            let [condition, then_branch, else_branch] = arguments[..] else {
+                return Err(anyhow!("if expects 3 arguments, got {}", arguments.len()));
+            };
+
+            let branch = if !self
+                .nested(|| self.solve(&mut condition.to_string()))?
+                .is_zero()
+            {
+                then_branch
+            } else {
+                else_branch
+            };
+
+            let replacement = format!("({branch})");
+
+            expr.replace_range(start..=arguments_end, &replacement);
+            pending.pop();
+        }
+
+        Ok(())
+    }
+
+    fn handle_parentheses(&self, expr: &mut String) -> SolverResult<()> {
+        // Innermost parentheses don't depend on each other, so all of them can be solved in the same pass
+        self.calculate_and_replace(
+            expr,
+            self.parenthesized_subexpr_re,
+            |_, _| true,
+            &|_, captures| self.nested(|| self.calculate_replacement_for_subexpression(captures)),
+        )
+    }
+
+    // A hyphen right after a number is a subtraction, which is left in place, anywhere else it is the sign of the first operand
+    // The sign has to be read together with the digits, because the magnitude of MIN alone doesn't fit in an i64
+    fn calculate_replacement_for_multiplication(
+        &self,
+        expr: &str,
+        captures: &Captures,
+    ) -> SolverResult<Replacement> {
+        let full_match = captures.get(0).ok_or_else(|| anyhow!("match not found"))?;
+        let subtraction = full_match.as_str().starts_with('-')
+            && expr[..full_match.start()].ends_with(|c: char| c.is_ascii_digit());
+        let start = full_match.start() + usize::from(subtraction);
+
+        let result = self
+            .eval_multiplication(BinaryOperation::from_captures(captures, subtraction)?)?
+            .to_expression_text();
+
+        Ok(Replacement {
+            start,
+            end: full_match.end(),
+            new_value: result,
+        })
+    }
+
+    fn calculate_replacement_for_subexpression(
+        &self,
+        captures: &Captures,
+    ) -> SolverResult<Replacement> {
+        let function_name = captures
+            .get(1)
+            .ok_or_else(|| anyhow!("function name not found"))?
+            .as_str();
+        let subexpr = captures
+            .get(2)
+            .ok_or_else(|| anyhow!("subexpression not found"))?
+            .as_str();
+
+        let subexpr_result = if function_name.is_empty() {
+            if subexpr.contains(',') {
+                return Err(anyhow!("argument separator outside of function call"));
+            }
+
+            self.eval_subexpression(&mut subexpr.to_string())?
+        } else {
+            let arguments = split_arguments(subexpr)
+                .into_iter()
+                .map(|argument| self.eval_subexpression(&mut argument.to_string()))
+                .collect::<SolverResult<Vec<N>>>()?;
+
+            self.call_function(function_name, &arguments)?
+        }
+        .to_expression_text();
+
+        let full_match = captures.get(0).ok_or_else(|| anyhow!("match not found"))?;
+
+        Ok(Replacement {
+            start: full_match.start(),
+            end: full_match.end(),
+            new_value: subexpr_result,
+        })
+    }
+
+    fn call_function(&self, name: &str, arguments: &[N]) -> SolverResult<N> {
+        self.count_operations(1);
+
+        if let Some(result) = N::call_builtin(self, name, arguments) {
+            return result;
+        }
+
+        if !is_builtin(name) {
+            return self
+                .user_functions
+                .and_then(|call| call(name, arguments))
+                .unwrap_or_else(|| Err(anyhow!("unknown function '{name}'")));
+        }
+
+        match BUILTIN_FUNCTIONS
+            .iter()
+            .chain(
+                FLOAT_BUILTIN_FUNCTIONS
+                    .iter()
+                    .filter(|_| self.config.float_mode),
+            )
+            .find(|(builtin, _)| *builtin == name)
+        {
+            Some((_, arity)) => Err(anyhow!(
+                "{name} expects {arity} argument{}, got {}",
+                if *arity == 1 { "" } else { "s" },
+                arguments.len()
+            )),
+            None => Err(anyhow!("unknown function '{name}'")),
+        }
+    }
+
+    fn eval_multiplication(&self, binary_operation: BinaryOperation<N>) -> SolverResult<N> {
+        let BinaryOperation {
+            first_operand,
+            operator,
+            second_operand,
+        } = binary_operation;
+
+        self.count_operations(1);
+
+        match operator {
+            "*" => self.count_op(|op_stats| op_stats.multiplications += 1),
+            _ => self.count_op(|op_stats| op_stats.divisions += 1),
+        }
+
+        if operator == "/" && second_operand.is_zero() {
+            return Err(anyhow!("division by zero"));
+        }
+
+        N::multiply(self, operator, first_operand, second_operand)
+            .map(|value| self.record_magnitude(value))
+    }
+
+    fn handle_multiplications(&self, expr: &mut String) -> SolverResult<()> {
+        self.calculate_and_replace(
+            expr,
+            self.multiplication_re,
+            starts_chain,
+            &|expr, captures| self.calculate_replacement_for_multiplication(expr, captures),
+        )
+    }
+
+    // The list of replacements is taken from the scratch while the passes run, and given back after them
+    fn calculate_and_replace<F>(
+        &self,
+        expr: &mut String,
+        re: &Regex,
+        can_replace: fn(&str, &Captures) -> bool,
+        calculate_fn: &F,
+    ) -> SolverResult<()>
+    where
+        F: Fn(&str, &Captures) -> SolverResult<Replacement>,
+    {
+        let mut replacements = self
+            .scratch
+            .and_then(|scratch| scratch.replacements.borrow_mut().pop())
+            .unwrap_or_default();
+
+        let result = replace_matches(
+            expr,
+            re,
+            can_replace,
+            calculate_fn,
+            &|expr| self.finish_pass(expr),
+            &mut replacements,
+        );
+
+        if let Some(scratch) = self.scratch {
+            replacements.clear();
+            scratch.replacements.borrow_mut().push(replacements);
+        }
+
+        result
+    }
+
+    fn handle_summations(&self, expr: &mut String) -> SolverResult<N> {
+        let leading_hyphen = separate_terms(expr);
+
+        // The zero inserted in front of a leading hyphen is not a term of the input
+        let term_count = expr.matches('+').count() + 1 - usize::from(leading_hyphen);
+
+        if self
+            .config
+            .max_terms
+            .is_some_and(|max_terms| term_count > max_terms)
+        {
+            return Err(anyhow!("too many terms"));
+        }
+
+        self.count_operations(term_count - 1);
+
+        // Only the terms after the first one are added, and a subtracted one is negative by now
+        let subtractions = expr
+            .split('+')
+            .skip(1 + usize::from(leading_hyphen))
+            .filter(|term| term.starts_with('-'))
+            .count();
+
+        self.count_op(|op_stats| {
+            op_stats.additions += term_count - 1 - subtractions;
+            op_stats.subtractions += subtractions;
+        });
+
+        let sum = expr
+            .split('+')
+            .map(N::from_text)
+            .collect::<SolverResult<Vec<N>>>()?
+            .into_iter()
+            .try_fold(N::from_i64(0)?, |sum, term| {
+                N::add(self, sum, term).map(|sum| self.record_magnitude(sum))
+            })?;
+
+        // A single term is not a pass which did anything
+        if term_count > 1 {
+            self.finish_pass(&sum.to_string());
+        }
+
+        Ok(sum)
+    }
+}
+
+impl Solver<'_, Value> {
+    fn pow(&self, base: Value, exponent: Value) -> SolverResult<Value> {
+        match (base, exponent) {
+            (Value::Int(_), Value::Int(exponent)) if exponent < 0 => {
+                Err(anyhow!("negative exponent"))
+            }
+            (Value::Int(base), Value::Int(exponent)) => {
+                let exponent =
+                    u32::try_from(exponent).map_err(|_| anyhow!("exponent too large"))?;
+
+                self.checked(base.checked_pow(exponent), base.wrapping_pow(exponent))
+                    .map(Value::Int)
+            }
+            (Value::Rational { .. }, Value::Int(exponent)) if exponent < 0 => {
+                Err(anyhow!("negative exponent"))
+            }
+            (
+                Value::Rational {
+                    numerator,
+                    denominator,
+                },
+                Value::Int(exponent),
+            ) => {
+                let exponent =
+                    u32::try_from(exponent).map_err(|_| anyhow!("exponent too large"))?;
+                let power = |value: i64| {
+                    i128::from(value)
+                        .checked_pow(exponent)
+                        .ok_or_else(|| anyhow!("integer overflow"))
+                };
+
+                Value::rational(power(numerator)?, power(denominator)?)
+            }
+            _ => float_result(base.as_f64().powf(exponent.as_f64())),
+        }
+    }
+
+    // Integers stay integers and a rational operand makes the operation exact, but a single float operand promotes the operation to float arithmetic
+    // Numerators and denominators fit in i64, so the intermediate i128 products can't overflow
+    fn arithmetic(
+        &self,
+        first: Value,
+        second: Value,
+        checked: fn(i64, i64) -> Option<i64>,
+        wrapping: fn(i64, i64) -> i64,
+        float: fn(f64, f64) -> f64,
+        rational: fn(Ratio, Ratio) -> Ratio,
+    ) -> SolverResult<Value> {
+        match (first, second) {
+            (Value::Int(first), Value::Int(second)) => self
+                .checked(checked(first, second), wrapping(first, second))
+                .map(Value::Int),
+            _ => match (first.as_ratio(), second.as_ratio()) {
+                (Some(first), Some(second)) => {
+                    let (numerator, denominator) = rational(first, second);
+
+                    Value::rational(numerator, denominator)
+                }
+                _ => float_result(float(first.as_f64(), second.as_f64())),
+            },
+        }
+    }
+
+    // Without a configured width overflowing is an error, otherwise the result is wrapped around
+    fn checked(&self, checked: Option<i64>, wrapped: i64) -> SolverResult<i64> {
+        match self.config.wrap_bits {
+            Some(_) => Ok(self.wrap(wrapped)),
+            None => checked.ok_or_else(|| anyhow!("integer overflow")),
+        }
+    }
+
+    // Without a configured width arithmetic is plain i64, otherwise the value is truncated to the low bits and sign-extended (two's complement)
+    fn wrap(&self, value: i64) -> i64 {
+        match self.config.wrap_bits {
+            Some(bits) if bits < i64::BITS => {
+                let shift = i64::BITS - bits;
+
+                (value << shift) >> shift
+            }
+            _ => value,
+        }
+    }
+
+    fn powmod(&self, base: Value, exponent: Value, modulus: Value) -> SolverResult<Value> {
+        let name = "powmod";
+        let result = powmod(
+            integer_argument(name, base)?,
+            integer_argument(name, exponent)?,
+            integer_argument(name, modulus)?,
+        )?;
+
+        self.checked(Some(result), result).map(Value::Int)
+    }
+
+    fn rand(&self, lower: Value, upper: Value) -> SolverResult<Value> {
+        let (lower, upper) = (
+            integer_argument("rand", lower)?,
+            integer_argument("rand", upper)?,
+        );
+
+        if lower > upper {
+            return Err(anyhow!(
+                "rand lower bound {lower} is greater than upper bound {upper}"
+            ));
+        }
+
+        let value = match self.random {
+            Some(random) => random.borrow_mut().in_range(lower, upper),
+            None => Random::from_entropy().in_range(lower, upper),
+        };
+
+        Ok(Value::Int(value))
+    }
+}
+
+impl Operand for Value {
+    fn from_text(text: &str) -> SolverResult<Self> {
+        Value::from_str(text)
+    }
+
+    fn from_i64(value: i64) -> SolverResult<Self> {
+        Ok(Value::Int(value))
+    }
+
+    fn to_expression_text(&self) -> String {
+        Value::to_expression_text(*self)
+    }
+
+    fn is_zero(&self) -> bool {
+        Value::is_zero(*self)
+    }
+
+    // Floats and rationals are measured by their integer part
+    fn magnitude(&self) -> i64 {
+        match self {
+            Value::Int(value) => value.saturating_abs(),
+            _ => self.as_f64().abs() as i64,
+        }
+    }
+
+    // Exact values are compared exactly, anything involving a float as floats, where a NaN is unordered
+    fn ordering(&self, other: &Self) -> Option<Ordering> {
+        match (self.as_ratio(), other.as_ratio()) {
+            (Some((a, b)), Some((c, d))) => Some((a * d).cmp(&(c * b))),
+            _ => self.as_f64().partial_cmp(&other.as_f64()),
+        }
+    }
+
+    fn add(solver: &Solver<'_, Self>, first: Self, second: Self) -> SolverResult<Self> {
+        solver.arithmetic(
+            first,
+            second,
+            i64::checked_add,
+            i64::wrapping_add,
+            |a, b| a + b,
+            |(a, b), (c, d)| (a * d + c * b, b * d),
+        )
+    }
+
+    fn multiply(
+        solver: &Solver<'_, Self>,
+        operator: &str,
+        first: Self,
+        second: Self,
+    ) -> SolverResult<Self> {
+        match operator {
+            "*" => solver.arithmetic(
+                first,
+                second,
+                i64::checked_mul,
+                i64::wrapping_mul,
+                |a, b| a * b,
+                |(a, b), (c, d)| (a * c, b * d),
+            ),
+            "/" if solver.config.divide_promotes_to_float => {
+                float_result(first.as_f64() / second.as_f64())
+            }
+            "/" if solver.exact_division => match (first.as_ratio(), second.as_ratio()) {
+                (Some((a, b)), Some((c, d))) => Value::rational(a * d, b * c),
+                _ => float_result(first.as_f64() / second.as_f64()),
+            },
+            "/" => {
+                if let (Value::Int(first), Value::Int(second)) = (first, second) {
+                    if first.wrapping_rem(second) != 0 {
+                        solver.discard_remainder()?;
+                    }
+                }
+
+                solver.arithmetic(
+                    first,
+                    second,
+                    i64::checked_div,
+                    i64::wrapping_div,
+                    |a, b| a / b,
+                    |(a, b), (c, d)| (a * d, b * c),
+                )
+            }
+            _ => Err(anyhow!("invalid operator for multiplication")),
+        }
+    }
+
+    fn call_builtin(
+        solver: &Solver<'_, Self>,
+        name: &str,
+        arguments: &[Self],
+    ) -> Option<SolverResult<Self>> {
+        let result = match (name, arguments) {
+            ("abs", &[Value::Int(value)]) => solver
+                .checked(value.checked_abs(), value.wrapping_abs())
+                .map(Value::Int),
+            ("abs", &[Value::Float(value)]) => Ok(Value::Float(value.abs())),
+            (
+                "abs",
+                &[Value::Rational {
+                    numerator,
+                    denominator,
+                }],
+            ) => Value::rational(i128::from(numerator).abs(), denominator.into()),
+            ("pow", &[base, exponent]) => solver.pow(base, exponent),
+            ("ln" | "log10" | "log2", &[value]) if solver.config.float_mode => {
+                if value.as_f64() <= 0.0 {
+                    return Some(Err(anyhow!("{name} expects a positive argument")));
+                }
+
+                let logarithm = match name {
+                    "ln" => f64::ln,
+                    "log10" => f64::log10,
+                    _ => f64::log2,
+                };
+
+                float_result(logarithm(value.as_f64()))
+            }
+            ("powmod", &[base, exponent, modulus]) => solver.powmod(base, exponent, modulus),
+            ("rand", &[lower, upper]) => solver.rand(lower, upper),
+            // A negative zero is zero too, the sign of a float is only what comparing it with zero tells
+            ("sign", &[value]) => Ok(Value::Int(match value {
+                Value::Int(value) => value.signum(),
+                Value::Float(0.0) => 0,
+                Value::Float(value) => value.signum() as i64,
+                Value::Rational { numerator, .. } => numerator.signum(),
+            })),
+            _ => return None,
+        };
+
+        Some(result)
+    }
+}
+
+struct BinaryOperation<'a, N> {
+    first_operand: N,
     operator: &'a str,
-    second_operand: i64,
+    second_operand: N,
 }
 
-impl<'a> BinaryOperation<'a> {
-    fn from_captures(captures: &'a Captures) -> SolverResult<Self> {
+impl<'a, N: Operand> BinaryOperation<'a, N> {
+    // The hyphen in front of the first operand is left out if it is a subtraction
+    fn from_captures(captures: &'a Captures, subtraction: bool) -> SolverResult<Self> {
+        let first_operand = captures
+            .get(1)
+            .ok_or(anyhow!("first operand not found"))?
+            .as_str();
+
         Ok(BinaryOperation {
-            first_operand: captures
-                .get(1)
-                .ok_or(anyhow!("first operand not found"))?
-                .as_str()
-                .parse()?,
+            first_operand: N::from_text(&first_operand[usize::from(subtraction)..])?,
             operator: captures
                 .get(2)
                 .ok_or(anyhow!("operator not found"))?
                 .as_str(),
-            second_operand: captures
-                .get(3)
-                .ok_or(anyhow!("second operand not found"))?
-                .as_str()
-                .parse()?,
+            second_operand: N::from_text(
+                captures
+                    .get(3)
+                    .ok_or(anyhow!("second operand not found"))?
+                    .as_str(),
+            )?,
         })
     }
 }
 
+// A multiplication continuing a chain like the 2/2 in 8/2/2 or in 8*-2/2 has to wait for the result on its left, so that the chain is evaluated left to right
+fn starts_chain(expr: &str, captures: &Captures) -> bool {
+    captures.get(0).is_some_and(|full_match| {
+        let before = &expr[..full_match.start()];
+
+        let before = before.strip_suffix('-').unwrap_or(before);
+
+        !operators::peers("*").any(|operator| before.ends_with(operator))
+    })
+}
+
+// Every subtraction becomes the addition of a negative term, so the terms are separated by plus signs only
+// Also tells whether a zero had to be inserted to have a term in front of a leading hyphen
+fn separate_terms(expr: &mut String) -> bool {
+    let leading_hyphen = expr.starts_with('-');
+
+    if leading_hyphen {
+        expr.replace_range(0..0, "0");
+    }
+
+    // Copied back, so the expression keeps its buffer
+    let separated = expr
+        .replace("--", "+")
+        .replace("+-", "-")
+        .replace("-", "+-");
+
+    expr.clear();
+    expr.push_str(&separated);
+
+    leading_hyphen
+}
+
 struct Replacement {
     start: usize,
     end: usize,
     new_value: String,
 }
 
-fn calculate_and_replace<F>(mut expr: String, re: &Regex, calculate_fn: &F) -> SolverResult<String>
+// Matches for which can_replace is false are left for a later pass, except for the first one, so every pass makes progress
+// The expression is shown to the observer after every pass, the list only holds the replacements of the pass being calculated
+fn replace_matches<F>(
+    expr: &mut String,
+    re: &Regex,
+    can_replace: fn(&str, &Captures) -> bool,
+    calculate_fn: &F,
+    after_pass: &dyn Fn(&str),
+    replacements: &mut Vec<Replacement>,
+) -> SolverResult<()>
 where
-    F: Fn(&Captures) -> SolverResult<Replacement>,
+    F: Fn(&str, &Captures) -> SolverResult<Replacement>,
 {
-    while let Some(all_captures) = get_all_captures(&expr, re) {
-        let replacements = all_captures
-            .iter()
-            .map(calculate_fn)
-            .collect::<SolverResult<Vec<Replacement>>>()?;
+    loop {
+        replacements.clear();
+
+        for (index, captures) in re.captures_iter(expr).enumerate() {
+            if index == 0 || can_replace(expr, &captures) {
+                replacements.push(calculate_fn(expr, &captures)?);
+            }
+        }
+
+        if replacements.is_empty() {
+            return Ok(());
+        }
 
         for replacement in replacements.iter().rev() {
             expr.replace_range(replacement.start..replacement.end, &replacement.new_value);
         }
+
+        after_pass(expr);
+    }
+}
+
+fn float_result(value: f64) -> SolverResult<Value> {
+    if value.is_nan() {
+        return Err(anyhow!("result is not a number"));
+    }
+
+    if value.is_infinite() {
+        return Err(anyhow!("float overflow"));
     }
 
-    Ok(expr)
+    Ok(Value::Float(value))
 }
 
-fn calculate_replacement_for_multiplication(captures: &Captures) -> SolverResult<Replacement> {
-    let result = eval_multiplication(&BinaryOperation::from_captures(&captures)?)?.to_string();
+// Float builtins count too, so a name means the same in both modes
+pub fn is_builtin(name: &str) -> bool {
+    BUILTIN_FUNCTIONS
+        .iter()
+        .chain(FLOAT_BUILTIN_FUNCTIONS)
+        .any(|(builtin, _)| *builtin == name)
+}
+
+fn integer_argument(function_name: &str, argument: Value) -> SolverResult<i64> {
+    match argument {
+        Value::Int(value) => Ok(value),
+        Value::Float(_) | Value::Rational { .. } => {
+            Err(anyhow!("{function_name} expects integer arguments"))
+        }
+    }
+}
 
-    let full_match = captures.get(0).unwrap();
+// Square-and-multiply, with intermediate products kept in i128 so they can't overflow even for moduli close to i64::MAX
+fn powmod(base: i64, exponent: i64, modulus: i64) -> SolverResult<i64> {
+    if modulus == 0 {
+        return Err(anyhow!("powmod modulus is zero"));
+    }
 
-    Ok(Replacement {
-        start: full_match.start(),
-        end: full_match.end(),
-        new_value: result,
-    })
+    if exponent < 0 {
+        return Err(anyhow!("powmod exponent is negative"));
+    }
+
+    let modulus = i128::from(modulus).abs();
+    let mut base = i128::from(base).rem_euclid(modulus);
+    let mut exponent = exponent;
+    let mut result = 1 % modulus;
+
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base % modulus;
+        }
+
+        base = base * base % modulus;
+        exponent >>= 1;
+    }
+
+    Ok(i64::try_from(result)?)
 }
 
-fn calculate_replacement_for_subexpression(
-    captures: &Captures,
-    multiplication_re: &Regex,
-) -> SolverResult<Replacement> {
-    let subexpr = captures.get(1).unwrap().as_str();
+// A bar closes the innermost open group when it follows a digit, a name, a closing parenthesis or another closing bar, otherwise it opens a new one
+// This makes nesting unambiguous: in |1-|2-5|| the second bar follows an operator, so it opens, and the last two both close
+pub fn replace_absolute_value_bars(expr: &str) -> SolverResult<String> {
+    let mut result = String::with_capacity(expr.len());
+    let mut depth = 0;
+    let mut previous_closes = false;
 
-    let subexpr_result =
-        eval_subexpression(String::from_str(subexpr)?, multiplication_re)?.to_string();
+    for c in expr.chars() {
+        if c != '|' {
+            result.push(c);
+            previous_closes = c.is_ascii_alphanumeric() || c == '_' || c == ')';
+            continue;
+        }
 
-    let full_match = captures.get(0).unwrap();
+        if previous_closes {
+            if depth == 0 {
+                return Err(anyhow!("unmatched absolute value bar"));
+            }
 
-    Ok(Replacement {
-        start: full_match.start(),
-        end: full_match.end(),
-        new_value: subexpr_result,
-    })
+            depth -= 1;
+            result.push(')');
+        } else {
+            depth += 1;
+            result.push_str("abs(");
+        }
+    }
+
+    if depth != 0 {
+        return Err(anyhow!("unmatched absolute value bar"));
+    }
+
+    Ok(result)
+}
+
+// Parentheses wrapping the whole expression would only cost the solver an extra pass each, so they are removed up front
+pub fn strip_enclosing_parentheses(mut expr: &str) -> &str {
+    while expr.starts_with('(') && find_closing_parenthesis(expr, 1).ok() == Some(expr.len() - 1) {
+        expr = &expr[1..expr.len() - 1];
+    }
+
+    expr
 }
 
-fn eval_multiplication(binary_operation: &BinaryOperation) -> SolverResult<i64> {
-    match binary_operation.operator {
-        "*" => Ok(binary_operation.first_operand * binary_operation.second_operand),
-        "/" => match binary_operation.second_operand {
-            0 => return Err(anyhow!("division by zero")),
-            nonzero => Ok(binary_operation.first_operand / nonzero),
-        },
-        _ => Err(anyhow!("invalid operator for multiplication")),
+// Exponentiation is right associative, so the rightmost operator is rewritten first, and its operands become the arguments of a pow call
+// The base is the number or parenthesized group right before the operator, so in -2^2 the unary minus applies to the result, like in mathematical notation
+pub fn replace_exponentiations(expr: &str, result: &mut String) -> SolverResult<()> {
+    result.clear();
+    result.push_str(expr);
+
+    while let Some(operator) = result.rfind('^') {
+        let base_start = find_base_start(&result[..operator])
+            .ok_or_else(|| anyhow!("missing base before '^'"))?;
+        let exponent_end = operator
+            + 1
+            + find_exponent_length(&result[operator + 1..])
+                .ok_or_else(|| anyhow!("missing exponent after '^'"))?;
+
+        // The call is written around the operands where they are, from the right so that the positions on the left stay valid
+        result.insert(exponent_end, ')');
+        result.replace_range(operator..=operator, ",");
+        result.insert_str(base_start, "pow(");
+
+        debug!("exponentiation pass gave '{result}'");
     }
+
+    Ok(())
 }
 
-fn get_all_captures<'a>(value: &'a str, pattern_re: &Regex) -> Option<Vec<Captures<'a>>> {
-    let all_captures: Vec<Captures> = pattern_re.captures_iter(value).collect();
+fn find_base_start(before: &str) -> Option<usize> {
+    let bytes = before.as_bytes();
 
-    if all_captures.is_empty() {
+    match bytes.last()? {
+        b'0'..=b'9' | b'.' => Some(
+            before
+                .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+                .len(),
+        ),
+        b')' => {
+            let mut depth = 0;
+
+            for (index, c) in before.char_indices().rev() {
+                match c {
+                    ')' => depth += 1,
+                    '(' if depth == 1 => {
+                        // A function call is a single operand together with its name
+                        return Some(
+                            before[..index]
+                                .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_')
+                                .len(),
+                        );
+                    }
+                    '(' => depth -= 1,
+                    _ => {}
+                }
+            }
+
+            None
+        }
+        _ => None,
+    }
+}
+
+fn find_exponent_length(after: &str) -> Option<usize> {
+    let sign_length = usize::from(after.starts_with('-'));
+    let operand = &after[sign_length..];
+
+    // Names may contain digits, just not start with one
+    let name_length = if operand.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        operand.len()
+            - operand
+                .trim_start_matches(|c: char| c.is_ascii_alphanumeric() || c == '_')
+                .len()
+    } else {
+        0
+    };
+
+    let operand_length = if name_length > 0 || operand.starts_with('(') {
+        if !operand[name_length..].starts_with('(') {
+            return None;
+        }
+
+        find_closing_parenthesis(operand, name_length + 1).ok()? + 1
+    } else {
+        operand.len()
+            - operand
+                .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+                .len()
+    };
+
+    if operand_length == 0 {
         return None;
     }
 
-    Some(all_captures)
+    Some(sign_length + operand_length)
 }
 
-fn handle_multiplications(expr: String, multiplication_re: &Regex) -> SolverResult<String> {
-    calculate_and_replace(expr, multiplication_re, &|captures| {
-        calculate_replacement_for_multiplication(&captures)
-    })
+// Replacing a negative value right after a sign leaves hyphens next to each other, like 2*-(-3) becoming 2*--3, or 1+-2*-6 becoming 1+--12
+// A single sign is all the solver can read after an operator, so every run of hyphens is replaced by what it amounts to
+// After an operand the first hyphen of a run is a subtraction and the rest are negations, anywhere else all of them are negations
+fn cancel_double_negations(expr: &mut String) {
+    if !expr.contains("--") {
+        return;
+    }
+
+    let mut result = String::with_capacity(expr.len());
+    let mut chars = expr.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '-' {
+            result.push(c);
+            continue;
+        }
+
+        let mut hyphens = 1;
+
+        while chars.next_if_eq(&'-').is_some() {
+            hyphens += 1;
+        }
+
+        let after_operand = result.ends_with(|c: char| c.is_ascii_digit() || c == ')');
+
+        let negations = if after_operand { hyphens - 1 } else { hyphens };
+
+        match (after_operand, negations % 2 == 1) {
+            (true, true) => result.push('+'),
+            (true, false) | (false, true) => result.push('-'),
+            (false, false) => {}
+        }
+    }
+
+    expr.clear();
+    expr.push_str(&result);
 }
 
-fn handle_summations(mut expr: String) -> SolverResult<i64> {
-    if expr.starts_with('-') {
-        expr.replace_range(0..0, "0");
+fn find_closing_parenthesis(expr: &str, start: usize) -> SolverResult<usize> {
+    let mut depth = 0;
+
+    for (index, c) in expr[start..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Ok(start + index),
+            ')' => depth -= 1,
+            _ => {}
+        }
     }
 
-    expr = expr.replace("--", "+");
-    expr = expr.replace("+-", "-");
-    expr = expr.replace("-", "+-");
+    Err(anyhow!("missing closing parenthesis"))
+}
 
-    Ok(expr
-        .split('+')
-        .map(|num_str| num_str.parse::<i64>())
-        .collect::<Result<Vec<i64>, ParseIntError>>()?
-        .iter()
-        .sum())
+// Whether the text following an opening parenthesis is still inside the first argument, i.e. it doesn't reach a top level separator or the closing parenthesis
+fn is_first_argument_prefix(text: &str) -> bool {
+    let mut depth = 0;
+
+    for c in text.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return false,
+            ')' => depth -= 1,
+            ',' if depth == 0 => return false,
+            _ => {}
+        }
+    }
+
+    true
+}
+
+fn is_comparison_character(c: char) -> bool {
+    operators::peers("<").any(|operator| operator.starts_with(c))
+}
+
+fn compare<N: Operand>(operator: &str, first: &N, second: &N) -> bool {
+    let ordering = first.ordering(second);
+
+    match operator {
+        "<" => ordering == Some(Ordering::Less),
+        "<=" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        ">" => ordering == Some(Ordering::Greater),
+        ">=" => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        "==" => ordering == Some(Ordering::Equal),
+        _ => ordering != Some(Ordering::Equal),
+    }
+}
+
+fn split_arguments(arguments: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut argument_start = 0;
+
+    for (index, c) in arguments.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(&arguments[argument_start..index]);
+                argument_start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    result.push(&arguments[argument_start..]);
+
+    result
 }
@@ -0,0 +1,35 @@
+use std::ops::Range;
+
+use super::tokenizer::{tokenize, TokenKind};
+
+// Spans of every parenthesized group of the original input in the order they are opened, parentheses included
+// Arguments of a function call are not a group of their own, because they can't be evaluated on their own
+pub fn parenthesized_groups(expr: &str) -> Vec<Range<usize>> {
+    let tokens = tokenize(expr);
+    let mut groups = Vec::new();
+    let mut open = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::OpenParenthesis => {
+                let is_call = index.checked_sub(1).is_some_and(|previous| {
+                    tokens[previous].kind == TokenKind::Identifier
+                        && tokens[previous].span.end == token.span.start
+                });
+
+                open.push((token.span.start, is_call));
+            }
+            TokenKind::CloseParenthesis => {
+                if let Some((start, is_call)) = open.pop() {
+                    if !is_call {
+                        groups.push(start..token.span.end);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    groups.sort_by_key(|group| group.start);
+    groups
+}
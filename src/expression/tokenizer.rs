@@ -0,0 +1,184 @@
+use std::{borrow::Cow, iter::Peekable, ops::Range, str::CharIndices};
+
+use super::operators;
+
+/// Kind of a token, see `tokenize_lossy`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Number,
+    Identifier,
+    Operator,
+    Superscript,
+    OpenParenthesis,
+    CloseParenthesis,
+    Bar,
+    Comma,
+    /// A character which can't start any token, the ones after it are tokenized as usual
+    Invalid,
+}
+
+/// Part of an expression, with its byte range in it
+///
+/// The text has the ASCII forms of full-width characters, like `2` for `２`, while the span is the one of what was typed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: Cow<'a, str>,
+    pub span: Range<usize>,
+}
+
+struct Tokens<'a> {
+    expr: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+// Unlike the evaluator, tokenizing works on the original input, so spans point into what the user actually typed
+pub fn tokenize(expr: &str) -> Vec<Token<'_>> {
+    tokenize_lossy(expr).collect()
+}
+
+/// Tokens of the expression one at a time, an invalid character is an `Invalid` token instead of the end
+///
+/// Nothing is rejected, so an editor can highlight every token of an expression which doesn't evaluate.
+pub fn tokenize_lossy(expr: &str) -> impl Iterator<Item = Token<'_>> {
+    Tokens {
+        expr,
+        chars: expr.char_indices().peekable(),
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let expr = self.expr;
+        let chars = &mut self.chars;
+        let (start, written) = chars.find(|(_, c)| !c.is_whitespace())?;
+        let c = super::half_width(written);
+
+        let kind = match c {
+            '0'..='9' => TokenKind::Number,
+            // Result references like `$2` are named values too
+            'A'..='Z' | 'a'..='z' | '_' | '$' => TokenKind::Identifier,
+            c if operators::is_operator(c) => TokenKind::Operator,
+            c if super::replacement(c)
+                .is_some_and(|text| text.chars().all(operators::is_operator)) =>
+            {
+                TokenKind::Operator
+            }
+            '²' | '³' => TokenKind::Superscript,
+            '(' => TokenKind::OpenParenthesis,
+            ')' => TokenKind::CloseParenthesis,
+            '|' => TokenKind::Bar,
+            ',' => TokenKind::Comma,
+            _ => TokenKind::Invalid,
+        };
+
+        let mut end = start + written.len_utf8();
+
+        // Two character operators like `<=` are a single token
+        if kind == TokenKind::Operator {
+            if let Some(&(index, next)) = chars.peek() {
+                if operators::find(&half_width_text(&expr[start..index + next.len_utf8()]))
+                    .is_some()
+                {
+                    end = index + next.len_utf8();
+                    chars.next();
+                }
+            }
+        }
+
+        if matches!(kind, TokenKind::Number | TokenKind::Identifier) {
+            while let Some(&(index, written)) = chars.peek() {
+                let next = super::half_width(written);
+                let text = half_width_text(&expr[start..end]);
+                let continues = match kind {
+                    // Hexadecimal and binary literals like `0xff` are a single number too
+                    TokenKind::Number if is_radix_prefix(&text) => {
+                        next.is_ascii_alphanumeric() || next == '_'
+                    }
+                    TokenKind::Number if text == "0" => {
+                        next.is_ascii_digit() || matches!(next, '.' | 'x' | 'X' | 'b' | 'B')
+                    }
+                    TokenKind::Number => next.is_ascii_digit() || next == '.',
+                    _ => next.is_ascii_alphanumeric() || next == '_',
+                };
+
+                if !continues {
+                    break;
+                }
+
+                end = index + written.len_utf8();
+                chars.next();
+            }
+        }
+
+        Some(Token {
+            kind,
+            text: half_width_text(&expr[start..end]),
+            span: start..end,
+        })
+    }
+}
+
+// The text is only copied if it has full-width characters
+fn half_width_text(text: &str) -> Cow<'_, str> {
+    if text.chars().all(|c| super::half_width(c) == c) {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(text.chars().map(super::half_width).collect())
+    }
+}
+
+fn is_radix_prefix(number: &str) -> bool {
+    matches!(number.get(..2), Some("0x" | "0X" | "0b" | "0B"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_after_invalid_characters() {
+        let tokens: Vec<Token> = tokenize_lossy("1+@+2").collect();
+
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (token.kind, token.text.as_ref()))
+                .collect::<Vec<_>>(),
+            [
+                (TokenKind::Number, "1"),
+                (TokenKind::Operator, "+"),
+                (TokenKind::Invalid, "@"),
+                (TokenKind::Operator, "+"),
+                (TokenKind::Number, "2"),
+            ]
+        );
+
+        let spans: Vec<Range<usize>> = tokenize_lossy(" é# x<=0xfF")
+            .map(|token| token.span)
+            .collect();
+
+        assert_eq!(spans, [1..3, 3..4, 5..6, 6..8, 8..12]);
+    }
+
+    #[test]
+    fn reads_full_width_characters() {
+        let tokens: Vec<Token> = tokenize("１２＋ｘ＜＝３");
+
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (token.kind, token.text.as_ref(), token.span.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (TokenKind::Number, "12", 0..6),
+                (TokenKind::Operator, "+", 6..9),
+                (TokenKind::Identifier, "x", 9..12),
+                (TokenKind::Operator, "<=", 12..18),
+                (TokenKind::Number, "3", 18..21),
+            ]
+        );
+    }
+}
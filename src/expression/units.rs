@@ -0,0 +1,303 @@
+use anyhow::anyhow;
+use regex::Regex;
+
+// Every number, with the character before it and the unit written right after it
+pub const DURATION_PATTERN: &str =
+    r"(^|[^0-9A-Za-z_\.])([0-9]+(?:\.[0-9]+)?)([A-Za-z_][0-9A-Za-z_]*)?";
+
+// Every number with a magnitude suffix right after it, which can't be followed by more letters, so `2kg` is not `2k` and a name
+pub const MAGNITUDE_PATTERN: &str = r"(^|[^0-9A-Za-z_\$\.])([0-9]+(?:\.[0-9]+)?)(Ki|Mi|Gi|k|M|G)\b";
+
+// Factor of every magnitude suffix, the binary ones end with an i
+const MAGNITUDE_SUFFIXES: &[(&str, i64)] = &[
+    ("k", 1_000),
+    ("M", 1_000_000),
+    ("G", 1_000_000_000),
+    ("Ki", 1 << 10),
+    ("Mi", 1 << 20),
+    ("Gi", 1 << 30),
+];
+
+// Length of every unit in minutes, the unit results are in
+const TIME_UNITS: &[(&str, i64)] = &[("m", 1), ("h", 60), ("d", 24 * 60), ("w", 7 * 24 * 60)];
+
+// Characters which end a term, besides the plus and minus between two terms, what is joined by them has to be a duration on both sides
+const TERM_SEPARATORS: &[char] = &['<', '>', '=', '!', '&', '|', ',', '?', ':'];
+
+// Terms of a sum, in the innermost parentheses which are still open
+#[derive(Default)]
+struct Sum<'a> {
+    // Whether the term being read has a duration in it, and its first plain number
+    term_has_duration: bool,
+    term_number: Option<&'a str>,
+    // Multiplication or division before the operand being read, in the term being read
+    term_operator: Option<char>,
+    has_duration: bool,
+    plain_number: Option<&'a str>,
+    // Whether the parentheses of the sum are what a term is divided by
+    divisor: bool,
+}
+
+impl<'a> Sum<'a> {
+    fn end_term(&mut self) {
+        if self.term_has_duration {
+            self.has_duration = true;
+        } else if let Some(number) = self.term_number {
+            self.plain_number.get_or_insert(number);
+        }
+
+        self.term_has_duration = false;
+        self.term_number = None;
+        self.term_operator = None;
+    }
+
+    // A duration can only be scaled by a number, so a term has at most one, and it is not a divisor
+    fn add_duration(&mut self) -> anyhow::Result<()> {
+        if self.term_operator == Some('/') {
+            return Err(anyhow!("can't divide by a duration"));
+        }
+
+        if self.term_has_duration {
+            return Err(anyhow!("can't multiply a duration by a duration"));
+        }
+
+        self.term_has_duration = true;
+
+        Ok(())
+    }
+
+    // Whether there was a duration in the sum, and its first plain number otherwise
+    fn finish(mut self) -> anyhow::Result<(bool, Option<&'a str>)> {
+        self.end_term();
+
+        match (self.has_duration, self.plain_number) {
+            (true, Some(number)) => Err(anyhow!("number {number} without a time unit")),
+            (has_duration, number) => Ok((has_duration, number)),
+        }
+    }
+}
+
+// Turns the durations into a number of minutes, like `2h` into `(2*60)`
+// Without a unit a number can only scale a duration, so it has to be in the same term of a sum, otherwise the result would be meaningless
+// A duration can only be multiplied or divided by such a number, the product of two of them is no duration either
+pub fn replace_durations(expr: &str, duration_re: &Regex) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(expr.len());
+    let mut last_end = 0;
+    // Start of every number, with it and whether it has a unit
+    let mut numbers = Vec::new();
+
+    for captures in duration_re.captures_iter(expr) {
+        let (Some(number), unit) = (captures.get(2), captures.get(3)) else {
+            continue;
+        };
+
+        numbers.push((number.start(), number.as_str(), unit.is_some()));
+
+        let Some(unit) = unit else {
+            continue;
+        };
+
+        let (_, minutes) = TIME_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit.as_str())
+            .ok_or_else(|| anyhow!("unknown time unit '{}'", unit.as_str()))?;
+
+        result.push_str(&expr[last_end..number.start()]);
+        result.push_str(&format!("({}*{minutes})", number.as_str()));
+        last_end = unit.end();
+    }
+
+    if numbers.iter().any(|&(_, _, has_unit)| has_unit) {
+        check_plain_numbers(expr, &numbers)?;
+    }
+
+    result.push_str(&expr[last_end..]);
+
+    Ok(result)
+}
+
+// Fails for the first number of a term without a duration, in a sum which has a duration in another term
+// Parentheses without a duration in them count as a plain number in the term they are in, like in `2h+(3*4)`
+fn check_plain_numbers(expr: &str, numbers: &[(usize, &str, bool)]) -> anyhow::Result<()> {
+    let mut sums = vec![Sum::default()];
+    let mut numbers = numbers.iter().peekable();
+    let mut previous = None;
+
+    for (index, c) in expr.char_indices() {
+        // An unmatched closing parenthesis is reported later, it doesn't close the outermost sum
+        let nested = sums.len() > 1;
+        let sum = sums.last_mut().expect("the outermost sum is never closed");
+
+        if let Some((_, number, has_unit)) = numbers.next_if(|(start, _, _)| *start == index) {
+            if *has_unit {
+                sum.add_duration()?;
+            } else {
+                sum.term_number.get_or_insert(number);
+            }
+        }
+
+        // A sign right after an operator or an opening parenthesis is part of the number
+        let between_terms =
+            previous.is_some_and(|p: char| p == ')' || p == '.' || p == '_' || p.is_alphanumeric());
+
+        match c {
+            '(' => {
+                let divisor = sum.term_operator == Some('/');
+
+                sums.push(Sum {
+                    divisor,
+                    ..Sum::default()
+                });
+            }
+            ')' if nested => {
+                let inner = sums.pop().expect("checked to be nested");
+                let divisor = inner.divisor;
+                let (has_duration, number) = inner.finish()?;
+                let sum = sums.last_mut().expect("the outermost sum is never closed");
+
+                if has_duration {
+                    if divisor {
+                        return Err(anyhow!("can't divide by a duration"));
+                    }
+
+                    sum.add_duration()?;
+                }
+
+                if let Some(number) = number {
+                    sum.term_number.get_or_insert(number);
+                }
+            }
+            '+' | '-' if between_terms => sum.end_term(),
+            '*' | '/' => sum.term_operator = Some(c),
+            c if TERM_SEPARATORS.contains(&c) => sum.end_term(),
+            _ => {}
+        }
+
+        previous = Some(c);
+    }
+
+    while let Some(sum) = sums.pop() {
+        sum.finish()?;
+    }
+
+    Ok(())
+}
+
+// Multiplies the numbers with a magnitude suffix by its factor, like `2k` into `2000`
+// A decimal number is only known to be valid later, in float mode, so it is turned into a multiplication like `(1.5*1000)`
+pub fn replace_magnitudes(expr: &str, magnitude_re: &Regex) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(expr.len());
+    let mut last_end = 0;
+
+    for captures in magnitude_re.captures_iter(expr) {
+        let (Some(number), Some(suffix)) = (captures.get(2), captures.get(3)) else {
+            continue;
+        };
+
+        let (_, factor) = MAGNITUDE_SUFFIXES
+            .iter()
+            .find(|(name, _)| *name == suffix.as_str())
+            .ok_or_else(|| anyhow!("unknown magnitude suffix '{}'", suffix.as_str()))?;
+        let literal = &expr[number.start()..suffix.end()];
+        let replacement = match number.as_str().parse::<i64>() {
+            Ok(value) => value
+                .checked_mul(*factor)
+                .ok_or_else(|| anyhow!("literal '{literal}' is too large"))?
+                .to_string(),
+            Err(_) if number.as_str().contains('.') => format!("({}*{factor})", number.as_str()),
+            Err(_) => return Err(anyhow!("literal '{literal}' is too large")),
+        };
+
+        result.push_str(&expr[last_end..number.start()]);
+        result.push_str(&replacement);
+        last_end = suffix.end();
+    }
+
+    result.push_str(&expr[last_end..]);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_durations() {
+        let duration_re = Regex::new(DURATION_PATTERN).unwrap();
+        let replace = |expr| replace_durations(expr, &duration_re);
+
+        assert_eq!(replace("2h+30m").unwrap(), "(2*60)+(30*1)");
+        assert_eq!(replace("1w-1d").unwrap(), "(1*10080)-(1*1440)");
+        assert_eq!(replace("3*2h/4").unwrap(), "3*(2*60)/4");
+        assert_eq!(replace("1.5h").unwrap(), "(1.5*60)");
+        assert_eq!(replace("1+2*3").unwrap(), "1+2*3");
+        assert_eq!(replace("log2(8)").unwrap(), "log2(8)");
+
+        assert_eq!(
+            replace("2h+5").unwrap_err().to_string(),
+            "number 5 without a time unit"
+        );
+        assert_eq!(
+            replace("2h+3*4").unwrap_err().to_string(),
+            "number 3 without a time unit"
+        );
+        assert_eq!(
+            replace("(2h+3)*4").unwrap_err().to_string(),
+            "number 3 without a time unit"
+        );
+        assert!(replace("2h+(3*4)").is_err());
+        assert!(replace("2h>5").is_err());
+        assert_eq!(replace("(1+2)*2h-30m").unwrap(), "(1+2)*(2*60)-(30*1)");
+        assert_eq!(replace("2h*-3+1d/2").unwrap(), "(2*60)*-3+(1*1440)/2");
+        assert_eq!(
+            replace("2y").unwrap_err().to_string(),
+            "unknown time unit 'y'"
+        );
+    }
+
+    #[test]
+    fn only_scales_durations_by_numbers() {
+        let duration_re = Regex::new(DURATION_PATTERN).unwrap();
+        let error = |expr| {
+            replace_durations(expr, &duration_re)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(error("2h*30m"), "can't multiply a duration by a duration");
+        assert_eq!(error("2h/4*30m"), "can't multiply a duration by a duration");
+        assert_eq!(
+            error("30m*(2h-1h)"),
+            "can't multiply a duration by a duration"
+        );
+        assert_eq!(error("120/2h"), "can't divide by a duration");
+        assert_eq!(error("2h/30m"), "can't divide by a duration");
+        assert_eq!(error("2h/-30m"), "can't divide by a duration");
+        assert_eq!(error("1d/(2h+1h)"), "can't divide by a duration");
+
+        let replace = |expr| replace_durations(expr, &duration_re);
+
+        assert!(replace("6/2*1h").is_ok());
+        assert!(replace("(2h+30m)*2").is_ok());
+        assert!(replace("2h*3+30m/(1+1)").is_ok());
+        assert!(replace("max(2h, 30m)*2").is_ok());
+    }
+
+    #[test]
+    fn replaces_magnitudes() {
+        let magnitude_re = Regex::new(MAGNITUDE_PATTERN).unwrap();
+        let replace = |expr| replace_magnitudes(expr, &magnitude_re);
+
+        assert_eq!(replace("2k+3M").unwrap(), "2000+3000000");
+        assert_eq!(replace("1G-4Ki").unwrap(), "1000000000-4096");
+        assert_eq!(replace("2Mi/1Gi").unwrap(), "2097152/1073741824");
+        assert_eq!(replace("1.5k").unwrap(), "(1.5*1000)");
+        assert_eq!(replace("2kg+MAX+log2(8)").unwrap(), "2kg+MAX+log2(8)");
+
+        assert_eq!(
+            replace("10000000000G").unwrap_err().to_string(),
+            "literal '10000000000G' is too large"
+        );
+    }
+}
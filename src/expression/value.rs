@@ -0,0 +1,167 @@
+use std::{fmt, num::IntErrorKind, str::FromStr};
+
+use anyhow::anyhow;
+
+/// Result of an evaluation
+///
+/// Integer operands produce integers, and a float operand promotes the whole operation to a float, so once a float appears it propagates outward through every operation it takes part in.
+/// Rationals only appear in exact evaluation, where they are always reduced and have a denominator greater than one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Rational { numerator: i64, denominator: i64 },
+}
+
+// Separates the numerator from the denominator of a rational inside an expression, it can't appear in user input, so it never gets confused with anything else
+const RATIONAL_SEPARATOR: char = ':';
+
+impl Value {
+    // Reduces the fraction, the result is an integer whenever the denominator divides the numerator
+    pub fn rational(numerator: i128, denominator: i128) -> anyhow::Result<Value> {
+        if denominator == 0 {
+            return Err(anyhow!("division by zero"));
+        }
+
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        let to_i64 = |value: i128| i64::try_from(value).map_err(|_| anyhow!("integer overflow"));
+        let numerator = to_i64(numerator / divisor)?;
+        let denominator = to_i64(denominator / divisor)?;
+
+        if denominator == 1 {
+            Ok(Value::Int(numerator))
+        } else {
+            Ok(Value::Rational {
+                numerator,
+                denominator,
+            })
+        }
+    }
+
+    // Numerator and denominator of exact values, floats have none
+    pub fn as_ratio(self) -> Option<(i128, i128)> {
+        match self {
+            Value::Int(value) => Some((value.into(), 1)),
+            Value::Float(_) => None,
+            Value::Rational {
+                numerator,
+                denominator,
+            } => Some((numerator.into(), denominator.into())),
+        }
+    }
+
+    // Unlike the displayed form, this one is parsed back by the solver as a single operand
+    pub fn to_expression_text(self) -> String {
+        match self {
+            Value::Rational {
+                numerator,
+                denominator,
+            } => format!("{numerator}{RATIONAL_SEPARATOR}{denominator}"),
+            _ => self.to_string(),
+        }
+    }
+
+    // Long division of the exact value, truncated after the given number of decimal places
+    pub fn to_decimal(self, places: u32) -> anyhow::Result<String> {
+        let (numerator, denominator) = self
+            .as_ratio()
+            .ok_or_else(|| anyhow!("decimal expansion needs an exact result: {self}"))?;
+
+        let sign = if numerator < 0 { "-" } else { "" };
+        let mut remainder = numerator.abs() % denominator;
+        let mut decimal = format!("{sign}{}", numerator.abs() / denominator);
+
+        if places > 0 {
+            decimal.push('.');
+        }
+
+        for _ in 0..places {
+            remainder *= 10;
+            decimal.push(char::from(b'0' + (remainder / denominator) as u8));
+            remainder %= denominator;
+        }
+
+        Ok(decimal)
+    }
+
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Value::Int(value) => value as f64,
+            Value::Float(value) => value,
+            Value::Rational {
+                numerator,
+                denominator,
+            } => numerator as f64 / denominator as f64,
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        match self {
+            Value::Int(value) => value == 0,
+            Value::Float(value) => value == 0.0,
+            Value::Rational { .. } => false,
+        }
+    }
+
+    // Floats with an integral value are accepted too, because promotion can turn exact results like 6/2 into floats
+    pub fn to_i64(self) -> anyhow::Result<i64> {
+        match self {
+            Value::Int(value) => Ok(value),
+            Value::Float(value)
+                if value.fract() == 0.0 && (i64::MIN as f64..i64::MAX as f64).contains(&value) =>
+            {
+                Ok(value as i64)
+            }
+            Value::Float(_) | Value::Rational { .. } => {
+                Err(anyhow!("result is not an integer: {self}"))
+            }
+        }
+    }
+}
+
+impl FromStr for Value {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some((numerator, denominator)) = value.split_once(RATIONAL_SEPARATOR) {
+            Ok(Value::Rational {
+                numerator: numerator.parse()?,
+                denominator: denominator.parse()?,
+            })
+        } else if value.contains('.') {
+            Ok(Value::Float(value.parse()?))
+        } else {
+            match value.parse() {
+                Ok(value) => Ok(Value::Int(value)),
+                // Out of the range of the arithmetic, whether it was written like that or it is a result like the negated MIN
+                Err(error) if error.kind() == &IntErrorKind::PosOverflow => {
+                    Err(anyhow!("integer overflow"))
+                }
+                Err(error) => Err(error.into()),
+            }
+        }
+    }
+}
+
+// Floats always keep their decimal point and are never written in exponent notation, so that a formatted float is parsed back as a float by the solver
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{value}"),
+            Value::Float(value) if value.fract() == 0.0 => write!(f, "{value:.1}"),
+            Value::Float(value) => write!(f, "{value}"),
+            Value::Rational {
+                numerator,
+                denominator,
+            } => write!(f, "{numerator}/{denominator}"),
+        }
+    }
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    a.abs()
+}
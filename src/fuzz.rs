@@ -0,0 +1,186 @@
+// Random expression trees, rendered to text, and evaluated directly as a reference the evaluator has to agree with
+
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{random::Random, ExpressionEvaluator};
+
+const MAX_DEPTH: u32 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operator {
+    fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            Operator::Add | Operator::Subtract => 1,
+            Operator::Multiply | Operator::Divide => 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Expression {
+    Number(i64),
+    Negate(Box<Expression>),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+    Power(Box<Expression>, u32),
+}
+
+impl Expression {
+    pub fn generate(random: &mut Random) -> Expression {
+        Expression::generate_nested(random, MAX_DEPTH)
+    }
+
+    fn generate_nested(random: &mut Random, depth: u32) -> Expression {
+        if depth == 0 || random.below(4) == 0 {
+            return Expression::Number(random.below(20) as i64);
+        }
+
+        match random.below(10) {
+            0 => Expression::Negate(Box::new(Expression::generate_nested(random, depth - 1))),
+            1 => Expression::Power(
+                Box::new(Expression::generate_nested(random, depth - 1)),
+                random.below(4) as u32,
+            ),
+            choice => {
+                let operator = match choice % 4 {
+                    0 => Operator::Add,
+                    1 => Operator::Subtract,
+                    2 => Operator::Multiply,
+                    _ => Operator::Divide,
+                };
+
+                Expression::Binary(
+                    Box::new(Expression::generate_nested(random, depth - 1)),
+                    operator,
+                    Box::new(Expression::generate_nested(random, depth - 1)),
+                )
+            }
+        }
+    }
+
+    /// Checked `i64` arithmetic, `None` stands for a division by zero or an overflow
+    pub fn evaluate(&self) -> Option<i64> {
+        match self {
+            Expression::Number(value) => Some(*value),
+            Expression::Negate(operand) => operand.evaluate()?.checked_neg(),
+            Expression::Power(base, exponent) => base.evaluate()?.checked_pow(*exponent),
+            Expression::Binary(first, operator, second) => {
+                let first = first.evaluate()?;
+                let second = second.evaluate()?;
+
+                match operator {
+                    Operator::Add => first.checked_add(second),
+                    Operator::Subtract => first.checked_sub(second),
+                    Operator::Multiply => first.checked_mul(second),
+                    Operator::Divide => first.checked_div(second),
+                }
+            }
+        }
+    }
+
+    /// Only the parentheses the precedence rules need are written out
+    pub fn render(&self) -> String {
+        match self {
+            Expression::Number(value) => value.to_string(),
+            Expression::Negate(operand) => match operand.as_ref() {
+                Expression::Number(_) => format!("-{}", operand.render()),
+                _ => format!("-({})", operand.render()),
+            },
+            Expression::Power(base, exponent) => match base.as_ref() {
+                Expression::Number(_) => format!("{}^{exponent}", base.render()),
+                _ => format!("({})^{exponent}", base.render()),
+            },
+            Expression::Binary(first, operator, second) => {
+                let first = match first.as_ref() {
+                    Expression::Binary(_, inner, _)
+                        if inner.precedence() < operator.precedence() =>
+                    {
+                        format!("({})", first.render())
+                    }
+                    _ => first.render(),
+                };
+
+                // A right operand of the same precedence needs parentheses too, because the operations are evaluated left to right
+                let second = match second.as_ref() {
+                    Expression::Binary(_, inner, _)
+                        if inner.precedence() <= operator.precedence() =>
+                    {
+                        format!("({})", second.render())
+                    }
+                    _ => second.render(),
+                };
+
+                format!("{first}{}{second}", operator.symbol())
+            }
+        }
+    }
+}
+
+/// Expression the evaluator disagreed with the reference on, `None` stands for an error
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    pub expr: String,
+    pub expected: Option<i64>,
+    /// `Err` if the evaluator panicked
+    pub actual: Result<Option<i64>, ()>,
+}
+
+// Evaluates that many random expressions, the same seed always gives the same ones
+pub fn run_batch(evaluator: &ExpressionEvaluator, cases: usize, seed: u64) -> Vec<Mismatch> {
+    let mut random = Random::new(seed);
+    let mut mismatches = Vec::new();
+
+    for _ in 0..cases {
+        let tree = Expression::generate(&mut random);
+        let expr = tree.render();
+        let expected = tree.evaluate();
+        let actual = panic::catch_unwind(AssertUnwindSafe(|| evaluator.eval(expr.clone()).ok()))
+            .map_err(|_| ());
+
+        if actual != Ok(expected) {
+            mismatches.push(Mismatch {
+                expr,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_no_mismatches() {
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        assert_eq!(run_batch(&evaluator, 50, 42), Vec::new());
+    }
+
+    #[test]
+    fn generates_expressions_from_a_seed() {
+        let render = |seed| Expression::generate(&mut Random::new(seed)).render();
+
+        assert_eq!(render(7), render(7));
+        assert_ne!(render(0), render(1));
+        assert_ne!(render(1), render(2));
+    }
+}
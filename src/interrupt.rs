@@ -0,0 +1,29 @@
+// Ctrl-C at a prompt drops the line being typed and prompts again, instead of ending the calculator
+// The terminal already discards the characters typed so far, so the handler only has to start a new prompt
+#[cfg(unix)]
+pub(crate) fn reprompt_on_interrupt() {
+    use std::ffi::c_int;
+
+    const SIGINT: c_int = 2;
+    const STDOUT: c_int = 1;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+        fn write(fd: c_int, buf: *const u8, count: usize) -> isize;
+    }
+
+    // Nothing but write is safe to call in a signal handler, the prompt of the standard output can't be used
+    extern "C" fn reprompt(_: c_int) {
+        for text in ["\n", crate::PROMPT] {
+            // SAFETY: the buffer is valid for its length, and a failed write only loses the prompt
+            unsafe { write(STDOUT, text.as_ptr(), text.len()) };
+        }
+    }
+
+    // SAFETY: the handler only calls write, and a read of the standard input is restarted after it
+    unsafe { signal(SIGINT, reprompt) };
+}
+
+// There is no signal to catch, Ctrl-C still ends the calculator
+#[cfg(not(unix))]
+pub(crate) fn reprompt_on_interrupt() {}
@@ -1,36 +1,1550 @@
-pub struct Config;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use anyhow::anyhow;
+
+/// Where the results are written, errors go to the standard error unless `Config::errors_to_stdout` is set
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum OutputSink {
+    #[default]
+    Stdout,
+    /// Created, or truncated if it exists, once the input could be opened
+    File(PathBuf),
+}
+
+/// Whether results and errors are written in color
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorChoice {
+    /// Only if the results go to a terminal, and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Options of the calculator, `Config::default()` turns every one of them off
+///
+/// ```
+/// use basic_arithmetic_calculator::{Config, ExpressionEvaluator};
+///
+/// let config = Config {
+///     float_mode: true,
+///     ..Config::default()
+/// };
+/// let evaluator = ExpressionEvaluator::with_config(config).unwrap();
+///
+/// assert_eq!(evaluator.eval_to_string("1.5*3"), "4.5");
+/// ```
+#[derive(Clone, Default)]
+pub struct Config {
+    /// Wrap every intermediate result to a two's complement integer of this many bits
+    pub wrap_bits: Option<u32>,
+    /// Reject subexpressions summing more than this many terms
+    pub max_terms: Option<usize>,
+    /// Reject expressions longer than this many bytes before looking at them
+    pub max_length: Option<usize>,
+    /// Make every division produce a float, while other operations on integers stay integers
+    pub divide_promotes_to_float: bool,
+    /// Print a warning when an integer division discarded a nonzero remainder
+    pub warn_on_truncation: bool,
+    /// Reject an integer division with a nonzero remainder, like `7/2`, instead of truncating it
+    pub strict_division: bool,
+    /// Print a warning when a result is less than this, it is printed anyway
+    pub warn_below: Option<i64>,
+    /// Print a warning when a result is greater than this, it is printed anyway
+    pub warn_above: Option<i64>,
+    /// Remember the results of this many of the most recently evaluated expressions without variables, so evaluating one again is only a lookup
+    ///
+    /// Expressions differing only in whitespace share a result, errors and expressions calling `rand` are never cached.
+    pub cache_size: Option<usize>,
+    /// Accept decimal literals and the float-only builtins like `ln`
+    pub float_mode: bool,
+    /// Separator of the fractional part of decimal literals and results, a point if not set
+    ///
+    /// With a comma, like in `1.000,50`, points group digits and semicolons separate function arguments.
+    /// Points have to group exactly three digits after a leading group of one to three, so `12.34` is an error.
+    /// A decimal comma needs float mode, and can't be combined with the comma as `digit_separator`.
+    pub decimal_separator: Option<char>,
+    /// Accept literals with their base after the digits, `b` for binary, `o` for octal and `h` for hexadecimal, like `1010b` or `0FFh`
+    ///
+    /// They start with a decimal digit, so they can't be taken for names, and they can't be combined with `time_units`.
+    pub radix_suffixes: bool,
+    /// Accept numbers with a magnitude suffix, `k`, `M` and `G` for powers of 1000 and `Ki`, `Mi` and `Gi` for powers of 1024, like `2k` or `4Ki`
+    pub magnitude_suffixes: bool,
+    /// Accept durations like `2h` or `30m`, which are evaluated as a number of minutes
+    ///
+    /// A duration can be added to another one, or multiplied or divided by a number, like `2h*3`, but not by a duration.
+    pub time_units: bool,
+    /// Reject binary operators without whitespace on both sides, like `2+3` instead of `2 + 3`
+    pub require_operator_spaces: bool,
+    /// Reject operations grouped by precedence or associativity instead of parentheses, so `1+2*3` has to be written as `1+(2*3)`
+    pub require_full_parens: bool,
+    /// Let a leading `--` cancel out like in C, instead of rejecting it
+    pub allow_double_negation: bool,
+    /// Group the digits of printed results by threes
+    pub group_output_digits: bool,
+    /// Separator between the digit groups of printed results, a comma if not set, or a point with a decimal comma
+    pub digit_separator: Option<char>,
+    /// Print integers from a million on in scientific notation with this many significant digits, like `1e6`
+    ///
+    /// A result which had to be rounded is marked as approximate, like `~1.23e6`.
+    pub scientific_digits: Option<u32>,
+    /// Print results from 1 to 3999 as Roman numerals, like `XIV`, other ones are printed as usual with a note
+    pub roman_output: bool,
+    /// Print results in decimal even if every literal of the expression was hexadecimal or binary
+    pub decimal_output: bool,
+    /// Follow every printed result with the number of operations it took
+    pub show_operation_count: bool,
+    /// Print every step of an evaluation to the standard error before its result
+    pub trace: bool,
+    /// Print the parse tree of every expression before its result
+    pub tree: bool,
+    /// Print nothing for lines assigning a variable, like `x = 5`
+    pub silent_assignments: bool,
+    /// Print every result after the expression it belongs to, like `2+3*4 = 14`
+    pub echo_input: bool,
+    /// Record every evaluated line with its result, like `2+3 = 5`, for the `tape` command to list
+    pub tape: bool,
+    /// Write the recorded lines to this file at the end of the session, they are recorded even without `tape` then
+    pub tape_file: Option<PathBuf>,
+    /// Only report the syntax errors of the given expressions and of every line, without evaluating anything
+    pub check: bool,
+    /// Evaluate everything without printing anything, only the outcome tells whether every expression had a value
+    ///
+    /// The program exits with 1 then if any of them failed, without a message.
+    pub check_only: bool,
+    /// Evaluate these before reading the input, which is only read if there is a file then
+    ///
+    /// They are given with `-e`, a single one also without it, or in the `CALC_EXPR` environment variable if nothing else is given.
+    pub expressions: Vec<String>,
+    /// Read the expressions from this file instead of the standard input, a `#` starts a comment on any of its lines
+    pub file: Option<PathBuf>,
+    /// Results in green and errors in red, diagnostics are highlighted in red too
+    pub color: ColorChoice,
+    /// Write the results here, so a file only gets results, while the errors are still shown
+    pub output: OutputSink,
+    /// Write the errors of expressions along with the results, so a transcript has them in order
+    pub errors_to_stdout: bool,
+    /// Evaluate with integers of any size, only available with the `bigint` feature
+    pub bigint: bool,
+    /// Instead of reading expressions, check the evaluator against this many random ones
+    pub fuzz: Option<usize>,
+    /// Seed of the random expressions of a fuzz test and of the numbers of `rand`, which is seeded from entropy if not set
+    pub seed: Option<u64>,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: Option<String>) -> anyhow::Result<T> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| anyhow!("missing or invalid number after {flag}"))
+}
+
+impl Config {
+    // Parses the command line arguments, the first one is the name of the program
+    pub fn build(mut args: impl Iterator<Item = String>) -> anyhow::Result<Config> {
+        let mut config = Config::default();
+        let mut mode = None;
+        let mut positional = false;
+
+        args.next();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--check" => config.check = true,
+                "--check-only" => config.check_only = true,
+                "--strict" => config.strict_division = true,
+                "--trace" => config.trace = true,
+                "--tree" => config.tree = true,
+                "--roman" => config.roman_output = true,
+                "--errors-to-stdout" => config.errors_to_stdout = true,
+                "--tape" => config.tape = true,
+                "--tape-file" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing path after --tape-file"))?;
+
+                    config.tape_file = Some(PathBuf::from(path));
+                }
+                "--integer" | "--float" => {
+                    if mode.as_ref().is_some_and(|mode| *mode != arg) {
+                        return Err(anyhow!("--integer and --float can't be used together"));
+                    }
+
+                    config.set_float_arithmetic(arg == "--float");
+                    mode = Some(arg);
+                }
+                "--bigint" if cfg!(feature = "bigint") => config.bigint = true,
+                "--bigint" => return Err(anyhow!("--bigint needs the bigint feature")),
+                "--file" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing path after --file"))?;
+
+                    config.file = Some(PathBuf::from(path));
+                }
+                "-e" => {
+                    let expr = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing expression after -e"))?;
+
+                    config.expressions.push(expr);
+                }
+                "--fuzz" => config.fuzz = Some(parse_number(&arg, args.next())?),
+                "--min-result" => config.warn_below = Some(parse_number(&arg, args.next())?),
+                "--max-result" => config.warn_above = Some(parse_number(&arg, args.next())?),
+                "--sci" => config.scientific_digits = Some(parse_number(&arg, args.next())?),
+                "--seed" => config.seed = Some(parse_number(&arg, args.next())?),
+                "--color" => {
+                    config.color = match args.next().as_deref() {
+                        Some("auto") => ColorChoice::Auto,
+                        Some("always") => ColorChoice::Always,
+                        Some("never") => ColorChoice::Never,
+                        _ => return Err(anyhow!("expected auto, always or never after --color")),
+                    };
+                }
+                "--output" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing path after --output"))?;
+
+                    config.output = OutputSink::File(PathBuf::from(path));
+                }
+                // Reading the standard input is the default anyway, the marker just makes it explicit in a pipeline
+                "-" => config.file = None,
+                // A single expression can be given without -e, like `calculator "1+1"`, as long as it doesn't look like an option
+                _ if !arg.starts_with('-')
+                    || !arg[1..].starts_with(|c: char| c.is_alphabetic() || c == '-') =>
+                {
+                    if positional {
+                        return Err(anyhow!("only one expression can be given without -e"));
+                    }
+
+                    config.expressions.push(arg);
+                    positional = true;
+                }
+                _ => return Err(anyhow!("unknown argument '{arg}'")),
+            }
+        }
+
+        Ok(config)
+    }
+
+    // The expression of the environment is only a fallback, it is ignored if anything else was given to evaluate
+    pub fn with_env_expression(mut self, value: Option<String>) -> Config {
+        if let Some(value) = value.filter(|_| self.expressions.is_empty() && self.file.is_none()) {
+            self.expressions.push(value);
+        }
+
+        self
+    }
+
+    // Float arithmetic accepts decimal literals, and a division of integers gives a float instead of truncating
+    pub fn set_float_arithmetic(&mut self, enabled: bool) {
+        self.float_mode = enabled;
+        self.divide_promotes_to_float = enabled;
+    }
+}
+
+// Nothing of a record is even evaluated without the logging feature
+macro_rules! debug {
+    ($($argument:tt)*) => {
+        #[cfg(feature = "logging")]
+        $crate::logging::debug(format_args!($($argument)*));
+    };
+}
 
 mod expression;
+pub mod fuzz;
+mod interrupt;
+#[cfg(feature = "logging")]
+pub mod logging;
+pub mod random;
+mod session;
+
+const PROMPT: &str = "> ";
+// Environment variable with an expression to evaluate once, for running in a container without arguments
+const EXPRESSION_VARIABLE: &str = "CALC_EXPR";
+// Ends a line of a file which continues on the next one
+const LINE_CONTINUATION: char = '\\';
+// Separates the expected value from the expression in a file, like in `2+3 => 5`
+const EXPECTATION_SEPARATOR: &str = "=>";
+// Starts a comment in a file, which goes on until the end of the line
+const COMMENT_PREFIX: char = '#';
+
+use expression::{format_error, format_evaluation, paint, Color};
+
+#[cfg(feature = "bigint")]
+pub use expression::BigInt;
+pub use expression::{
+    operators, patterns, render_diagnostic, tokenize_lossy, CalcNumber, CompiledExpression,
+    Diagnostic, EvalRecord, EvalScratch, Evaluation, ExpressionEvaluator,
+    ExpressionEvaluatorBuilder, FunctionResolver, Functions, IncrementalEvaluator, OpStats, Radix,
+    Sign, Token, TokenKind, UserFunction, Value, Variables,
+};
+pub use session::{Command, Session};
+
+pub fn run(config: Config) -> anyhow::Result<()> {
+    let config = config.with_env_expression(std::env::var(EXPRESSION_VARIABLE).ok());
+
+    if let Some(cases) = config.fuzz {
+        return run_fuzz(config, cases, std::io::stdout());
+    }
+
+    let input = open_input(&config)?;
+    let output = open_output(&config)?;
+    // Only someone typing at a terminal needs a prompt, it would just get mixed into the results of a pipeline
+    let prompt = config.file.is_none()
+        && config.expressions.is_empty()
+        && config.output == OutputSink::Stdout
+        && std::io::stdin().is_terminal();
+
+    // NO_COLOR is a convention for turning colors off whatever its value is, see https://no-color.org
+    let color = match config.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            config.output == OutputSink::Stdout
+                && std::io::stdout().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none()
+        }
+    };
+
+    if prompt {
+        interrupt::reprompt_on_interrupt();
+    }
+
+    run_session(config, input, output, std::io::stderr(), prompt, color)
+}
+
+// Lists every expression the evaluator got wrong, and fails if there was any
+fn run_fuzz(config: Config, cases: usize, mut output: impl Write) -> anyhow::Result<()> {
+    let evaluator = ExpressionEvaluator::with_config(config.clone())?;
+    let seed = config.seed.unwrap_or_default();
+    let mismatches = fuzz::run_batch(&evaluator, cases, seed);
+    let show = |value: Option<i64>| value.map_or("error".to_string(), |value| value.to_string());
+
+    for mismatch in &mismatches {
+        let actual = match mismatch.actual {
+            Ok(value) => show(value),
+            Err(()) => "panic".to_string(),
+        };
 
-use expression::ExpressionEvaluator;
+        writeln!(
+            output,
+            "{}: expected {}, got {actual}",
+            mismatch.expr,
+            show(mismatch.expected)
+        )?;
+    }
+
+    writeln!(
+        output,
+        "{cases} expressions with seed {seed}, {} mismatch(es)",
+        mismatches.len()
+    )?;
+
+    if !mismatches.is_empty() {
+        return Err(anyhow!("the evaluator disagreed with the reference"));
+    }
+
+    Ok(())
+}
+
+fn open_input(config: &Config) -> anyhow::Result<Box<dyn BufRead>> {
+    match &config.file {
+        Some(path) => {
+            let file = File::open(path)
+                .map_err(|error| anyhow!("can't open {}: {error}", path.display()))?;
+
+            Ok(Box::new(BufReader::new(file)))
+        }
+        // The expressions of the command line are the whole input then, so a script doesn't wait for the standard input to end
+        None if !config.expressions.is_empty() => Ok(Box::new(std::io::empty())),
+        None => Ok(Box::new(std::io::stdin().lock())),
+    }
+}
+
+fn open_output(config: &Config) -> anyhow::Result<Box<dyn Write>> {
+    match &config.output {
+        OutputSink::File(path) => {
+            let file = File::create(path)
+                .map_err(|error| anyhow!("can't create {}: {error}", path.display()))?;
 
-pub fn run(_config: Config) -> anyhow::Result<()> {
-    let evaluator = ExpressionEvaluator::new()?;
+            Ok(Box::new(BufWriter::new(file)))
+        }
+        OutputSink::Stdout => Ok(Box::new(std::io::stdout())),
+    }
+}
+
+// Same as run, but reads expressions from and writes results to the given streams instead of the standard ones, until the input ends
+pub fn run_with(
+    config: Config,
+    input: impl BufRead,
+    output: impl Write,
+    error: impl Write,
+) -> anyhow::Result<()> {
+    run_session(config, input, output, error, false, false)
+}
+
+fn run_session(
+    config: Config,
+    mut input: impl BufRead,
+    mut output: impl Write,
+    mut error: impl Write,
+    prompt: bool,
+    color: bool,
+) -> anyhow::Result<()> {
+    if config.check {
+        return check_lines(config, input, error);
+    }
+
+    if config.check_only {
+        return evaluate_silently(config, input);
+    }
+
+    #[cfg(feature = "bigint")]
+    if config.bigint {
+        return bigint_lines(config, input, output, error, prompt);
+    }
+
+    let mut session = Session::new(config.clone())?;
+    let mut unmet_expectations = 0;
+    let mut failed_expressions = 0;
+
+    // Evaluated in the same session as the input, so a file can use the variables they assign
+    for expr in &config.expressions {
+        if config.tree {
+            print_tree(&session, expr, &mut output)?;
+        }
+
+        let result = session.eval_line(expr);
+
+        failed_expressions += usize::from(result.is_err());
+        print_trace(&session, &mut error)?;
+        print_expression_result(&result, &config, &mut output, &mut error, color)?;
+    }
 
     loop {
-        let input = get_stdin()?;
+        if prompt {
+            write!(output, "{PROMPT}")?;
+            output.flush()?;
+        }
+
+        let Some(mut line) = get_line(&mut input, &mut error)? else {
+            break;
+        };
+
+        if config.file.is_some() {
+            line = join_continued_lines(line, &mut input, &mut error)?;
+
+            // A comment can describe the cases of a file, a line of nothing else is skipped like a command
+            if line.trim_start().starts_with(COMMENT_PREFIX) {
+                continue;
+            }
+
+            if let Some(start) = line.find(COMMENT_PREFIX) {
+                line.truncate(start);
+            }
+        }
+
+        if let Some(command) = session.command_of(&line) {
+            // Interrupting only drops the line at a prompt, so this is the way to leave a session at a terminal besides the end of the input
+            if command == Command::Exit {
+                break;
+            }
+
+            run_command(&mut session, command, &mut output, &mut error)?;
+            continue;
+        }
+
+        // Only the lines of a file can be annotated, so a file of expressions can be run as a test suite
+        let (line, expected) = match line.split_once(EXPECTATION_SEPARATOR) {
+            Some((expr, expected)) if config.file.is_some() => (expr.to_string(), Some(expected)),
+            _ => (line.clone(), None),
+        };
 
-        let result = evaluator.eval(input);
+        if config.tree {
+            print_tree(&session, &line, &mut output)?;
+        }
 
-        print_expression_result(&result);
+        let result = session.eval_line(&line);
+
+        if let Some(expected) = expected {
+            let met = match &result {
+                Ok(evaluation) => session.is_expected(evaluation.value, expected),
+                Err(_) => Ok(false),
+            };
+
+            if !met.is_ok_and(|met| met) {
+                writeln!(
+                    error,
+                    "expectation not met: {} => {}",
+                    line.trim(),
+                    expected.trim()
+                )?;
+                unmet_expectations += 1;
+            }
+        }
+
+        if config.silent_assignments
+            && result.is_ok()
+            && Session::assigned_variable(&line).is_some()
+        {
+            continue;
+        }
+
+        if config.echo_input && result.is_ok() {
+            let expr = session.previous_expression().unwrap_or_default().trim();
+
+            write!(output, "{expr} = ")?;
+        }
+
+        // Someone at a terminal gets syntax errors pointed out in the expression they typed
+        let diagnostics = match (&result, session.previous_expression()) {
+            (Err(_), Some(expr)) if prompt => session.diagnostics(expr),
+            _ => Vec::new(),
+        };
+
+        print_trace(&session, &mut error)?;
+
+        if diagnostics.is_empty() {
+            print_expression_result(&result, &config, &mut output, &mut error, color)?;
+        }
+
+        for diagnostic in &diagnostics {
+            let expr = session.previous_expression().unwrap_or_default();
+
+            writeln!(error, "{}", render_diagnostic(expr, diagnostic, color))?;
+        }
+
+        if config.warn_on_truncation && result.is_ok_and(|evaluation| evaluation.truncated) {
+            writeln!(error, "warning: division truncated a remainder")?;
+        }
     }
+
+    // A buffered file only reports a failed write when it is flushed, dropping it would lose the error
+    output.flush()?;
+
+    if let Some(path) = &config.tape_file {
+        write_tape(&session, path)?;
+    }
+
+    if unmet_expectations > 0 {
+        return Err(anyhow!("{unmet_expectations} expectation(s) not met"));
+    }
+
+    if failed_expressions > 0 {
+        return Err(anyhow!("{failed_expressions} given expression(s) failed"));
+    }
+
+    Ok(())
+}
+
+// One line for every entry, the file is replaced if it exists
+fn write_tape(session: &Session, path: &std::path::Path) -> anyhow::Result<()> {
+    let tape: String = session
+        .tape()?
+        .iter()
+        .map(|entry| format!("{entry}\n"))
+        .collect();
+
+    std::fs::write(path, tape).map_err(|error| anyhow!("can't write {}: {error}", path.display()))
 }
 
-fn get_stdin() -> anyhow::Result<String> {
-    let mut input = String::new();
+fn run_command(
+    session: &mut Session,
+    command: Command,
+    output: &mut impl Write,
+    error: &mut impl Write,
+) -> std::io::Result<()> {
+    let result = match command {
+        Command::Undo => session
+            .undo()
+            .map(|name| writeln!(output, "undid assignment to {name}")),
+        Command::ListVariables => Ok(session
+            .variables()
+            .try_for_each(|(name, value)| writeln!(output, "{name} = {value}"))),
+        Command::Delete(name) => session.delete(name).map(Ok),
+        Command::Mode(mode) => session
+            .set_mode(mode)
+            .map(|_| writeln!(output, "mode {mode}")),
+        Command::EvalOverRange(line) => session.eval_over_range(line).map(|values| {
+            let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+
+            writeln!(output, "[{}]", values.join(", "))
+        }),
+        Command::Define(definition) => session
+            .define(definition)
+            .map(|name| writeln!(output, "defined {name}")),
+        Command::Reset => {
+            session.reset();
+            Ok(writeln!(output, "session reset"))
+        }
+        Command::Tape => session.tape().map(|tape| {
+            tape.iter()
+                .try_for_each(|entry| writeln!(output, "{entry}"))
+        }),
+        // The session loop stops before running it
+        Command::Exit => Ok(Ok(())),
+    };
+
+    match result {
+        Ok(written) => written,
+        Err(message) => writeln!(error, "{}", format_error(&message)),
+    }
+}
+
+// Reports the syntax errors of every line with its line number starting from 1, and fails if there was any
+// The given expressions are checked first, numbered the same way, as they would be evaluated before the input
+fn check_lines(config: Config, input: impl BufRead, mut error: impl Write) -> anyhow::Result<()> {
+    let expressions = config.expressions.clone();
+    let evaluator = ExpressionEvaluator::with_config(config)?;
+    let mut invalid_expressions = 0;
+    let mut invalid_lines = 0;
+
+    for (index, expr) in expressions.iter().enumerate() {
+        let diagnostics = evaluator.diagnostics(expr);
+
+        for diagnostic in &diagnostics {
+            writeln!(error, "expression {}: {}", index + 1, diagnostic.message)?;
+        }
+
+        invalid_expressions += usize::from(!diagnostics.is_empty());
+    }
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let diagnostics = evaluator.diagnostics(&line);
+
+        for diagnostic in &diagnostics {
+            writeln!(error, "line {}: {}", index + 1, diagnostic.message)?;
+        }
+
+        invalid_lines += usize::from(!diagnostics.is_empty());
+    }
+
+    if invalid_lines > 0 {
+        return Err(anyhow!("{invalid_lines} line(s) with syntax errors"));
+    }
+
+    if invalid_expressions > 0 {
+        return Err(anyhow!(
+            "{invalid_expressions} given expression(s) with syntax errors"
+        ));
+    }
+
+    Ok(())
+}
+
+// Same session as usual, just without any output, commands still run so the expressions after them see their effect
+fn evaluate_silently(config: Config, input: impl BufRead) -> anyhow::Result<()> {
+    let mut session = Session::new(config.clone())?;
+    let mut failed_expressions = 0;
+
+    for expr in &config.expressions {
+        failed_expressions += usize::from(session.eval_line(expr).is_err());
+    }
+
+    for line in input.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(command) = session.command_of(&line) {
+            if command == Command::Exit {
+                break;
+            }
+
+            run_command(
+                &mut session,
+                command,
+                &mut std::io::sink(),
+                &mut std::io::sink(),
+            )?;
+            continue;
+        }
+
+        failed_expressions += usize::from(session.eval_line(&line).is_err());
+    }
+
+    if failed_expressions > 0 {
+        return Err(anyhow!("{failed_expressions} expression(s) failed"));
+    }
+
+    Ok(())
+}
+
+// Every line is a separate expression, there are no sessions of big integers
+#[cfg(feature = "bigint")]
+fn bigint_lines(
+    config: Config,
+    mut input: impl BufRead,
+    mut output: impl Write,
+    mut error: impl Write,
+    prompt: bool,
+) -> anyhow::Result<()> {
+    let evaluator = ExpressionEvaluator::with_config(config)?;
+
+    loop {
+        if prompt {
+            write!(output, "{PROMPT}")?;
+            output.flush()?;
+        }
+
+        let Some(line) = get_line(&mut input, &mut error)? else {
+            break;
+        };
+
+        match evaluator.eval_bigint(&line) {
+            Ok(value) => writeln!(output, "{value}")?,
+            Err(message) => writeln!(error, "{}", format_error(&message))?,
+        }
+    }
+
+    output.flush()?;
+
+    Ok(())
+}
+
+// A line which is not valid UTF-8 is skipped with a warning, so noise in the input doesn't end the session
+fn get_line(input: &mut impl BufRead, error: &mut impl Write) -> anyhow::Result<Option<String>> {
+    let mut line = Vec::new();
+
+    loop {
+        if input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+
+        match String::from_utf8(std::mem::take(&mut line)) {
+            Ok(line) => return Ok(Some(line)),
+            Err(_) => writeln!(error, "warning: skipped a line which is not valid UTF-8")?,
+        }
+    }
+}
+
+// Long formulas can be split like in a shell script, a backslash anywhere else is still an invalid character
+fn join_continued_lines(
+    mut line: String,
+    input: &mut impl BufRead,
+    error: &mut impl Write,
+) -> anyhow::Result<String> {
+    while let Some(length) = line
+        .trim_end()
+        .strip_suffix(LINE_CONTINUATION)
+        .map(str::len)
+    {
+        line.truncate(length);
+
+        match get_line(input, error)? {
+            Some(next) => line.push_str(&next),
+            None => break,
+        }
+    }
+
+    Ok(line)
+}
 
-    std::io::stdin().read_line(&mut input)?;
+// Nothing is printed for an expression without a tree, the evaluation reports what is wrong with it
+fn print_tree(session: &Session, line: &str, output: &mut impl Write) -> std::io::Result<()> {
+    match session.tree(line) {
+        Ok(tree) => writeln!(output, "{tree}"),
+        Err(_) => Ok(()),
+    }
+}
 
-    Ok(input)
+fn print_trace(session: &Session, error: &mut impl Write) -> std::io::Result<()> {
+    session
+        .steps()
+        .iter()
+        .try_for_each(|step| writeln!(error, "trace: {step}"))
 }
 
-fn print_expression_result(result: &anyhow::Result<i64>) {
+fn print_expression_result(
+    result: &anyhow::Result<Evaluation>,
+    config: &Config,
+    output: &mut impl Write,
+    error: &mut impl Write,
+    color: bool,
+) -> std::io::Result<()> {
     match result {
-        Ok(solution) => {
-            println!("{solution}");
+        Ok(evaluation) => {
+            writeln!(
+                output,
+                "{}",
+                paint(&format_evaluation(evaluation, config), Color::Green, color)
+            )?;
+            warn_outside_range(evaluation.value, config, error)
         }
-        Err(error) => {
-            eprintln!("Error: {error}");
+        Err(message) => {
+            let message = paint(&format_error(message), Color::Red, color);
+
+            if config.errors_to_stdout {
+                writeln!(output, "{message}")
+            } else {
+                writeln!(error, "{message}")
+            }
         }
     }
 }
+
+// Floats are compared by value too, so a fraction just above the bound is outside as well
+fn warn_outside_range(
+    value: Value,
+    config: &Config,
+    error: &mut impl Write,
+) -> std::io::Result<()> {
+    let number = value.as_f64();
+
+    if let Some(bound) = config.warn_below.filter(|bound| number < *bound as f64) {
+        writeln!(error, "warning: result {value} is below {bound}")?;
+    }
+
+    if let Some(bound) = config.warn_above.filter(|bound| number > *bound as f64) {
+        writeln!(error, "warning: result {value} is above {bound}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_lines(config: Config, input: &str) -> (String, String) {
+        let mut output = Vec::new();
+        let mut error = Vec::new();
+
+        run_with(config, input.as_bytes(), &mut output, &mut error).unwrap();
+
+        (
+            String::from_utf8(output).unwrap(),
+            String::from_utf8(error).unwrap(),
+        )
+    }
+
+    #[test]
+    fn prints_results_and_errors() {
+        let (output, error) = run_lines(Config::default(), "1+1\n1/0\n3*3\n");
+
+        assert_eq!(output, "2\n9\n");
+        assert_eq!(error, "Error: division by zero\n");
+    }
+
+    #[test]
+    fn checks_expectations_only_in_files() {
+        let mut config = Config {
+            file: Some(PathBuf::from("expressions.txt")),
+            ..Config::default()
+        };
+        config.set_float_arithmetic(true);
+
+        let (output, error) = run_lines(config, "2+3 => 5\nx = 0.1+0.2 => 3/10\n");
+
+        assert_eq!(output, "5\n0.30000000000000004\n");
+        assert_eq!(error, "");
+
+        let (output, error) = run_lines(Config::default(), "2+3 => 5\n");
+
+        assert_eq!(output, "");
+        assert!(error.starts_with("Error: "));
+    }
+
+    #[test]
+    fn evaluates_expressions_before_input() {
+        let config = Config {
+            expressions: vec!["x = 2".to_string(), "1/0".to_string(), "x*3".to_string()],
+            ..Config::default()
+        };
+        let mut output = Vec::new();
+        let mut error = Vec::new();
+        let result = run_with(config, "x+1\n".as_bytes(), &mut output, &mut error);
+
+        assert_eq!(String::from_utf8(output).unwrap(), "2\n6\n3\n");
+        assert_eq!(
+            String::from_utf8(error).unwrap(),
+            "Error: division by zero\n"
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "1 given expression(s) failed"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_expression_of_environment() {
+        let expressions = |config: Config| {
+            config
+                .with_env_expression(Some("6*7".to_string()))
+                .expressions
+        };
+
+        assert_eq!(expressions(Config::default()), ["6*7"]);
+        assert_eq!(
+            expressions(Config {
+                expressions: vec!["1+1".to_string()],
+                ..Config::default()
+            }),
+            ["1+1"]
+        );
+        assert!(expressions(Config {
+            file: Some(PathBuf::from("cases.txt")),
+            ..Config::default()
+        })
+        .is_empty());
+        assert!(Config::default()
+            .with_env_expression(None)
+            .expressions
+            .is_empty());
+
+        let config = Config::default().with_env_expression(Some("6*7".to_string()));
+        let (output, error) = run_lines(config, "");
+
+        assert_eq!(output, "42\n");
+        assert_eq!(error, "");
+    }
+
+    #[test]
+    fn exits_on_command() {
+        let (output, error) = run_lines(Config::default(), "1+1\nexit\n2+2\n");
+
+        assert_eq!(output, "2\n");
+        assert_eq!(error, "");
+
+        let (output, _) = run_lines(Config::default(), "exit = 3\nexit*2\nexit\n1+1\n");
+
+        assert_eq!(output, "3\n6\n3\n2\n");
+    }
+
+    #[test]
+    fn joins_continued_lines_of_files() {
+        let config = Config {
+            file: Some(PathBuf::from("expressions.txt")),
+            ..Config::default()
+        };
+        let (output, error) = run_lines(config.clone(), "1+2*\\\n  (3+4) \\  \n-5\n6\n1\\2\n");
+
+        assert_eq!(output, "10\n6\n");
+        assert_eq!(error, "Error: not a valid expression\n");
+
+        let (output, error) = run_lines(config, "2\\\n");
+
+        assert_eq!(output, "2\n");
+        assert_eq!(error, "");
+
+        let (output, error) = run_lines(Config::default(), "1+2*\\\n3\n");
+
+        assert_eq!(output, "3\n");
+        assert_eq!(error, "Error: not a valid expression\n");
+    }
+
+    #[test]
+    fn prompts_interactive_input() {
+        let mut output = Vec::new();
+
+        run_session(
+            Config::default(),
+            "1+1\n".as_bytes(),
+            &mut output,
+            Vec::new(),
+            true,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "> 2\n> ");
+    }
+
+    #[test]
+    fn annotates_interactive_errors() {
+        let mut error = Vec::new();
+
+        run_session(
+            Config::default(),
+            "2+*3\n1/0\n".as_bytes(),
+            Vec::new(),
+            &mut error,
+            true,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(error).unwrap(),
+            "Error: unexpected operator '*' after '+'\n | 2+*3\n |  ^^\nError: division by zero\n"
+        );
+    }
+
+    #[test]
+    fn colors_results_and_errors() {
+        let mut output = Vec::new();
+        let mut error = Vec::new();
+
+        run_session(
+            Config::default(),
+            "1+1\n1/0\n".as_bytes(),
+            &mut output,
+            &mut error,
+            false,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "\x1b[32m2\x1b[0m\n");
+        assert_eq!(
+            String::from_utf8(error).unwrap(),
+            "\x1b[1;31mError: division by zero\x1b[0m\n"
+        );
+
+        let (output, error) = run_lines(Config::default(), "1+1\n1/0\n");
+
+        assert!(!output.contains('\x1b') && !error.contains('\x1b'));
+    }
+
+    #[test]
+    fn repeats_previous_expression() {
+        let (output, error) = run_lines(Config::default(), "!!\n2*21\n!!\n");
+
+        assert_eq!(output, "42\n42\n");
+        assert_eq!(error, "Error: no previous expression\n");
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let args = |args: &[&str]| {
+            Config::build(["calculator"].iter().chain(args).map(|arg| arg.to_string()))
+        };
+
+        let config = args(&["--check", "--file", "cases.txt"]).unwrap();
+
+        assert!(config.check);
+        assert_eq!(config.file, Some(PathBuf::from("cases.txt")));
+        assert!(!args(&[]).unwrap().check);
+        assert_eq!(args(&["--file", "cases.txt", "-"]).unwrap().file, None);
+        assert_eq!(
+            args(&["--output", "results.txt"]).unwrap().output,
+            OutputSink::File(PathBuf::from("results.txt"))
+        );
+        assert_eq!(args(&[]).unwrap().output, OutputSink::Stdout);
+        assert!(args(&["--output"]).is_err());
+        assert_eq!(
+            args(&["--fuzz", "10", "--seed", "42"]).unwrap().fuzz,
+            Some(10)
+        );
+        assert_eq!(args(&["--seed", "42"]).unwrap().seed, Some(42));
+        assert!(args(&["--fuzz", "many"]).is_err());
+        assert!(args(&["--seed"]).is_err());
+
+        assert_eq!(args(&[]).unwrap().color, ColorChoice::Auto);
+        assert_eq!(
+            args(&["--color", "always"]).unwrap().color,
+            ColorChoice::Always
+        );
+        assert_eq!(
+            args(&["--color", "never"]).unwrap().color,
+            ColorChoice::Never
+        );
+        assert!(args(&["--color"]).is_err());
+        assert!(args(&["--color", "sometimes"]).is_err());
+        assert!(args(&["--float"]).unwrap().float_mode);
+        assert!(!args(&["--integer"]).unwrap().float_mode);
+        assert!(args(&["--float", "--float"]).is_ok());
+        assert_eq!(
+            args(&["--integer", "--file", "cases.txt", "--float"])
+                .err()
+                .map(|error| error.to_string()),
+            Some("--integer and --float can't be used together".to_string())
+        );
+        assert_eq!(
+            args(&["-e", "1+1", "--float", "-e", "x = 2"])
+                .unwrap()
+                .expressions,
+            ["1+1", "x = 2"]
+        );
+        assert!(args(&["-e"]).is_err());
+        assert_eq!(args(&["1+1"]).unwrap().expressions, ["1+1"]);
+        assert_eq!(
+            args(&["--trace", "-3*2", "-e", "4"]).unwrap().expressions,
+            ["-3*2", "4"]
+        );
+        assert!(args(&["1+1", "2+2"]).is_err());
+        assert!(args(&["--frobnicate"]).is_err());
+        assert!(args(&["-x"]).is_err());
+        assert!(args(&["--trace"]).unwrap().trace);
+        assert!(args(&["--tree"]).unwrap().tree);
+        assert!(args(&["--roman"]).unwrap().roman_output);
+        assert!(args(&["--errors-to-stdout"]).unwrap().errors_to_stdout);
+        assert!(args(&["--tape"]).unwrap().tape);
+        assert!(args(&["--check-only"]).unwrap().check_only);
+        assert!(args(&["--strict"]).unwrap().strict_division);
+        assert!(args(&["--tape-file"]).is_err());
+        assert_eq!(args(&["--sci", "3"]).unwrap().scientific_digits, Some(3));
+
+        let config = args(&["--min-result", "-10", "--max-result", "10"]).unwrap();
+
+        assert_eq!(
+            (config.warn_below, config.warn_above),
+            (Some(-10), Some(10))
+        );
+        assert!(args(&["--max-result", "big"]).is_err());
+        assert!(args(&["--file"]).is_err());
+        assert!(args(&["--unknown"]).is_err());
+        assert_eq!(args(&["--bigint"]).is_ok(), cfg!(feature = "bigint"));
+    }
+
+    #[test]
+    fn checks_syntax_without_evaluating() {
+        let path =
+            std::env::temp_dir().join(format!("calculator-check-{}.txt", std::process::id()));
+
+        std::fs::write(&path, "1+1\n\n1/0\n2+*3\n(1\n").unwrap();
+
+        let config = Config {
+            check: true,
+            file: Some(path.clone()),
+            ..Config::default()
+        };
+        let input = open_input(&config).unwrap();
+        let mut output = Vec::new();
+        let mut error = Vec::new();
+
+        let result = run_with(config, input, &mut output, &mut error);
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "2 line(s) with syntax errors"
+        );
+        assert!(output.is_empty());
+
+        let error = String::from_utf8(error).unwrap();
+        let lines: Vec<&str> = error.lines().collect();
+
+        assert_eq!(lines.len(), 2, "{error}");
+        assert!(lines[0].starts_with("line 4: "));
+        assert!(lines[1].starts_with("line 5: "));
+
+        let (_, error) = run_lines(
+            Config {
+                check: true,
+                ..Config::default()
+            },
+            "1/0\n2*(3+4)\n",
+        );
+
+        assert_eq!(error, "");
+    }
+
+    #[test]
+    fn checks_only_after_commands() {
+        let config = Config {
+            check_only: true,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            run_lines(config.clone(), "def double(x) = x*2\ndouble(4)\n"),
+            (String::new(), String::new())
+        );
+
+        let mut error = Vec::new();
+        let result = run_with(
+            config,
+            "x = 2\ndel x\nx\n".as_bytes(),
+            Vec::new(),
+            &mut error,
+        );
+
+        assert!(result.is_err());
+        assert!(error.is_empty());
+    }
+
+    #[test]
+    fn checks_given_expressions() {
+        let config = Config {
+            check: true,
+            expressions: vec!["1+1".to_string(), "2+*3".to_string()],
+            ..Config::default()
+        };
+        let mut error = Vec::new();
+
+        let result = run_with(config, std::io::empty(), Vec::new(), &mut error);
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "1 given expression(s) with syntax errors"
+        );
+        assert!(String::from_utf8(error)
+            .unwrap()
+            .starts_with("expression 2: "));
+    }
+
+    #[test]
+    fn routes_errors() {
+        let input = "1+1\n1/0\n3*3\n";
+
+        assert_eq!(
+            run_lines(Config::default(), input),
+            (
+                "2\n9\n".to_string(),
+                "Error: division by zero\n".to_string()
+            )
+        );
+
+        let config = Config {
+            errors_to_stdout: true,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            run_lines(config, input),
+            ("2\nError: division by zero\n9\n".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn records_tape() {
+        let path = std::env::temp_dir().join(format!("calculator-tape-{}.txt", std::process::id()));
+        let config = Config {
+            tape_file: Some(path.clone()),
+            ..Config::default()
+        };
+        let tape = "1+2 = 3\nx = 4 = 4\n1/0 = Error: division by zero\nx*2 = 8\n";
+
+        let (output, _) = run_lines(config, "1+2\nx = 4\n1/0\nvars\nx*2\ntape\n");
+        let written = std::fs::read_to_string(&path).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(output, format!("3\n4\nx = 4\n8\n{tape}"));
+        assert_eq!(written, tape);
+
+        assert_eq!(
+            run_lines(Config::default(), "tape\n").1,
+            "Error: the tape is off\n"
+        );
+    }
+
+    #[test]
+    fn prints_scientific_notation() {
+        let config = Config {
+            scientific_digits: Some(3),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            run_lines(config, "10^9\n2^40\n12345\n").0,
+            "1e9\n~1.1e12\n12345\n"
+        );
+    }
+
+    #[test]
+    fn prints_roman_numerals() {
+        let config = Config {
+            roman_output: true,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            run_lines(config, "2+2\n1994\n4000\n0\n").0,
+            "IV\n\
+             MCMXCIV\n\
+             4000  (no Roman numeral, only 1 to 3999)\n\
+             0  (no Roman numeral, only 1 to 3999)\n"
+        );
+    }
+
+    #[test]
+    fn groups_digits() {
+        let config = Config {
+            group_output_digits: true,
+            ..Config::default()
+        };
+
+        assert_eq!(run_lines(config, "1000*1000\n").0, "1,000,000\n");
+
+        let config = Config {
+            group_output_digits: true,
+            digit_separator: Some('_'),
+            ..Config::default()
+        };
+
+        assert_eq!(run_lines(config, "-1234567\n").0, "-1_234_567\n");
+    }
+
+    #[test]
+    fn shows_operation_count() {
+        let config = Config {
+            show_operation_count: true,
+            ..Config::default()
+        };
+
+        let (output, _) = run_lines(config, "2+3*4\n(1+1)*(2+2)-3\n1+1\n5\n");
+
+        assert_eq!(output, "14  (2 ops)\n5  (4 ops)\n2  (1 op)\n5  (0 ops)\n");
+        assert_eq!(run_lines(Config::default(), "2+3*4\n").0, "14\n");
+    }
+
+    #[test]
+    fn echoes_input() {
+        let config = Config {
+            echo_input: true,
+            ..Config::default()
+        };
+
+        let (output, error) = run_lines(config, "2+3*4\n 1 + 1 \n!!\n1/0\n");
+
+        assert_eq!(output, "2+3*4 = 14\n1 + 1 = 2\n1 + 1 = 2\n");
+        assert_eq!(error, "Error: division by zero\n");
+    }
+
+    #[test]
+    fn silences_assignments() {
+        let config = Config {
+            silent_assignments: true,
+            ..Config::default()
+        };
+
+        let (output, error) = run_lines(config, "x = 5\nx*2\ny = 1/0\n");
+
+        assert_eq!(output, "10\n");
+        assert_eq!(error, "Error: division by zero\n");
+
+        let (output, _) = run_lines(Config::default(), "x = 5\nx*2\n");
+
+        assert_eq!(output, "5\n10\n");
+    }
+
+    #[test]
+    fn undoes_assignments() {
+        let (output, error) = run_lines(Config::default(), "x = 2\nx = 3\nundo\nx\nundo\nundo\n");
+
+        assert_eq!(
+            output,
+            "2\n3\nundid assignment to x\n2\nundid assignment to x\n"
+        );
+        assert_eq!(error, "Error: nothing to undo\n");
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn prints_big_integers() {
+        let config = Config {
+            bigint: true,
+            ..Config::default()
+        };
+        let (output, error) = run_lines(config, "2^64\n1/0\n-9223372036854775807-2\n");
+
+        assert_eq!(output, "18446744073709551616\n-9223372036854775809\n");
+        assert_eq!(error, "Error: division by zero\n");
+    }
+
+    #[test]
+    fn lists_variables() {
+        let (output, error) = run_lines(
+            Config::default(),
+            "y = 2\nx = y*3\nvars\ndel y\ndel y\nvars\n",
+        );
+
+        assert_eq!(output, "2\n6\nx = 6\ny = 2\nx = 6\n");
+        assert_eq!(error, "Error: unknown variable 'y'\n");
+    }
+
+    #[test]
+    fn resets_session() {
+        let (output, error) = run_lines(Config::default(), "x = 5\nx+$1\nreset\nx\n$1\n7\n");
+
+        assert_eq!(output, "5\n10\nsession reset\n7\n");
+        assert_eq!(error, "Error: unknown variable 'x'\nError: no result $1\n");
+    }
+
+    #[test]
+    fn writes_results_to_file() {
+        let directory = std::env::temp_dir();
+        let input = directory.join(format!("calculator-input-{}.txt", std::process::id()));
+        let output = directory.join(format!("calculator-output-{}.txt", std::process::id()));
+
+        std::fs::write(&input, "1+1\n1/0\n2*3\n").unwrap();
+
+        let result = run(Config {
+            file: Some(input.clone()),
+            output: OutputSink::File(output.clone()),
+            ..Config::default()
+        });
+        let written = std::fs::read_to_string(&output).unwrap();
+
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        result.unwrap();
+        assert_eq!(written, "2\n6\n");
+    }
+
+    #[test]
+    fn toggles_float_arithmetic() {
+        let (output, error) = run_lines(
+            Config::default(),
+            "1/2\nmode float\n1/2\nmode integer\n1/2\nmode complex\n",
+        );
+
+        assert_eq!(output, "0\nmode float\n0.5\nmode integer\n0\n");
+        assert_eq!(error, "Error: unknown mode 'complex'\n");
+    }
+
+    #[test]
+    fn defines_functions() {
+        let (output, error) = run_lines(
+            Config::default(),
+            "def double(x) = x*2\ndouble(21)\ndef abs(x) = x\ndouble(1, 2)\n",
+        );
+
+        assert_eq!(output, "defined double\n42\n");
+        assert_eq!(
+            error,
+            "Error: can't redefine builtin function 'abs'\nError: double expects 1 argument, got 2\n"
+        );
+    }
+
+    #[test]
+    fn runs_fuzz_batch() {
+        let mut output = Vec::new();
+
+        run_fuzz(
+            Config {
+                seed: Some(42),
+                ..Config::default()
+            },
+            25,
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "25 expressions with seed 42, 0 mismatch(es)\n"
+        );
+    }
+
+    #[test]
+    fn evaluates_over_ranges() {
+        let (output, error) = run_lines(
+            Config::default(),
+            "x^2 for x in 1..5\nx for x in 1..1\nx for x in 1..z\n",
+        );
+
+        assert_eq!(output, "[1, 4, 9, 16]\n[]\n");
+        assert_eq!(error, "Error: invalid range bound 'z'\n");
+    }
+
+    #[test]
+    fn skips_invalid_utf8_lines() {
+        let mut output = Vec::new();
+        let mut error = Vec::new();
+
+        run_with(
+            Config::default(),
+            &b"1+\xff\xfe\n2*3\n\xc3"[..],
+            &mut output,
+            &mut error,
+        )
+        .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "6\n");
+        assert_eq!(
+            String::from_utf8(error).unwrap(),
+            "warning: skipped a line which is not valid UTF-8\n".repeat(2)
+        );
+    }
+
+    #[test]
+    fn warns_on_truncation() {
+        let config = Config {
+            warn_on_truncation: true,
+            ..Config::default()
+        };
+
+        let (output, error) = run_lines(config, "6/2\n7/2\n");
+
+        assert_eq!(output, "3\n3\n");
+        assert_eq!(error, "warning: division truncated a remainder\n");
+
+        let (output, error) = run_lines(Config::default(), "7/2\n");
+
+        assert_eq!(output, "3\n");
+        assert_eq!(error, "");
+    }
+
+    #[test]
+    fn traces_evaluations() {
+        let config = Config {
+            trace: true,
+            expressions: vec!["2+3*(1+4/2)".to_string()],
+            ..Config::default()
+        };
+        let (output, error) = run_lines(config, "x = 2*3+1\n1/0\n");
+
+        assert_eq!(output, "11\n7\n");
+        assert_eq!(
+            error,
+            "trace: 2+3*(1+4/2)\n\
+             trace: 2+3*3\n\
+             trace: 2+9\n\
+             trace: 11\n\
+             trace: 2*3+1\n\
+             trace: 6+1\n\
+             trace: 7\n\
+             trace: 1/0\n\
+             Error: division by zero\n"
+        );
+    }
+
+    #[test]
+    fn prints_parse_trees() {
+        let config = Config {
+            tree: true,
+            ..Config::default()
+        };
+        let (output, error) = run_lines(config, "x = 2+3*4\nx*\n");
+
+        assert_eq!(output, "+\n|-- 2\n`-- *\n    |-- 3\n    `-- 4\n14\n");
+        assert!(error.starts_with("Error: "));
+    }
+
+    #[test]
+    fn warns_on_results_outside_range() {
+        let config = Config {
+            warn_below: Some(-10),
+            warn_above: Some(100),
+            ..Config::default()
+        };
+
+        let (output, error) = run_lines(config.clone(), "-10\n100\n-11\n10*10+1\n");
+
+        assert_eq!(output, "-10\n100\n-11\n101\n");
+        assert_eq!(
+            error,
+            "warning: result -11 is below -10\nwarning: result 101 is above 100\n"
+        );
+
+        let mut config = Config {
+            warn_above: Some(1),
+            ..config
+        };
+        config.set_float_arithmetic(true);
+
+        let (output, error) = run_lines(config, "1.0\n1.5\n");
+
+        assert_eq!(output, "1.0\n1.5\n");
+        assert_eq!(error, "warning: result 1.5 is above 1\n");
+    }
+}
@@ -0,0 +1,89 @@
+// Debug records of what the evaluator did, only compiled with the `logging` feature
+// Records go to the logger given to `set_logger`, or to the standard error if there is none and `RUST_LOG` is `debug` or `trace`
+
+use std::{fmt, sync::OnceLock};
+
+use anyhow::anyhow;
+
+// Same variable as the log crate uses, so turning on the records works the same way
+const LEVEL_VARIABLE: &str = "RUST_LOG";
+
+type Logger = Box<dyn Fn(&str) + Send + Sync>;
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+// Whether records without a logger are written to the standard error, only looked up once
+static LOG_TO_STDERR: OnceLock<bool> = OnceLock::new();
+
+/// Sends every debug record to the logger, which can only be set once
+pub fn set_logger(logger: impl Fn(&str) + Send + Sync + 'static) -> anyhow::Result<()> {
+    LOGGER
+        .set(Box::new(logger))
+        .map_err(|_| anyhow!("a logger is already set"))
+}
+
+pub(crate) fn debug(message: fmt::Arguments) {
+    if let Some(logger) = LOGGER.get() {
+        return logger(&message.to_string());
+    }
+
+    let enabled = LOG_TO_STDERR.get_or_init(|| {
+        std::env::var(LEVEL_VARIABLE)
+            .is_ok_and(|level| ["debug", "trace"].contains(&level.to_lowercase().as_str()))
+    });
+
+    if *enabled {
+        eprintln!("DEBUG {}: {message}", env!("CARGO_PKG_NAME"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::ExpressionEvaluator;
+
+    thread_local! {
+        // Other tests evaluate on their own threads at the same time, so only the records of this one are kept
+        static RECORDS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[test]
+    fn records_evaluation() {
+        set_logger(|record| RECORDS.with(|records| records.borrow_mut().push(record.to_string())))
+            .unwrap();
+
+        let evaluator = ExpressionEvaluator::new().unwrap();
+
+        evaluator.eval("2+3*4".to_string()).unwrap();
+
+        assert_eq!(
+            RECORDS.take(),
+            [
+                "evaluating '2+3*4'",
+                "validated as '2+3*4'",
+                "solver pass gave '2+12'",
+                "solver pass gave '14'"
+            ]
+        );
+
+        evaluator.eval("2^3>7".to_string()).unwrap();
+
+        assert_eq!(
+            RECORDS.take()[2..],
+            [
+                "exponentiation pass gave 'pow(2,3)>7'",
+                "solver pass gave '8>7'",
+                "solver pass gave '1'"
+            ]
+        );
+
+        assert!(evaluator.eval("1/0".to_string()).is_err());
+        assert_eq!(
+            RECORDS.take().last().unwrap(),
+            "evaluation of '1/0' failed: division by zero"
+        );
+
+        assert!(set_logger(|_| ()).is_err());
+    }
+}
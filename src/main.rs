@@ -1,10 +1,19 @@
 use basic_arithmetic_calculator as calculator;
 
 fn main() {
-    let config = calculator::Config;
+    let config = calculator::Config::build(std::env::args()).unwrap_or_else(|error| {
+        eprintln!("Problem parsing arguments: {error}");
+
+        std::process::exit(1);
+    });
+
+    // The exit code is the whole outcome of a check, so nothing is printed for it
+    let silent = config.check_only;
 
     if let Err(error) = calculator::run(config) {
-        eprintln!("Application error: {error}");
+        if !silent {
+            eprintln!("Application error: {error}");
+        }
 
         std::process::exit(1);
     }
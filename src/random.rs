@@ -0,0 +1,76 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// Pseudo-random but deterministic, the same seed gives the same numbers for rand and the fuzzer
+pub struct Random(u64);
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // A xorshift generator would only produce zeros from zero, the seed is mixed first so that only one seed of all leads there instead of both 0 and 1
+        Random(mix(seed).max(1))
+    }
+
+    // The hash keys of the standard library are random for every process, and differ for every call within one
+    pub fn from_entropy() -> Random {
+        Random::new(RandomState::new().build_hasher().finish())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// Both bounds included, the lower one can't be greater than the upper one
+    pub fn in_range(&mut self, lower: i64, upper: i64) -> i64 {
+        // Wraps around to zero when the range is every i64, then any value will do
+        let length = upper.abs_diff(lower).wrapping_add(1);
+
+        match length {
+            0 => self.next_u64() as i64,
+            length => lower.wrapping_add(self.below(length) as i64),
+        }
+    }
+}
+
+// The finalizer of splitmix64, close seeds like 1 and 2 give states which have nothing in common
+fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_numbers_in_range() {
+        let mut random = Random::new(7);
+
+        for _ in 0..100 {
+            assert!((1..=6).contains(&random.in_range(1, 6)));
+        }
+
+        assert_eq!(random.in_range(-3, -3), -3);
+        random.in_range(i64::MIN, i64::MAX);
+    }
+
+    #[test]
+    fn differs_for_every_seed() {
+        let first = |seed| Random::new(seed).next_u64();
+
+        assert_ne!(first(0), first(1));
+        assert_ne!(first(1), first(2));
+        assert_eq!(first(42), first(42));
+    }
+}
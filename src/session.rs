@@ -0,0 +1,762 @@
+use std::{borrow::Cow, cell::RefCell, collections::VecDeque};
+
+use anyhow::anyhow;
+
+use crate::{
+    expression::{format_error, format_evaluation, values_equivalent},
+    random::Random,
+    Config, Diagnostic, Evaluation, ExpressionEvaluator, Functions, Value, Variables,
+};
+
+const REPEAT_COMMAND: &str = "!!";
+const UNDO_COMMAND: &str = "undo";
+const VARIABLES_COMMAND: &str = "vars";
+const DELETE_COMMAND: &str = "del";
+const RESET_COMMAND: &str = "reset";
+const MODE_COMMAND: &str = "mode";
+const DEFINE_COMMAND: &str = "def";
+const EXIT_COMMAND: &str = "exit";
+const TAPE_COMMAND: &str = "tape";
+const RANGE_KEYWORDS: (&str, &str) = (" for ", " in ");
+pub(crate) const ASSIGNMENT_OPERATOR: char = '=';
+const RESULT_REFERENCE_PREFIX: char = '$';
+// Assignments older than this can't be undone anymore
+const UNDO_LIMIT: usize = 100;
+
+/// Line of a session which is not an expression
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command<'a> {
+    Undo,
+    ListVariables,
+    Delete(&'a str),
+    Reset,
+    Mode(&'a str),
+    /// Like `x^2 for x in 1..5`
+    EvalOverRange(&'a str),
+    /// Like `def double(x) = x*2`, without the keyword
+    Define(&'a str),
+    /// Ends the session like the end of the input
+    Exit,
+    /// Lists every line evaluated so far with its result, see `Config::tape`
+    Tape,
+}
+
+/// State kept between the lines of an interactive session
+///
+/// ```
+/// use basic_arithmetic_calculator::{Config, Session};
+///
+/// let mut session = Session::new(Config::default()).unwrap();
+///
+/// session.eval_line("x = 6").unwrap();
+/// assert_eq!(session.eval_line("x*7").unwrap().value.to_i64().unwrap(), 42);
+/// ```
+pub struct Session {
+    // Kept, so the evaluator can be built again when the mode changes
+    config: Config,
+    evaluator: ExpressionEvaluator,
+    previous_expression: Option<String>,
+    variables: Variables,
+    // Variable of every assignment with the value it had before, the most recent one last
+    undo_stack: VecDeque<(String, Option<Value>)>,
+    // Value of every successful line, `$1` refers to the first one
+    results: Vec<Value>,
+    functions: Functions,
+    // Numbers of rand, the same seed gives the same ones in every session
+    random: RefCell<Random>,
+    // Steps of the most recent evaluation, only recorded if the configuration asks for a trace
+    steps: RefCell<Vec<String>>,
+    // Every evaluated line like `2+3 = 5`, only recorded if the configuration asks for a tape
+    tape: Vec<String>,
+}
+
+impl Session {
+    pub fn new(config: Config) -> anyhow::Result<Self> {
+        Ok(Session {
+            evaluator: ExpressionEvaluator::with_config(config.clone())?,
+            random: RefCell::new(config.seed.map_or_else(Random::from_entropy, Random::new)),
+            config,
+            previous_expression: None,
+            variables: Variables::new(),
+            undo_stack: VecDeque::new(),
+            results: Vec::new(),
+            functions: Functions::new(),
+            steps: RefCell::new(Vec::new()),
+            tape: Vec::new(),
+        })
+    }
+
+    pub fn eval_line(&mut self, line: &str) -> anyhow::Result<Evaluation> {
+        let result = self.eval_unrecorded(line);
+
+        if self.config.tape || self.config.tape_file.is_some() {
+            let expr = self.previous_expression.as_deref().unwrap_or(line).trim();
+            let value = match &result {
+                Ok(evaluation) => format_evaluation(evaluation, &self.config),
+                Err(message) => format_error(message),
+            };
+
+            self.tape.push(format!("{expr} = {value}"));
+        }
+
+        result
+    }
+
+    fn eval_unrecorded(&mut self, line: &str) -> anyhow::Result<Evaluation> {
+        // Repeating re-evaluates the text of the previous expression, not just its result
+        let expr = if line.trim() == REPEAT_COMMAND {
+            self.previous_expression
+                .clone()
+                .ok_or_else(|| anyhow!("no previous expression"))?
+        } else {
+            line.to_string()
+        };
+
+        self.previous_expression = Some(expr.clone());
+        self.steps.get_mut().clear();
+
+        let trace = self.config.trace.then_some(&self.steps);
+
+        // The value of an assignment is the value it stored
+        let evaluation = match Session::assigned_variable(&expr) {
+            Some(name) if self.evaluator.constant(name).is_some() => {
+                return Err(anyhow!("can't assign to constant '{name}'"));
+            }
+            Some(name) => {
+                let (_, value_expr) = expr.split_once(ASSIGNMENT_OPERATOR).unwrap_or_default();
+                let evaluation = self.evaluator.eval_traced_in_session(
+                    value_expr,
+                    &self.named_values(value_expr),
+                    &self.functions,
+                    &self.random,
+                    trace,
+                )?;
+
+                let previous_value = self.variables.insert(name.to_string(), evaluation.value);
+
+                if self.undo_stack.len() == UNDO_LIMIT {
+                    self.undo_stack.pop_front();
+                }
+
+                self.undo_stack
+                    .push_back((name.to_string(), previous_value));
+
+                evaluation
+            }
+            None => self.evaluator.eval_traced_in_session(
+                &expr,
+                &self.named_values(&expr),
+                &self.functions,
+                &self.random,
+                trace,
+            )?,
+        };
+
+        self.results.push(evaluation.value);
+
+        Ok(evaluation)
+    }
+
+    // Parse tree of the expression of the line, of the value of an assignment
+    pub fn tree(&self, line: &str) -> anyhow::Result<String> {
+        let expr = match Session::assigned_variable(line) {
+            Some(_) => line.split_once(ASSIGNMENT_OPERATOR).unwrap_or_default().1,
+            None => line,
+        };
+
+        self.evaluator.tree(expr)
+    }
+
+    // Lines recorded so far, the oldest one first, a reset doesn't clear them as they are a record of the whole session
+    pub fn tape(&self) -> anyhow::Result<&[String]> {
+        if !self.config.tape && self.config.tape_file.is_none() {
+            return Err(anyhow!("the tape is off"));
+        }
+
+        Ok(&self.tape)
+    }
+
+    // Every form the most recent expression took on the way to its value, see `ExpressionEvaluator::eval_with_trace`
+    pub fn steps(&self) -> Vec<String> {
+        self.steps.borrow().clone()
+    }
+
+    // The variables, and the results too if the expression refers to any of them
+    fn named_values(&self, expr: &str) -> Cow<'_, Variables> {
+        if !expr.contains(RESULT_REFERENCE_PREFIX) {
+            return Cow::Borrowed(&self.variables);
+        }
+
+        let mut values = self.variables.clone();
+
+        for (index, value) in self.results.iter().enumerate() {
+            values.insert(format!("{RESULT_REFERENCE_PREFIX}{}", index + 1), *value);
+        }
+
+        Cow::Owned(values)
+    }
+
+    // Reverts the most recent assignment that wasn't undone yet, a variable it created is removed
+    // Returns the name of the variable
+    pub fn undo(&mut self) -> anyhow::Result<String> {
+        let (name, previous_value) = self
+            .undo_stack
+            .pop_back()
+            .ok_or_else(|| anyhow!("nothing to undo"))?;
+
+        match previous_value {
+            Some(value) => self.variables.insert(name.clone(), value),
+            None => self.variables.remove(&name),
+        };
+
+        Ok(name)
+    }
+
+    // Syntax problems of a line which is not an assignment, the variables of the session are not unknown identifiers
+    pub fn diagnostics(&self, line: &str) -> Vec<Diagnostic> {
+        if Session::assigned_variable(line).is_some() {
+            return Vec::new();
+        }
+
+        let mut diagnostics = self.evaluator.diagnostics(line);
+
+        diagnostics.retain(|diagnostic| {
+            !(diagnostic.message.starts_with("unknown identifier")
+                && self
+                    .named_values(line)
+                    .contains_key(&line[diagnostic.span.clone()]))
+        });
+
+        diagnostics
+    }
+
+    // Removes a variable, which can be undone like an assignment
+    pub fn delete(&mut self, name: &str) -> anyhow::Result<()> {
+        let value = self
+            .variables
+            .remove(name)
+            .ok_or_else(|| anyhow!("unknown variable '{name}'"))?;
+
+        if self.undo_stack.len() == UNDO_LIMIT {
+            self.undo_stack.pop_front();
+        }
+
+        self.undo_stack.push_back((name.to_string(), Some(value)));
+
+        Ok(())
+    }
+
+    // Forgets everything the previous lines left behind, like a new session with the same configuration
+    pub fn reset(&mut self) {
+        self.previous_expression = None;
+        self.variables.clear();
+        self.undo_stack.clear();
+        self.results.clear();
+        self.functions.clear();
+    }
+
+    // Defines a function, or replaces the definition of one, and returns its name
+    pub fn define(&mut self, definition: &str) -> anyhow::Result<String> {
+        let (name, function) = self.evaluator.define_function(definition)?;
+
+        self.functions.insert(name.clone(), function);
+
+        Ok(name)
+    }
+
+    // Switches between integer and float arithmetic, the variables are kept
+    pub fn set_mode(&mut self, mode: &str) -> anyhow::Result<()> {
+        let mut config = self.config.clone();
+
+        match mode {
+            "integer" => config.set_float_arithmetic(false),
+            "float" => config.set_float_arithmetic(true),
+            _ => return Err(anyhow!("unknown mode '{mode}'")),
+        }
+
+        self.evaluator = ExpressionEvaluator::with_config(config.clone())?;
+        self.config = config;
+
+        Ok(())
+    }
+
+    // Evaluates a line like `x^2 for x in 1..5` for every value of the variable
+    // Like in Rust, `a..b` doesn't include `b`, while `a..=b` does
+    pub fn eval_over_range(&self, line: &str) -> anyhow::Result<Vec<Value>> {
+        let invalid = || anyhow!("expected an expression like 'x^2 for x in 1..5'");
+        let (template, rest) = line.split_once(RANGE_KEYWORDS.0).ok_or_else(invalid)?;
+        let (variable, range) = rest.split_once(RANGE_KEYWORDS.1).ok_or_else(invalid)?;
+        let (start, end) = range.split_once("..").ok_or_else(invalid)?;
+
+        // A bound is an integer, or one of the constants, like in `x for x in MIN..=MIN`
+        let bound = |text: &str| {
+            let text = text.trim();
+
+            match self.evaluator.constant(text) {
+                Some(value) => value.to_i64(),
+                None => text.parse::<i64>().map_err(anyhow::Error::from),
+            }
+            .map_err(|_| anyhow!("invalid range bound '{text}'"))
+        };
+        let start = bound(start)?;
+        let range = match end.strip_prefix('=') {
+            Some(end) => start..=bound(end)?,
+            // There is no value before an end at MIN to stop at, so an empty range is known to be empty before it is written as an inclusive one
+            None => match bound(end)? {
+                end if end <= start => return Ok(Vec::new()),
+                end => start..=end - 1,
+            },
+        };
+
+        self.evaluator.eval_over_range_in_session(
+            template,
+            variable.trim(),
+            range,
+            &self.named_values(template),
+            &self.functions,
+            &self.random,
+        )
+    }
+
+    // Every variable with its value, sorted by name
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    pub fn command(line: &str) -> Option<Command<'_>> {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [UNDO_COMMAND] => Some(Command::Undo),
+            [VARIABLES_COMMAND] => Some(Command::ListVariables),
+            [DELETE_COMMAND, name] => Some(Command::Delete(name)),
+            [RESET_COMMAND] => Some(Command::Reset),
+            [EXIT_COMMAND] => Some(Command::Exit),
+            [TAPE_COMMAND] => Some(Command::Tape),
+            [MODE_COMMAND, mode] => Some(Command::Mode(mode)),
+            [DEFINE_COMMAND, ..] => line
+                .trim_start()
+                .strip_prefix(DEFINE_COMMAND)
+                .map(Command::Define),
+            _ if line.contains(RANGE_KEYWORDS.0) && line.contains(RANGE_KEYWORDS.1) => {
+                Some(Command::EvalOverRange(line))
+            }
+            _ => None,
+        }
+    }
+
+    // Same as command, but a variable named like a command of a single word, like `exit`, is evaluated instead
+    pub fn command_of<'a>(&self, line: &'a str) -> Option<Command<'a>> {
+        if self.variables.contains_key(line.trim()) {
+            return None;
+        }
+
+        Session::command(line)
+    }
+
+    // Name of the variable the line assigns to, if it is an assignment like `x = 5`
+    pub fn assigned_variable(line: &str) -> Option<&str> {
+        let (name, value) = line.split_once(ASSIGNMENT_OPERATOR)?;
+
+        // Like in `x == 1`, which is a comparison
+        if value.starts_with(ASSIGNMENT_OPERATOR) {
+            return None;
+        }
+
+        let name = name.trim();
+        let mut chars = name.chars();
+
+        let is_identifier = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        is_identifier.then_some(name)
+    }
+
+    // Whether the value is what the expected expression evaluates to with the variables of the session, a float only has to be close
+    pub fn is_expected(&self, value: Value, expected: &str) -> anyhow::Result<bool> {
+        let expected = self
+            .evaluator
+            .eval_with_variables(expected, &self.named_values(expected))?;
+
+        Ok(values_equivalent(value, expected.value))
+    }
+
+    // Text of the last evaluated expression as it was typed, with a repeat already resolved
+    pub fn previous_expression(&self) -> Option<&str> {
+        self.previous_expression.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_line(session: &mut Session, line: &str) -> anyhow::Result<i64> {
+        session.eval_line(line)?.value.to_i64()
+    }
+
+    #[test]
+    fn repeats_previous_expression() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        assert_eq!(eval_line(&mut session, "2+3*4").unwrap(), 14);
+        assert_eq!(eval_line(&mut session, "!!").unwrap(), 14);
+        assert_eq!(eval_line(&mut session, "  !!\n").unwrap(), 14);
+
+        assert_eq!(eval_line(&mut session, "1-1").unwrap(), 0);
+        assert_eq!(eval_line(&mut session, "!!").unwrap(), 0);
+
+        assert!(eval_line(&mut session, "1/0").is_err());
+        assert!(eval_line(&mut session, "!!").is_err());
+    }
+
+    #[test]
+    fn assigns_variables() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        assert_eq!(eval_line(&mut session, "x = 5").unwrap(), 5);
+        assert_eq!(eval_line(&mut session, "y=x*2").unwrap(), 10);
+        assert_eq!(eval_line(&mut session, "x + y").unwrap(), 15);
+        assert_eq!(eval_line(&mut session, "x = -x").unwrap(), -5);
+        assert_eq!(eval_line(&mut session, "x^2 - x").unwrap(), 30);
+        assert_eq!(eval_line(&mut session, "abs(x)").unwrap(), 5);
+
+        assert!(eval_line(&mut session, "z + 1").is_err());
+        assert!(eval_line(&mut session, "1 = 2").is_err());
+        assert!(eval_line(&mut session, "z = ").is_err());
+        assert!(eval_line(&mut session, "z").is_err());
+        assert_eq!(
+            eval_line(&mut session, "MAX = 1").unwrap_err().to_string(),
+            "can't assign to constant 'MAX'"
+        );
+    }
+
+    #[test]
+    fn takes_absolute_values_of_names() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        assert_eq!(eval_line(&mut session, "x = -3").unwrap(), -3);
+        assert_eq!(eval_line(&mut session, "|x|").unwrap(), 3);
+        assert_eq!(eval_line(&mut session, "|MAX|").unwrap(), i64::MAX);
+        assert_eq!(eval_line(&mut session, "|x - |x||").unwrap(), 6);
+
+        session.define("sq(x) = x*x").unwrap();
+        assert_eq!(eval_line(&mut session, "sq(x)+|x|").unwrap(), 12);
+        assert_eq!(eval_line(&mut session, "|$1|*2").unwrap(), 6);
+    }
+
+    #[test]
+    fn undoes_assignments() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        assert!(session.undo().is_err());
+
+        assert_eq!(eval_line(&mut session, "x = 5").unwrap(), 5);
+        assert_eq!(eval_line(&mut session, "y = 1").unwrap(), 1);
+        assert_eq!(eval_line(&mut session, "x = 7").unwrap(), 7);
+        assert_eq!(eval_line(&mut session, "x = x*2").unwrap(), 14);
+
+        assert_eq!(session.undo().unwrap(), "x");
+        assert_eq!(eval_line(&mut session, "x").unwrap(), 7);
+        assert_eq!(session.undo().unwrap(), "x");
+        assert_eq!(eval_line(&mut session, "x + y").unwrap(), 6);
+        assert_eq!(session.undo().unwrap(), "y");
+        assert!(eval_line(&mut session, "y").is_err());
+        assert_eq!(session.undo().unwrap(), "x");
+        assert!(eval_line(&mut session, "x").is_err());
+
+        assert!(session.undo().is_err());
+    }
+
+    #[test]
+    fn lists_and_deletes_variables() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        for line in ["total = 10", "b = 2", "a = total/b", "b = 3"] {
+            eval_line(&mut session, line).unwrap();
+        }
+
+        let listing = |session: &Session| {
+            session
+                .variables()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(listing(&session), ["a=5", "b=3", "total=10"]);
+
+        session.delete("b").unwrap();
+        assert_eq!(listing(&session), ["a=5", "total=10"]);
+        assert!(session.delete("b").is_err());
+        assert!(eval_line(&mut session, "b").is_err());
+
+        assert_eq!(session.undo().unwrap(), "b");
+        assert_eq!(listing(&session), ["a=5", "b=3", "total=10"]);
+    }
+
+    #[test]
+    fn refers_to_previous_results() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        assert!(eval_line(&mut session, "$1").is_err());
+        assert_eq!(eval_line(&mut session, "2+3").unwrap(), 5);
+        assert!(eval_line(&mut session, "1/0").is_err());
+        assert_eq!(eval_line(&mut session, "x = $1 * 2").unwrap(), 10);
+        assert_eq!(eval_line(&mut session, "$2 - $1 + x").unwrap(), 15);
+        assert_eq!(eval_line(&mut session, "$3").unwrap(), 15);
+        assert_eq!(
+            eval_line(&mut session, "$9").unwrap_err().to_string(),
+            "no result $9"
+        );
+        assert!(eval_line(&mut session, "2$1").is_err());
+        assert!(session.diagnostics("$1 + $2").is_empty());
+        assert_eq!(session.diagnostics("$1 + $7").len(), 1);
+    }
+
+    #[test]
+    fn resets_state() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        eval_line(&mut session, "x = 4").unwrap();
+        eval_line(&mut session, "x * 2").unwrap();
+        session.reset();
+
+        assert!(eval_line(&mut session, "x").is_err());
+        assert!(eval_line(&mut session, "$1").is_err());
+        assert!(session.undo().is_err());
+        assert_eq!(session.variables().count(), 0);
+
+        session.reset();
+        assert!(eval_line(&mut session, "!!").is_err());
+        assert_eq!(eval_line(&mut session, "1+1").unwrap(), 2);
+        assert_eq!(eval_line(&mut session, "$1").unwrap(), 2);
+    }
+
+    #[test]
+    fn switches_modes() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        eval_line(&mut session, "x = 7").unwrap();
+        session.set_mode("float").unwrap();
+        assert_eq!(session.eval_line("x/2").unwrap().value, Value::Float(3.5));
+        assert_eq!(session.eval_line("1.5*2").unwrap().value, Value::Float(3.0));
+
+        session.set_mode("integer").unwrap();
+        assert_eq!(eval_line(&mut session, "x/2").unwrap(), 3);
+        assert!(eval_line(&mut session, "1.5*2").is_err());
+        assert!(session.set_mode("complex").is_err());
+    }
+
+    #[test]
+    fn reproduces_random_numbers_from_seed() {
+        let rolls = |seed| {
+            let mut session = Session::new(Config {
+                seed,
+                ..Config::default()
+            })
+            .unwrap();
+
+            (0..20)
+                .map(|_| eval_line(&mut session, "rand(1, 6)").unwrap())
+                .collect::<Vec<i64>>()
+        };
+
+        assert_eq!(rolls(Some(42)), rolls(Some(42)));
+        assert_ne!(rolls(Some(42)), rolls(Some(43)));
+        assert!(rolls(None).iter().all(|roll| (1..=6).contains(roll)));
+
+        let mut session = Session::new(Config::default()).unwrap();
+
+        assert_eq!(eval_line(&mut session, "rand(3, 3)").unwrap(), 3);
+        assert_eq!(
+            eval_line(&mut session, "rand(6, 1)")
+                .unwrap_err()
+                .to_string(),
+            "rand lower bound 6 is greater than upper bound 1"
+        );
+        assert!(eval_line(&mut session, "rand(1)").is_err());
+    }
+
+    #[test]
+    fn evaluates_over_ranges() {
+        let mut session = Session::new(Config::default()).unwrap();
+        let over = |session: &Session, line| {
+            session.eval_over_range(line).map(|values| {
+                values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            over(&session, "x^2 for x in 1..5").unwrap(),
+            ["1", "4", "9", "16"]
+        );
+        assert_eq!(
+            over(&session, "x^2 for x in 1..=5").unwrap(),
+            ["1", "4", "9", "16", "25"]
+        );
+
+        eval_line(&mut session, "k = 3").unwrap();
+        assert_eq!(over(&session, "k*n for n in -1 .. 1").unwrap(), ["-3", "0"]);
+        assert!(over(&session, "x for x in 1...5").is_err());
+        assert!(over(&session, "x for x in a..5").is_err());
+        assert!(over(&session, "x for x in 1").is_err());
+        assert_eq!(over(&session, "k for k in 1..3").unwrap(), ["1", "2"]);
+        assert!(over(&session, "x for y in 1..3").is_err());
+        assert!(over(&session, "x for x in 3..3").unwrap().is_empty());
+        assert!(over(&session, "x for x in 3..1").unwrap().is_empty());
+        assert!(over(&session, "x for x in 3..=2").unwrap().is_empty());
+        assert_eq!(
+            over(&session, "x for x in MIN..=MIN").unwrap(),
+            ["-9223372036854775808"]
+        );
+        assert!(over(&session, "x for x in MIN..MIN").unwrap().is_empty());
+        assert!(over(&session, "x for x in MAX..MIN").unwrap().is_empty());
+        assert_eq!(
+            over(
+                &session,
+                "x for x in -9223372036854775808..-9223372036854775806"
+            )
+            .unwrap(),
+            ["-9223372036854775808", "-9223372036854775807"]
+        );
+
+        session.define("sq(x) = x*x").unwrap();
+        assert_eq!(
+            over(&session, "sq(x) for x in 1..4").unwrap(),
+            ["1", "4", "9"]
+        );
+        assert_eq!(over(&session, "x*$1 for x in 1..3").unwrap(), ["3", "6"]);
+        assert!(over(&session, "x*$9 for x in 1..3").is_err());
+
+        let seeded = || {
+            Session::new(Config {
+                seed: Some(42),
+                ..Config::default()
+            })
+            .unwrap()
+        };
+        let mut rolled = seeded();
+        let rolls = (0..3)
+            .map(|_| eval_line(&mut rolled, "rand(1, 1000)").unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            over(&seeded(), "rand(1, 1000) for x in 0..3").unwrap(),
+            rolls
+        );
+    }
+
+    #[test]
+    fn recognizes_commands() {
+        assert_eq!(Session::command(" undo\n"), Some(Command::Undo));
+        assert_eq!(Session::command("vars"), Some(Command::ListVariables));
+        assert_eq!(Session::command("del  x\n"), Some(Command::Delete("x")));
+        assert_eq!(Session::command("undo = 1"), None);
+        assert_eq!(Session::command("reset"), Some(Command::Reset));
+        assert_eq!(Session::command(" exit\n"), Some(Command::Exit));
+        assert_eq!(Session::command("mode float"), Some(Command::Mode("float")));
+        assert_eq!(
+            Session::command("x for x in 1..3"),
+            Some(Command::EvalOverRange("x for x in 1..3"))
+        );
+        assert_eq!(
+            Session::command(" def double(x) = x*2\n"),
+            Some(Command::Define(" double(x) = x*2\n"))
+        );
+        assert_eq!(Session::command("del"), None);
+        assert_eq!(Session::command("default = 2"), None);
+        assert_eq!(Session::command("2+2"), None);
+    }
+
+    #[test]
+    fn defines_functions() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        assert_eq!(session.define(" double(x) = x*2").unwrap(), "double");
+        assert_eq!(eval_line(&mut session, "double(21)").unwrap(), 42);
+        assert_eq!(eval_line(&mut session, "double(double(1+1))").unwrap(), 8);
+
+        session.define("hyp(a, b) = a^2 + b^2").unwrap();
+        eval_line(&mut session, "a = 100").unwrap();
+        assert_eq!(eval_line(&mut session, "hyp(3, 4) + a").unwrap(), 125);
+        assert_eq!(eval_line(&mut session, "y = double(hyp(1, 1))").unwrap(), 4);
+
+        session
+            .define("fact(n) = if(n <= 1, 1, n*fact(n-1))")
+            .unwrap();
+        assert_eq!(eval_line(&mut session, "fact(10)").unwrap(), 3628800);
+
+        session.define("double(x) = x*x").unwrap();
+        assert_eq!(eval_line(&mut session, "double(5)").unwrap(), 25);
+
+        assert_eq!(
+            eval_line(&mut session, "double(1, 2)")
+                .unwrap_err()
+                .to_string(),
+            "double expects 1 argument, got 2"
+        );
+        assert_eq!(
+            eval_line(&mut session, "hyp(1)").unwrap_err().to_string(),
+            "hyp expects 2 arguments, got 1"
+        );
+
+        session.define("forever(n) = forever(n+1)").unwrap();
+        assert_eq!(
+            eval_line(&mut session, "forever(1)")
+                .unwrap_err()
+                .to_string(),
+            "function calls nested too deeply"
+        );
+
+        assert_eq!(
+            session.define("pow(x, y) = x").unwrap_err().to_string(),
+            "can't redefine builtin function 'pow'"
+        );
+        assert!(session.define("broken(x) = x+").is_err());
+        assert!(session.define("broken(x) = x#2").is_err());
+
+        session.reset();
+        assert_eq!(
+            eval_line(&mut session, "double(1)")
+                .unwrap_err()
+                .to_string(),
+            "unknown function 'double'"
+        );
+    }
+
+    #[test]
+    fn diagnoses_lines() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        eval_line(&mut session, "x = 1").unwrap();
+
+        assert!(session.diagnostics("x + 1").is_empty());
+        assert!(session.diagnostics("y = 1 +").is_empty());
+        assert_eq!(session.diagnostics("x + y").len(), 1);
+        assert_eq!(session.diagnostics("2+*x")[0].span, 1..3);
+    }
+
+    #[test]
+    fn recognizes_assignments() {
+        assert_eq!(Session::assigned_variable("x = 5"), Some("x"));
+        assert_eq!(Session::assigned_variable(" total_2=1"), Some("total_2"));
+        assert_eq!(Session::assigned_variable("2x = 5"), None);
+        assert_eq!(Session::assigned_variable("x + 5"), None);
+        assert_eq!(Session::assigned_variable("= 5"), None);
+        assert_eq!(Session::assigned_variable("x == 5"), None);
+        assert_eq!(Session::assigned_variable("x = y == 5"), Some("x"));
+    }
+
+    #[test]
+    fn rejects_repeat_without_history() {
+        let mut session = Session::new(Config::default()).unwrap();
+
+        assert!(eval_line(&mut session, "!!").is_err());
+        assert_eq!(eval_line(&mut session, "5").unwrap(), 5);
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+fn run_calculator(args: &[&str], input: &str) -> (String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_basic_arithmetic_calculator"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn evaluates_piped_lines() {
+    for args in [&[][..], &["-"][..]] {
+        let (output, error) = run_calculator(args, "1+1\n2*3\n1/0\n10-4\n");
+
+        assert_eq!(output, "2\n6\n6\n");
+        assert_eq!(error, "Error: division by zero\n");
+    }
+}
+
+#[test]
+fn refers_to_previous_results() {
+    let (output, error) = run_calculator(&[], "1+1\n2*3\n10\n$1+$2\n$7\n");
+
+    assert_eq!(output, "2\n6\n10\n8\n");
+    assert_eq!(error, "Error: no result $7\n");
+}
+
+#[test]
+fn checks_expectations_of_a_file() {
+    let output = Command::new(env!("CARGO_BIN_EXE_basic_arithmetic_calculator"))
+        .args(["--file", "tests/data/expectations.txt"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "5\n4\n16\n3\n2\n9\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "expectation not met: 7/2 => 4\n\
+         expectation not met: 10-1 => 2*4\n\
+         Application error: 2 expectation(s) not met\n"
+    );
+}
+
+#[test]
+fn colors_only_when_asked_for_if_piped() {
+    for args in [&[][..], &["--color", "never"][..], &["--color", "auto"][..]] {
+        let (output, error) = run_calculator(args, "1+1\n1/0\n");
+
+        assert_eq!(output, "2\n");
+        assert_eq!(error, "Error: division by zero\n");
+    }
+
+    let (output, error) = run_calculator(&["--color", "always"], "1+1\n1/0\n");
+
+    assert_eq!(output, "\x1b[32m2\x1b[0m\n");
+    assert_eq!(error, "\x1b[1;31mError: division by zero\x1b[0m\n");
+}
+
+#[test]
+fn evaluates_expressions_of_arguments() {
+    let output = Command::new(env!("CARGO_BIN_EXE_basic_arithmetic_calculator"))
+        .args(["-e", "1+1", "-e", "2*", "-e", "2*2"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2\n4\n");
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .ends_with("Application error: 1 given expression(s) failed\n"));
+
+    let (output, error) = run_calculator(&["-e", "3-1"], "");
+
+    assert_eq!(output, "2\n");
+    assert_eq!(error, "");
+}
+
+#[test]
+fn checks_only_exit_code() {
+    for (expr, success) in [
+        ("1+1", true),
+        ("x = 2", true),
+        ("1/0", false),
+        ("2*", false),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_basic_arithmetic_calculator"))
+            .args(["--check-only", "-e", expr])
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.success(), success, "{expr}");
+        assert_eq!(output.status.code(), Some(if success { 0 } else { 1 }));
+        assert!(output.stdout.is_empty());
+        assert!(output.stderr.is_empty());
+    }
+
+    let (output, error) = run_calculator(&["--check-only"], "x = 3\nvars\n\nx*2\n");
+
+    assert_eq!((output, error), (String::new(), String::new()));
+}
+
+#[test]
+fn checks_syntax_of_full_width_expressions() {
+    let output = Command::new(env!("CARGO_BIN_EXE_basic_arithmetic_calculator"))
+        .args(["--check", "-e", "２＋３×（４－１）"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_basic_arithmetic_calculator"))
+        .args(["--check", "-e", "２＋"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "expression 1: unexpected end of expression\n\
+         Application error: 1 given expression(s) with syntax errors\n"
+    );
+}
+
+#[test]
+fn evaluates_positional_expression() {
+    let (output, error) = run_calculator(&["1+1"], "");
+
+    assert_eq!((output, error), ("2\n".to_string(), String::new()));
+
+    let (output, error) = run_calculator(&["--trace", "2+3*(1+4/2)"], "");
+
+    assert_eq!(output, "11\n");
+    assert_eq!(
+        error,
+        "trace: 2+3*(1+4/2)\ntrace: 2+3*3\ntrace: 2+9\ntrace: 11\n"
+    );
+}
@@ -0,0 +1,22 @@
+// Property based tests: random expression trees are rendered to text, and the evaluator has to agree with a direct evaluation of the tree
+
+use basic_arithmetic_calculator::{fuzz::Expression, random::Random, ExpressionEvaluator};
+
+const CASES: usize = 2000;
+
+#[test]
+fn evaluates_like_the_expression_tree() {
+    let evaluator = ExpressionEvaluator::new().unwrap();
+    let mut random = Random::new(0x9e37_79b9_7f4a_7c15);
+
+    for _ in 0..CASES {
+        let tree = Expression::generate(&mut random);
+        let expr = tree.render();
+
+        assert_eq!(
+            evaluator.eval(expr.clone()).ok(),
+            tree.evaluate(),
+            "{expr} from {tree:?}"
+        );
+    }
+}